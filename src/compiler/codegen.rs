@@ -1,8 +1,10 @@
 use std::collections::BTreeMap;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::error;
 use std::fmt;
 use std::io;
+use std::iter;
 use std::rc::Rc;
 use std::string;
 
@@ -10,11 +12,21 @@ use serde_json::Value;
 use super::*;
 use template::Template;
 
-const VOID_ELEMENTS: [&'static str; 13] = ["area", "base", "br", "col", "hr", "img", "input",
-                                           "link", "meta", "command", "keygen", "source",
-                                           "!DOCTYPE"];
 pub type CodegenResult = Result<String, CodegenError>;
 
+/// The key, or index, and value of each item an `@each` loop renders.
+type LoopItems = Box<Iterator<Item = (Value, Value)>>;
+
+/// The function that renders the children passed to a component, as `$children()`.
+const CHILDREN_MARKER: &'static str = "children";
+
+/// The name of the variable holding the position within an `@each` loop.
+const LOOP_VARIABLE: &'static str = "loop";
+
+/// Attributes whose values are URLs.
+const URL_ATTRIBUTES: [&'static str; 6] = ["action", "formaction", "href", "poster", "src",
+                                           "xlink:href"];
+
 macro_rules! html_try {
     ($result:expr) => {
         if let Err(io_error) = $result {
@@ -24,45 +36,172 @@ macro_rules! html_try {
 }
 
 pub struct Codegen {
-    elements: Vec<AstResult>,
+    elements: Vec<Token>,
     variables: BTreeMap<String, Value>,
     parent: Rc<RefCell<Template>>,
+    /// The rendered children passed to the component being rendered.
+    children: String,
+    /// The names of the components currently being rendered, outermost first.
+    expanding: Vec<String>,
+    /// The kind of content currently being written, which decides how variables are escaped.
+    context: Cell<Context>,
+    /// How deeply the current element is nested when pretty printing, or `None` when the
+    /// output is compact.
+    depth: Cell<Option<usize>>,
+    /// Whether the whitespace in text is being minified, which it isn't within elements that
+    /// keep their whitespace, such as `pre`.
+    minify: Cell<bool>,
+}
+
+/// Options changing how the HTML is rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// Whether variables are escaped based on where they're written, such as within a script,
+    /// or a URL. Otherwise every variable is escaped as HTML. Defaults to `true`.
+    pub contextual_escaping: bool,
+    /// Whether each element is written on its own line, with its children indented. Elements
+    /// containing text are kept on one line, so no whitespace is added to the text. Defaults
+    /// to `false`.
+    pub pretty: bool,
+    /// The indentation written for each level of nesting when pretty printing. Defaults to
+    /// four spaces.
+    pub indent: String,
+    /// Whether a missing variable checked by `@if`, or `@switch` is an error, rather than
+    /// false, and whether a call of a component, or function that doesn't exist is an error
+    /// before rendering, even within a branch that wouldn't be rendered. Defaults to `false`.
+    pub strict: bool,
+    /// Whether void elements are closed as XHTML, as in `<br />`. Defaults to `false`.
+    pub xhtml: bool,
+    /// Whether the whitespace in text is collapsed into single spaces, and the whitespace
+    /// around block elements, such as `div`, or `p` is removed. Elements that keep their
+    /// whitespace, such as `pre`, are left as is. Defaults to `false`.
+    pub minify: bool,
+    /// The elements that can't have any children, and are written without a closing tag.
+    /// Defaults to the void elements of HTML, and can be replaced when writing another
+    /// vocabulary, such as SVG. Giving a void element children is an error when parsing.
+    pub void_elements: Vec<String>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            contextual_escaping: true,
+            pretty: false,
+            indent: String::from("    "),
+            strict: false,
+            xhtml: false,
+            minify: false,
+            void_elements: VOID_ELEMENTS.iter().map(|tag| String::from(*tag)).collect(),
+        }
+    }
+}
+
+/// The kind of content an element contains.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Context {
+    Html,
+    Script,
+    Style,
 }
 
 impl Codegen {
-    pub fn new(ast: Vec<AstResult>,
+    pub fn new(ast: Vec<Token>,
                json: BTreeMap<String, Value>,
                parent: Rc<RefCell<Template>>)
                -> Self {
+        let depth = if parent.borrow().get_render_options().pretty {
+            Some(0)
+        } else {
+            None
+        };
+        let minify = parent.borrow().get_render_options().minify;
+
         Codegen {
             elements: ast,
             variables: json,
             parent: parent,
+            children: String::new(),
+            expanding: Vec::new(),
+            context: Cell::new(Context::Html),
+            depth: Cell::new(depth),
+            minify: Cell::new(minify),
         }
     }
 
-    pub fn render_component(ast: Vec<AstResult>,
-                            json: BTreeMap<String, Value>,
-                            parent: Rc<RefCell<Template>>)
-                            -> CodegenResult {
-        Codegen {
-            elements: ast,
-            variables: json,
-            parent: parent,
+    pub fn generate_html(&mut self) -> CodegenResult {
+        self.render_tokens(&self.elements)
+    }
+
+    /// Writes the HTML into `out` as the AST is walked, rather than building it up in memory.
+    pub fn write_html(&mut self, out: &mut io::Write) -> Result<(), CodegenError> {
+        self.write_tokens(&self.elements, out)
+    }
+
+    /// Renders the tokens into a String, for the places that need the whole output at once.
+    fn render_tokens(&self, tokens: &[Token]) -> CodegenResult {
+        let mut html = Vec::new();
+        match self.write_tokens(tokens, &mut html) {
+            Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+
+        match String::from_utf8(html) {
+            Ok(html) => Ok(html),
+            Err(error) => Err(CodegenError::FromUtf8Error(error)),
         }
-        .generate_html()
     }
 
-    pub fn generate_html(&mut self) -> CodegenResult {
-        let mut html = String::new();
+    /// Writes each of the tokens. Text directly after an element written with `<` has the
+    /// whitespace at its start trimmed.
+    fn write_tokens(&self, tokens: &[Token], out: &mut io::Write) -> Result<(), CodegenError> {
+        self.write_tokens_within(tokens, false, out)
+    }
 
-        for element in &self.elements {
-            match self.render(element) {
-                Ok(string) => html.push_str(&*string),
-                Err(error) => return Err(error),
+    /// Writes each of the tokens. When minifying, each run of whitespace in text is collapsed
+    /// into a space, and the whitespace next to a block element is removed, along with the
+    /// whitespace at either end of the tokens if they're the children of a block element.
+    fn write_tokens_within(&self,
+                           tokens: &[Token],
+                           in_block: bool,
+                           out: &mut io::Write)
+                           -> Result<(), CodegenError> {
+        let minify = self.minify.get();
+        let mut trim = false;
+        for (index, token) in tokens.iter().enumerate() {
+            let result = match *token {
+                Token::Text(ref text) if minify => {
+                    let mut text = collapse_whitespace(text);
+                    let after_block = match index.checked_sub(1) {
+                        Some(previous) => is_block(&tokens[previous]),
+                        None => in_block,
+                    };
+                    let before_block = match tokens.get(index + 1) {
+                        Some(next) => is_block(next),
+                        None => in_block,
+                    };
+                    if trim || after_block {
+                        text = text.trim_left().to_owned();
+                    }
+                    if before_block {
+                        text = text.trim_right().to_owned();
+                    }
+                    self.write(&Token::Text(text), out)
+                }
+                Token::Text(ref text) if trim => {
+                    self.write(&Token::Text(text.trim_left().to_owned()), out)
+                }
+                ref token => self.write(token, out),
+            };
+            if let Err(error) = result {
+                return Err(error.at(token.span()));
             }
+
+            trim = match *token {
+                Token::Html(ref element) => element.trims_after(),
+                _ => false,
+            };
         }
-        Ok(html)
+        Ok(())
     }
 
     pub fn call_component(component: &Component,
@@ -74,123 +213,519 @@ impl Codegen {
                 elements: component.ast(),
                 variables: arg_map,
                 parent: parent.clone(),
+                children: String::new(),
+                expanding: Vec::new(),
+                context: Cell::new(Context::Html),
+                depth: Cell::new(None),
+                minify: Cell::new(parent.borrow().get_render_options().minify),
             }
         } else {
             Codegen {
                 elements: component.ast(),
                 variables: BTreeMap::new(),
                 parent: parent.clone(),
+                children: String::new(),
+                expanding: Vec::new(),
+                context: Cell::new(Context::Html),
+                depth: Cell::new(None),
+                minify: Cell::new(parent.borrow().get_render_options().minify),
             }
         }
         .generate_html()
     }
 
-    fn generate_from_component(&self, component_call: ComponentCall) -> CodegenResult {
+    fn write_component(&self,
+                       component_call: &ComponentCall,
+                       out: &mut io::Write)
+                       -> Result<(), CodegenError> {
+        if let Some(start) = self.expanding.iter().position(|name| name == component_call.name()) {
+            let mut cycle = self.expanding[start..].to_vec();
+            cycle.push(String::from(component_call.name()));
+            let cycle = cycle.join(" -> ");
+            return Err(CodegenError::RecursiveComponent(component_call.span(), cycle));
+        }
+
         let parent = self.parent.borrow();
+        if self.expanding.len() >= parent.max_component_depth() {
+            let mut chain = self.expanding.clone();
+            chain.push(String::from(component_call.name()));
+            let chain = chain.join(" -> ");
+            return Err(CodegenError::RecursiveComponent(component_call.span(), chain));
+        }
+
         if let Some(component) = parent.get_component(component_call.name()) {
             let args = component.args();
+            let defaults = component.defaults();
             let arg_values = component_call.values();
             let mut arg_map = BTreeMap::new();
-            if args.len() == arg_values.len() {
-                let zipped = args.iter().zip(arg_values.iter());
-
-                for (ref arg, ref value) in zipped {
-
-                    match **arg {
-                        ArgKey::Json(ref arg_name) => {
-                            if let ArgKey::Json(ref arg_value) = **value {
-                                let value = match self.get_variable(arg_value) {
-                                    Ok(value) => value,
-                                    Err(error) => return Err(error),
-                                };
-                                arg_map.insert(arg_name.clone(), value);
-                            }
-                        }
-                        ArgKey::Comp(ref name) => {
-                            return Err(CodegenError::CompPassedToComp(name.clone()))
-                        }
+
+            let named_values = component_call.named_values();
+
+            let span = component_call.span();
+            if arg_values.len() > args.len() {
+                return Err(CodegenError::ArgumentCountMismatch {
+                    component: String::from(component.name()),
+                    expected: args.len(),
+                    found: arg_values.len(),
+                    span: span,
+                });
+            }
+
+            for name in named_values.keys() {
+                match args.iter().position(|arg| arg.value() == *name) {
+                    Some(index) if index < arg_values.len() => {
+                        return Err(CodegenError::DuplicateArgument(span, name.clone()))
                     }
+                    Some(_) => {}
+                    None => return Err(CodegenError::NoSuchArgument(span, name.clone())),
                 }
-                Codegen::render_component(component.ast(), arg_map, self.parent.clone())
-            } else {
-                Err(CodegenError::WrongNumberOfArguments(args.len(), arg_values.len()))
             }
+
+            for (index, arg) in args.iter().enumerate() {
+                let arg_name = match *arg {
+                    ArgKey::Json(ref arg_name) => arg_name,
+                    ArgKey::Comp(_) | ArgKey::Literal(_) => {
+                        return Err(CodegenError::CompPassedToComp(span, arg.value()))
+                    }
+                };
+
+                let value = arg_values.get(index).or(named_values.get(arg_name));
+                let value = match (value, &defaults[index]) {
+                    (Some(&ArgKey::Json(ref arg_value)), _) => {
+                        match self.get_variable(arg_value) {
+                            Ok(value) => value,
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    (Some(&ArgKey::Literal(ref value)), _) => value.clone(),
+                    (Some(&ArgKey::Comp(ref name)), _) => {
+                        return Err(CodegenError::CompPassedToComp(span, name.clone()))
+                    }
+                    (None, &Some(Token::Text(ref default))) => Value::String(default.clone()),
+                    (None, _) => {
+                        // Only the parameters without a default have to be passed.
+                        let required = defaults.iter().filter(|default| default.is_none()).count();
+                        return Err(CodegenError::ArgumentCountMismatch {
+                            component: String::from(component.name()),
+                            expected: required,
+                            found: arg_values.len() + named_values.len(),
+                            span: span,
+                        });
+                    }
+                };
+                arg_map.insert(arg_name.clone(), value);
+            }
+            // The children are rendered with the variables of the call site.
+            let children = match self.render_tokens(component_call.children()) {
+                Ok(children) => children,
+                Err(error) => return Err(error),
+            };
+            let mut expanding = self.expanding.clone();
+            expanding.push(String::from(component.name()));
+
+            Codegen {
+                elements: component.ast(),
+                variables: arg_map,
+                parent: self.parent.clone(),
+                children: children,
+                expanding: expanding,
+                context: Cell::new(self.context.get()),
+                depth: Cell::new(self.depth.get()),
+                minify: Cell::new(self.minify.get()),
+            }
+            .write_html(out)
         } else {
-            Err(CodegenError::NoSuchComponent(String::from(component_call.name())))
+            Err(CodegenError::NoSuchComponent(component_call.span(),
+                                              String::from(component_call.name())))
         }
     }
 
-    fn render_element(&self, element: &Element) -> CodegenResult {
-        use std::io::Write;
-        let mut html = Vec::new();
+    fn write_element(&self, element: &Element, out: &mut io::Write) -> Result<(), CodegenError> {
         let tag = element.tag();
-        html_try!(write!(&mut html, "<{}", tag));
+        if element.is_void() && (!element.children().is_empty() || element.resource().is_some()) {
+            let tag = String::from(tag);
+            return Err(CodegenError::VoidElementWithChildren(element.span(), tag));
+        }
 
-        if !element.classes().is_empty() {
+        if element.has_space_before() {
+            html_try!(write!(out, " "));
+        }
+        let depth = self.depth.get();
+        if let Some(depth) = depth {
+            if let Err(error) = self.write_indent(depth, out) {
+                return Err(error);
+            }
+        }
+        html_try!(write!(out, "<{}", tag));
 
-            html_try!(write!(&mut html, " class=\""));
-            let mut classes_iter = element.classes().iter();
-            html_try!(write!(&mut html, "{}", classes_iter.next().unwrap()));
+        let escape = if element.has_raw_attributes() {
+            raw_attribute
+        } else {
+            escape_attribute
+        };
 
-            for class in classes_iter {
-                if !class.is_empty() {
-                    html_try!(write!(&mut html, " {}", &*class));
+        let classes = match self.element_classes(element) {
+            Ok(classes) => classes,
+            Err(error) => return Err(error),
+        };
+
+        // The classes are written where the first of them was written among the attributes.
+        let class_index = element.class_index();
+        for (index, &(ref key, ref value)) in element.attributes().iter().enumerate() {
+            if index == class_index {
+                if let Err(error) = write_classes(&classes, escape, out) {
+                    return Err(error);
                 }
             }
-            html_try!(write!(&mut html, "\""));
-        }
-
-        if !element.attributes().is_empty() {
-            for (key, value) in element.attributes() {
-                if !key.is_empty() {
-                    if !value.is_empty() {
-                        html_try!(write!(&mut html, " {}=\"{}\"", key, value));
-                    } else {
-                        html_try!(write!(&mut html, " {}", key));
+            if let Some(condition) = element.condition(key) {
+                match self.evaluate(condition) {
+                    Ok(ref value) if !json_into_bool(value) => continue,
+                    Ok(_) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            // A class attribute with variables is written along with the other classes.
+            if !key.is_empty() && !(key == "class" && value.is_some()) {
+                let boolean = value.as_ref().and_then(|value| self.boolean_value(value));
+                match (value, boolean) {
+                    (&None, _) | (_, Some(true)) => html_try!(write!(out, " {}", key)),
+                    (_, Some(false)) => {}
+                    (&Some(ref value), None) => {
+                        let raw = element.has_raw_attributes();
+                        let value = match self.render_attribute_value(key, value, raw) {
+                            Ok(value) => value,
+                            Err(error) => return Err(error),
+                        };
+                        html_try!(write!(out, " {}=\"{}\"", key, escape(&value)))
                     }
                 }
             }
         }
+        if class_index >= element.attributes().len() {
+            if let Err(error) = write_classes(&classes, escape, out) {
+                return Err(error);
+            }
+        }
 
-        html_try!(write!(&mut html, ">"));
+        for name in element.spreads() {
+            if let Err(error) = self.write_spread(element, name, out) {
+                return Err(error);
+            }
+        }
 
+        if element.is_void() && !element.is_declaration() && self.xhtml() {
+            html_try!(write!(out, " />"));
+        } else {
+            html_try!(write!(out, ">"));
+        }
 
-        for void in &VOID_ELEMENTS {
-            if void == &tag {
-                return match String::from_utf8(html) {
-                    Ok(html) => Ok(html),
-                    Err(error) => Err(CodegenError::FromUtf8Error(error)),
-                };
+        if element.is_void() {
+            if depth.is_some() {
+                html_try!(write!(out, "\n"));
             }
+            return Ok(());
         }
 
-        if let Some(ref resource) = *element.resource() {
-            match self.generate_from_component(resource.clone()) {
-                Ok(rendered) => html_try!(write!(&mut html, "{}", rendered)),
-                Err(err) => return Err(err),
+        // Only elements containing nothing but other elements have their children on their own
+        // lines, as whitespace around text would change how it's shown.
+        let block = depth.is_some() && element.resource().is_none() &&
+                    !element.children().is_empty() &&
+                    !element.children().iter().any(is_inline);
+        if block {
+            html_try!(write!(out, "\n"));
+            self.depth.set(depth.map(|depth| depth + 1));
+        } else {
+            self.depth.set(None);
+        }
+
+        let minify = self.minify.get();
+        if element.preserves_whitespace() {
+            self.minify.set(false);
+        }
+        let previous = self.context.get();
+        if self.contextual_escaping() {
+            match &*tag.to_lowercase() {
+                "script" => self.context.set(Context::Script),
+                "style" => self.context.set(Context::Style),
+                _ => {}
             }
+        }
 
+        let result = match *element.resource() {
+            Some(ref resource) => self.write_component(resource, out),
+            None => self.write_tokens_within(element.children(), element.is_block(), out),
+        };
+        self.minify.set(minify);
+        self.context.set(previous);
+        self.depth.set(depth);
+        if let Err(error) = result {
+            return Err(error);
+        }
 
-        } else {
-            for child in element.children() {
-                match self.render(child) {
-                    Ok(rendered) => {
-                        html_try!(write!(&mut html, "{}", rendered));
+        if let (true, Some(depth)) = (block, depth) {
+            if let Err(error) = self.write_indent(depth, out) {
+                return Err(error);
+            }
+        }
+        html_try!(write!(out, "</{}>", tag));
+        if depth.is_some() {
+            html_try!(write!(out, "\n"));
+        }
+        Ok(())
+    }
+
+    /// Writes the indentation for an element nested `depth` elements deep.
+    fn write_indent(&self, depth: usize, out: &mut io::Write) -> Result<(), CodegenError> {
+        let parent = self.parent.borrow();
+        let indent = &parent.get_render_options().indent;
+        for _ in 0..depth {
+            html_try!(write!(out, "{}", indent));
+        }
+        Ok(())
+    }
+
+    /// Whether variables are escaped based on where they're written.
+    fn contextual_escaping(&self) -> bool {
+        self.parent.borrow().get_render_options().contextual_escaping
+    }
+
+    /// Whether void elements are closed as XHTML.
+    fn xhtml(&self) -> bool {
+        self.parent.borrow().get_render_options().xhtml
+    }
+
+    /// Whether a missing variable in a condition is an error, rather than false.
+    fn strict(&self) -> bool {
+        self.parent.borrow().get_render_options().strict
+    }
+
+    /// Writes the pairs of the object in the variable `name` as attributes, as in
+    /// `(**@attributes)`. Attributes written on the element take precedence, a `class` is
+    /// written with the element's classes, and pairs that are `null`, or `false` are left out,
+    /// while `true` is written without a value.
+    fn write_spread(&self,
+                    element: &Element,
+                    name: &str,
+                    out: &mut io::Write)
+                    -> Result<(), CodegenError> {
+        let object = match self.get_variable(name) {
+            Ok(Value::Object(object)) => object,
+            Ok(Value::Null) => return Ok(()),
+            Ok(value) => {
+                let kind = json_type(&value);
+                return Err(CodegenError::InvalidSpread(element.span(), name.to_owned(), kind));
+            }
+            Err(error) => return Err(error),
+        };
+        let raw = element.has_raw_attributes();
+        let contextual = !raw && self.contextual_escaping();
+
+        for (key, value) in object {
+            if key == "class" || element.attribute(&key).is_some() {
+                continue;
+            }
+            if !is_attribute_name(&key) {
+                return Err(CodegenError::InvalidAttributeName(element.span(), key));
+            }
+
+            let text = match value {
+                Value::Null | Value::Bool(false) => continue,
+                Value::Bool(true) => {
+                    html_try!(write!(out, " {}", key));
+                    continue;
+                }
+                Value::Array(_) | Value::Object(_) => {
+                    let kind = json_type(&value);
+                    return Err(CodegenError::InvalidAttribute(element.span(), key, kind));
+                }
+                value => value_to_string(&value),
+            };
+
+            let lowercase = key.to_lowercase();
+            let text = if contextual && URL_ATTRIBUTES.contains(&&*lowercase) {
+                let url = encode_control_characters(&text);
+                if is_javascript_url(&url) {
+                    return Err(CodegenError::UnsafeUrl(element.span(), url));
+                }
+                url
+            } else if contextual && lowercase.starts_with("on") {
+                escape_script(&text)
+            } else {
+                text
+            };
+
+            if raw {
+                html_try!(write!(out, " {}=\"{}\"", key, text));
+            } else {
+                html_try!(write!(out, " {}=\"{}\"", key, escape_attribute(&text)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The element's classes, followed by those from a `class` attribute with variables, such
+    /// as `(class=@classes)`, and those from the `class` of each object spread into its
+    /// attributes, with any class given twice only written once.
+    fn element_classes(&self, element: &Element) -> Result<Vec<String>, CodegenError> {
+        let mut classes = element.classes().clone();
+        if let Some(&Some(ref value)) = element.attribute("class") {
+            let variable_classes = match self.render_classes(element, value) {
+                Ok(variable_classes) => variable_classes,
+                Err(error) => return Err(error),
+            };
+            for class in variable_classes {
+                if !classes.contains(&class) {
+                    classes.push(class);
+                }
+            }
+        }
+
+        for name in element.spreads() {
+            // A variable that isn't an object is reported when the spread is written.
+            let value = match self.get_variable(name) {
+                Ok(Value::Object(mut object)) => object.remove("class"),
+                _ => None,
+            };
+            let text = match value {
+                None | Some(Value::Null) | Some(Value::Bool(_)) => continue,
+                Some(value @ Value::Array(_)) |
+                Some(value @ Value::Object(_)) => {
+                    let kind = json_type(&value);
+                    let key = String::from("class");
+                    return Err(CodegenError::InvalidAttribute(element.span(), key, kind));
+                }
+                Some(value) => value_to_string(&value),
+            };
+            for class in text.split_whitespace() {
+                if !classes.iter().any(|existing| existing == class) {
+                    classes.push(class.to_owned());
+                }
+            }
+        }
+        Ok(classes)
+    }
+
+    /// The classes of the element's `class` attribute with variables. A single variable can be
+    /// an array of class names, or an object, where each key with a truthy value is a class, in
+    /// the order of the keys. Otherwise the value is split on whitespace.
+    fn render_classes(&self,
+                      element: &Element,
+                      value: &AttributeValue)
+                      -> Result<Vec<String>, CodegenError> {
+        let name = match *value {
+            AttributeValue::Interpolated(ref segments) if segments.len() == 1 => {
+                match segments[0] {
+                    Segment::Variable(ref name) => Some(name),
+                    Segment::Literal(_) => None,
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(name) = name {
+            match self.get_variable(name) {
+                Ok(Value::Array(ref array)) => {
+                    let mut classes = Vec::new();
+                    for class in array {
+                        match *class {
+                            Value::String(ref class) => classes.push(class.trim().to_owned()),
+                            ref class => {
+                                return Err(CodegenError::InvalidClass(element.span(),
+                                                                      name.clone(),
+                                                                      json_type(class)))
+                            }
+                        }
                     }
-                    Err(error) => return Err(error),
+                    return Ok(classes);
+                }
+                Ok(Value::Object(ref object)) => {
+                    let classes = object.iter()
+                                        .filter(|&(_, value)| json_into_bool(value))
+                                        .map(|(class, _)| class.clone())
+                                        .collect();
+                    return Ok(classes);
                 }
+                Ok(_) => {}
+                Err(error) => return Err(error),
             }
         }
 
-        html_try!(write!(&mut html, "</{}>", tag));
+        match self.render_attribute_value("class", value, element.has_raw_attributes()) {
+            Ok(classes) => Ok(classes.split_whitespace().map(String::from).collect()),
+            Err(error) => Err(error),
+        }
+    }
 
-        match String::from_utf8(html) {
-            Ok(html) => Ok(html),
-            Err(error) => Err(CodegenError::FromUtf8Error(error)),
+    /// The value of an attribute that's a single variable set to a boolean, as in
+    /// `(checked=@active)`, which is written without a value when `true`, and left out when
+    /// `false`.
+    fn boolean_value(&self, value: &AttributeValue) -> Option<bool> {
+        match *value {
+            AttributeValue::Interpolated(ref segments) if segments.len() == 1 => {
+                match segments[0] {
+                    Segment::Variable(ref name) => {
+                        match self.get_variable(name) {
+                            Ok(Value::Bool(value)) => Some(value),
+                            _ => None,
+                        }
+                    }
+                    Segment::Literal(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Resolves the variables within an attribute's value. Unless the attributes are `raw`,
+    /// variables within a URL have their control characters encoded, and can't make it a
+    /// `javascript:` URL, and variables within an event handler are escaped as JavaScript.
+    fn render_attribute_value(&self,
+                              key: &str,
+                              value: &AttributeValue,
+                              raw: bool)
+                              -> CodegenResult {
+        match *value {
+            AttributeValue::Literal(ref text) => Ok(text.clone()),
+            AttributeValue::Interpolated(ref segments) => {
+                let contextual = !raw && self.contextual_escaping();
+                let key = key.to_lowercase();
+                let url = contextual && URL_ATTRIBUTES.contains(&&*key);
+                let script = contextual && key.starts_with("on");
+
+                let mut rendered = String::new();
+                for segment in segments {
+                    match *segment {
+                        Segment::Literal(ref text) => rendered.push_str(&*text),
+                        Segment::Variable(ref name) => {
+                            let value = match self.get_variable(name) {
+                                Ok(value) => value_to_string(&value),
+                                Err(error) => return Err(error),
+                            };
+
+                            if url {
+                                rendered.push_str(&*encode_control_characters(&value));
+                            } else if script {
+                                rendered.push_str(&*escape_script(&value));
+                            } else {
+                                rendered.push_str(&*value);
+                            }
+                        }
+                    }
+                }
+
+                if url && is_javascript_url(&rendered) {
+                    return Err(CodegenError::UnsafeUrl(None, rendered));
+                }
+                Ok(rendered)
+            }
         }
     }
 
     fn render_function(&self, function: &FunctionCall) -> CodegenResult {
+        if function.identifier() == CHILDREN_MARKER && function.args().is_empty() {
+            return Ok(self.children.clone());
+        }
+
         let mut arguments: BTreeMap<String, ArgValue> = BTreeMap::new();
 
         for (key, value) in function.args().clone() {
@@ -211,6 +746,19 @@ impl Codegen {
                     };
                     arguments.insert(key, ArgValue::Comp(real_value));
                 }
+                ArgKey::Literal(value) => {
+                    arguments.insert(key, ArgValue::Json(Some(value)));
+                }
+            }
+        }
+
+        if !function.children().is_empty() {
+            // Like a component's children, the block is rendered with the call site's variables.
+            match self.render_tokens(function.children()) {
+                Ok(children) => {
+                    arguments.insert(String::from(CHILDREN_MARKER), ArgValue::Children(children));
+                }
+                Err(error) => return Err(error),
             }
         }
 
@@ -218,71 +766,509 @@ impl Codegen {
         if let Some(fun) = parent.get_function(function.identifier()) {
             match fun(arguments, &self.parent) {
                 Ok(string) => Ok(string),
-                Err(error) => Err(CodegenError::FunctionError(error)),
+                Err(error) => Err(CodegenError::FunctionError(function.span(), error)),
             }
         } else {
-            Err(CodegenError::NoSuchFunction(String::from(function.identifier())))
+            let identifier = String::from(function.identifier());
+            Err(CodegenError::NoSuchFunction(function.span(), identifier))
         }
     }
 
-    fn get_variable(&self, name: &String) -> Result<Value, CodegenError> {
-        let segments: Vec<&str> = name.split('.').collect();
+    /// Writes `body` for each item in the collection, or `else_branch` if there are no items.
+    fn write_loop(&self,
+                  key: &Option<String>,
+                  binding: &str,
+                  collection: &Collection,
+                  body: &[Token],
+                  else_branch: &Option<Vec<Token>>,
+                  out: &mut io::Write)
+                  -> Result<(), CodegenError> {
+        let (length, items) = match self.loop_items(collection) {
+            Ok(items) => items,
+            Err(error) => return Err(error),
+        };
 
-        if segments.len() == 1 {
-            match self.variables.get(name) {
-                Some(value) => Ok(value.clone()),
-                None => Ok(Value::String(String::new())),
+        if length == 0 {
+            if let Some(ref else_branch) = *else_branch {
+                return self.write_tokens(else_branch, out);
             }
-        } else {
-            match Value::Object(self.variables.clone()).find_path(&segments) {
-                Some(value) => return Ok(value.clone()),
-                _ => return Err(CodegenError::NotAnObjectOrNull(String::from(name.clone()))),
+        }
+        for (index, (item_key, item)) in items.enumerate() {
+            // The position within the loop is available as `@loop.index`, `@loop.first`, and
+            // `@loop.last`, unless the item is itself bound to `loop`.
+            let mut position = BTreeMap::new();
+            position.insert(String::from("index"), Value::U64(index as u64));
+            position.insert(String::from("first"), Value::Bool(index == 0));
+            position.insert(String::from("last"), Value::Bool(index as u64 + 1 == length));
+
+            let mut variables = self.variables.clone();
+            variables.insert(String::from(LOOP_VARIABLE), Value::Object(position));
+            if let Some(ref key) = *key {
+                variables.insert(key.clone(), item_key);
+            }
+            variables.insert(String::from(binding), item);
+
+            let scope = Codegen {
+                elements: Vec::new(),
+                variables: variables,
+                parent: self.parent.clone(),
+                children: self.children.clone(),
+                expanding: self.expanding.clone(),
+                context: Cell::new(self.context.get()),
+                depth: Cell::new(self.depth.get()),
+                minify: Cell::new(self.minify.get()),
+            };
+
+            if let Err(error) = scope.write_tokens(body, out) {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of items in the collection, and the key, or index, and value of each of them.
+    /// An object's items are in the order of their keys, and a range's are made as they're
+    /// rendered, rather than all at once. Null is the same as an empty array, and so is a
+    /// variable that doesn't exist, unless rendering is strict.
+    fn loop_items(&self, collection: &Collection) -> Result<(u64, LoopItems), CodegenError> {
+        let name = match *collection {
+            Collection::Variable(ref name) => name,
+            Collection::Range(ref start, ref end) => {
+                let (start, end) = match (self.range_bound(start), self.range_bound(end)) {
+                    (Ok(Some(start)), Ok(Some(end))) => (start, end),
+                    (Err(error), _) | (_, Err(error)) => return Err(error),
+                    _ => return Ok(no_loop_items()),
+                };
+
+                // A range that ends before it starts is empty.
+                if start > end {
+                    return Ok(no_loop_items());
+                }
+                let length = (end.wrapping_sub(start) as u64).saturating_add(1);
+                let items = (start..end)
+                                .chain(Some(end))
+                                .enumerate()
+                                .map(|(index, integer)| {
+                                    (Value::U64(index as u64), integer_value(integer))
+                                });
+                return Ok((length, Box::new(items)));
+            }
+        };
+
+        match self.get_variable(name) {
+            Ok(Value::Array(items)) => {
+                let length = items.len() as u64;
+                let items = items.into_iter()
+                                 .enumerate()
+                                 .map(|(index, item)| (Value::U64(index as u64), item));
+                Ok((length, Box::new(items)))
+            }
+            Ok(Value::Object(items)) => {
+                let length = items.len() as u64;
+                let items = items.into_iter().map(|(key, item)| (Value::String(key), item));
+                Ok((length, Box::new(items)))
+            }
+            Ok(Value::Null) => Ok(no_loop_items()),
+            Ok(value) => Err(CodegenError::NotIterable(None, name.clone(), json_type(&value))),
+            Err(CodegenError::MissingVariable(_, _, _)) |
+            Err(CodegenError::IndexOutOfBounds(_, _, _, _)) if !self.strict() => {
+                Ok(no_loop_items())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// The integer a range starts, or ends at, which is `None` for null, or a variable that
+    /// doesn't exist, unless rendering is strict.
+    fn range_bound(&self, bound: &Expr) -> Result<Option<i64>, CodegenError> {
+        match self.evaluate(bound) {
+            Ok(Value::I64(integer)) => Ok(Some(integer)),
+            Ok(Value::U64(integer)) if integer <= i64::MAX as u64 => Ok(Some(integer as i64)),
+            Ok(Value::Null) => Ok(None),
+            Ok(value) => {
+                Err(CodegenError::NotAnInteger(None, bound.to_string(), json_type(&value)))
+            }
+            Err(error) => Err(error),
+        }
+    }
 
+    /// Looks up a variable, walking into an object, or array for each `.` in the name.
+    fn get_variable(&self, name: &str) -> Result<Value, CodegenError> {
+        let mut segments = name.split('.');
+        let first = segments.next().unwrap_or("");
+
+        let mut value = match self.variables.get(first) {
+            Some(value) => value,
+            None => {
+                let first = String::from(first);
+                return Err(CodegenError::MissingVariable(None, name.to_owned(), first));
             }
+        };
+        let mut parent = first;
+
+        for segment in segments {
+            value = match index_value(value, segment) {
+                Ok(value) => value,
+                Err(IndexError::Missing) => {
+                    let segment = String::from(segment);
+                    return Err(CodegenError::MissingVariable(None, name.to_owned(), segment));
+                }
+                Err(IndexError::NotIndexable) => {
+                    let parent = String::from(parent);
+                    return Err(CodegenError::NotAnObjectOrNull(None, name.to_owned(), parent));
+                }
+                Err(IndexError::OutOfBounds(index, length)) => {
+                    return Err(CodegenError::IndexOutOfBounds(None, name.to_owned(), index, length))
+                }
+                Err(IndexError::Negative) => {
+                    let segment = String::from(segment);
+                    return Err(CodegenError::NegativeIndex(None, name.to_owned(), segment));
+                }
+            };
+            parent = segment;
         }
+        Ok(value.clone())
     }
 
-    fn render(&self, token: &AstResult) -> CodegenResult {
+    /// Evaluates the condition of an `@if`. A variable that doesn't exist is `null`, unless
+    /// rendering is strict.
+    fn evaluate(&self, expression: &Expr) -> Result<Value, CodegenError> {
+        match *expression {
+            Expr::Variable(ref name) => {
+                match self.get_variable(name) {
+                    Ok(value) => Ok(value),
+                    Err(CodegenError::MissingVariable(_, _, _)) |
+                    Err(CodegenError::IndexOutOfBounds(_, _, _, _)) if !self.strict() => {
+                        Ok(Value::Null)
+                    }
+                    Err(error) => Err(error),
+                }
+            }
+            Expr::Literal(ref value) => Ok(value.clone()),
+            Expr::Not(ref operand) => {
+                match self.evaluate(operand) {
+                    Ok(value) => Ok(Value::Bool(!json_into_bool(&value))),
+                    Err(error) => Err(error),
+                }
+            }
+            Expr::And(ref left, ref right) => {
+                match self.evaluate(left) {
+                    Ok(ref value) if !json_into_bool(value) => Ok(Value::Bool(false)),
+                    Ok(_) => {
+                        match self.evaluate(right) {
+                            Ok(value) => Ok(Value::Bool(json_into_bool(&value))),
+                            Err(error) => Err(error),
+                        }
+                    }
+                    Err(error) => Err(error),
+                }
+            }
+            Expr::Or(ref left, ref right) => {
+                match self.evaluate(left) {
+                    Ok(ref value) if json_into_bool(value) => Ok(Value::Bool(true)),
+                    Ok(_) => {
+                        match self.evaluate(right) {
+                            Ok(value) => Ok(Value::Bool(json_into_bool(&value))),
+                            Err(error) => Err(error),
+                        }
+                    }
+                    Err(error) => Err(error),
+                }
+            }
+            Expr::Compare(ref left, comparison, ref right) => {
+                let left = match self.evaluate(left) {
+                    Ok(value) => value,
+                    Err(error) => return Err(error),
+                };
+                let right = match self.evaluate(right) {
+                    Ok(value) => value,
+                    Err(error) => return Err(error),
+                };
+
+                match compare(&left, comparison, &right) {
+                    Ok(is_true) => Ok(Value::Bool(is_true)),
+                    Err(error) => Err(error),
+                }
+            }
+        }
+    }
+
+    /// Looks up a variable, and passes it through each of the filters, from left to right.
+    fn render_variable(&self, name: &str, filters: &[String]) -> CodegenResult {
+        let mut value = match self.get_variable(name) {
+            Ok(value) => value_to_string(&value),
+            Err(error) => return Err(error),
+        };
+
+        let parent = self.parent.borrow();
+        for filter in filters {
+            value = match parent.get_filter(filter) {
+                Some(filter) => filter(&value),
+                None => return Err(CodegenError::UnknownFilter(None, filter.clone())),
+            };
+        }
+        Ok(value)
+    }
+
+    fn write(&self, token: &Token, out: &mut io::Write) -> Result<(), CodegenError> {
         use super::Token::*;
         match *token {
-            Ok(Html(ref element)) => self.render_element(element),
-            Ok(Text(ref text)) => Ok(text.clone()),
-            Ok(Variable(ref variable)) => {
-                match self.get_variable(variable) {
-                    Ok(value) => Ok(value_to_string(&value)),
-                    Err(error) => Err(error),
+            Html(ref element) => return self.write_element(element, out),
+            Text(ref text) => {
+                match self.context.get() {
+                    Context::Html => html_try!(write!(out, "{}", escape_text(text))),
+                    // Scripts, and styles are written as is, as HTML escaping would break them.
+                    Context::Script | Context::Style => html_try!(write!(out, "{}", text)),
+                }
+            }
+            Raw(ref text) => html_try!(write!(out, "{}", text)),
+            Variable(ref variable, ref filters, _) => {
+                match self.render_variable(variable, filters) {
+                    Ok(value) => {
+                        let escaped = match self.context.get() {
+                            Context::Html => escape_text(&value),
+                            Context::Script => escape_script(&value),
+                            Context::Style => escape_style(&value),
+                        };
+                        html_try!(write!(out, "{}", escaped))
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            RawVariable(ref variable, ref filters, _) => {
+                match self.render_variable(variable, filters) {
+                    Ok(value) => html_try!(write!(out, "{}", value)),
+                    Err(error) => return Err(error),
                 }
             }
-            Ok(CompCall(ref component_call)) => {
-                self.generate_from_component(component_call.clone())
+            CompCall(ref component_call) => return self.write_component(component_call, out),
+            Function(ref function) => {
+                match self.render_function(function) {
+                    Ok(rendered) => html_try!(write!(out, "{}", rendered)),
+                    Err(error) => return Err(error),
+                }
+            }
+            Conditional { ref condition, ref then_branch, ref else_ifs, ref else_branch, .. } => {
+                let branches = Some((condition, then_branch))
+                                   .into_iter()
+                                   .chain(else_ifs.iter().map(|&(ref condition, ref children)| {
+                                       (condition, children)
+                                   }));
+
+                for (condition, children) in branches {
+                    match self.evaluate(condition) {
+                        Ok(ref value) if json_into_bool(value) => {
+                            return self.write_tokens(children, out)
+                        }
+                        Ok(_) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+
+                if let Some(ref else_branch) = *else_branch {
+                    return self.write_tokens(else_branch, out);
+                }
+            }
+            Loop { ref key, ref binding, ref collection, ref body, ref else_branch, .. } => {
+                return self.write_loop(key, binding, collection, body, else_branch, out)
+            }
+            Switch { ref subject, ref cases, ref default, .. } => {
+                // Like a conditional, a missing variable renders the default.
+                let value = match self.get_variable(subject) {
+                    Ok(value) => Some(value),
+                    Err(CodegenError::MissingVariable(_, _, _)) |
+                    Err(CodegenError::IndexOutOfBounds(_, _, _, _)) if !self.strict() => None,
+                    Err(error) => return Err(error),
+                };
+
+                // Cases are compared like `@if`'s `==`, so `1`, and `1.0` are the same case, but a
+                // case of another type, like `"0"` for `0`, never matches.
+                let case = value.and_then(|value| {
+                    cases.iter()
+                         .find(|&&(ref case, _)| {
+                             compare(&value, Comparison::Equal, case).unwrap_or(false)
+                         })
+                         .map(|&(_, ref children)| children)
+                });
+
+                match (case, default.as_ref()) {
+                    (Some(children), _) |
+                    (None, Some(children)) => return self.write_tokens(children, out),
+                    (None, None) => {}
+                }
             }
-            Ok(Function(ref function)) => self.render_function(function),
-            Err(ref error) => Err(CodegenError::AstError(error.clone())),
+            Comment(ref children) => {
+                match self.render_tokens(children) {
+                    Ok(comment) => {
+                        html_try!(write!(out, "<!-- {} -->", escape_comment(comment.trim())))
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            Doctype(ref doctype) => {
+                html_try!(write!(out, "<!DOCTYPE {}>", doctype));
+                if self.depth.get().is_some() {
+                    html_try!(write!(out, "\n"));
+                }
+            }
+            Block { ref body, .. } => return self.write_tokens(body, out),
         }
+        Ok(())
     }
 }
 
 #[derive(Debug)]
-/// Errors generated during code generation
+/// Errors generated during code generation. Errors from rendering a token carry where the token
+/// was parsed from, if it was parsed from source.
 pub enum CodegenError {
+    /// The number of arguments passed to a component doesn't match its parameters. `expected` is
+    /// the number of parameters if too many were passed, otherwise the number without defaults.
+    ArgumentCountMismatch {
+        /// The name of the component.
+        component: String,
+        /// The number of arguments the component takes.
+        expected: usize,
+        /// The number of arguments passed.
+        found: usize,
+        /// Where the component was called.
+        span: Option<Span>,
+    },
     /// Errors during AST parsing.
     AstError(AstError),
+    /// A condition compared values that can't be compared, such as a number, and a string.
+    InvalidComparison {
+        /// The comparison made.
+        comparison: Comparison,
+        /// The type of JSON on the left of the comparison.
+        left: &'static str,
+        /// The type of JSON on the right of the comparison.
+        right: &'static str,
+        /// Where the condition was written.
+        span: Option<Span>,
+    },
+    /// An array of classes contained something other than a string, with the name of the
+    /// variable, and the type of JSON it was.
+    InvalidClass(Option<Span>, String, &'static str),
+    /// A spread attribute's value was an array, or object, with the attribute's name, and the
+    /// type of JSON it was.
+    InvalidAttribute(Option<Span>, String, &'static str),
+    /// A spread attribute's name can't be written as an attribute.
+    InvalidAttributeName(Option<Span>, String),
+    /// A variable spread into an element's attributes wasn't an object, or null, with the name
+    /// of the variable, and the type of JSON it was.
+    InvalidSpread(Option<Span>, String, &'static str),
     /// Component passed to a component.
-    CompPassedToComp(String),
+    CompPassedToComp(Option<Span>, String),
+    /// An argument was passed both by position, and by name.
+    DuplicateArgument(Option<Span>, String),
     /// Error converting to a UTF8 String.
     FromUtf8Error(string::FromUtf8Error),
-    /// An error generated by a Polly Function.
-    FunctionError(String),
+    /// An error generated by a Polly Function, with where the function was called.
+    FunctionError(Option<Span>, String),
+    /// An array in the variable's name was indexed past its end, with the full name, the
+    /// index, and the length of the array.
+    IndexOutOfBounds(Option<Span>, String, usize, usize),
     /// Error generated from writing to a buffer.
     IoError(io::Error),
+    /// Variable wasn't found in the JSON, with the full name, and the segment that was missing.
+    MissingVariable(Option<Span>, String, String),
+    /// An array in the variable's name was indexed with a negative number.
+    NegativeIndex(Option<Span>, String, String),
+    /// A named argument doesn't match any of the component's parameters.
+    NoSuchArgument(Option<Span>, String),
     /// No such component in the template.
-    NoSuchComponent(String),
+    NoSuchComponent(Option<Span>, String),
     /// No such function in the template.
-    NoSuchFunction(String),
-    /// JSON wasn't an object, or it was Null.
-    NotAnObjectOrNull(String),
-    /// Wrong number of arguments passed in.
-    WrongNumberOfArguments(usize, usize),
+    NoSuchFunction(Option<Span>, String),
+    /// JSON wasn't an object, or it was Null, with the full name of the variable, and the
+    /// segment that couldn't be indexed into.
+    NotAnObjectOrNull(Option<Span>, String, String),
+    /// The start, or end of a range wasn't an integer, with the bound as it was written, and the
+    /// type of JSON it was.
+    NotAnInteger(Option<Span>, String, &'static str),
+    /// JSON being looped over wasn't an array, or object, with the name of the variable, and the
+    /// type of JSON it was.
+    NotIterable(Option<Span>, String, &'static str),
+    /// A component that calls itself, directly, or through other components.
+    RecursiveComponent(Option<Span>, String),
+    /// A variable was passed through a filter that isn't registered.
+    UnknownFilter(Option<Span>, String),
+    /// A variable made a URL attribute into a `javascript:` URL.
+    UnsafeUrl(Option<Span>, String),
+    /// A void element, such as `img`, or `br` was given children.
+    VoidElementWithChildren(Option<Span>, String),
+}
+
+impl CodegenError {
+    /// Where the token being rendered was parsed from, if the error came from rendering a
+    /// token, and the token was parsed from source.
+    pub fn span(&self) -> Option<Span> {
+        use self::CodegenError::*;
+
+        match *self {
+            AstError(ref error) => error.span(),
+            FromUtf8Error(_) | IoError(_) => None,
+            ArgumentCountMismatch { span, .. } |
+            InvalidComparison { span, .. } |
+            InvalidClass(span, _, _) |
+            InvalidAttribute(span, _, _) |
+            InvalidAttributeName(span, _) |
+            InvalidSpread(span, _, _) |
+            CompPassedToComp(span, _) |
+            DuplicateArgument(span, _) |
+            FunctionError(span, _) |
+            IndexOutOfBounds(span, _, _, _) |
+            MissingVariable(span, _, _) |
+            NegativeIndex(span, _, _) |
+            NoSuchArgument(span, _) |
+            NoSuchComponent(span, _) |
+            NoSuchFunction(span, _) |
+            NotAnObjectOrNull(span, _, _) |
+            NotAnInteger(span, _, _) |
+            NotIterable(span, _, _) |
+            RecursiveComponent(span, _) |
+            UnknownFilter(span, _) |
+            UnsafeUrl(span, _) |
+            VoidElementWithChildren(span, _) => span,
+        }
+    }
+
+    /// Sets where the error occurred to `span`, unless it already has a position, so an error
+    /// keeps the position of the innermost token that was being rendered.
+    fn at(mut self, span: Option<Span>) -> Self {
+        use self::CodegenError::*;
+
+        match self {
+            AstError(_) | FromUtf8Error(_) | IoError(_) => {}
+            ArgumentCountMismatch { span: ref mut position, .. } |
+            InvalidComparison { span: ref mut position, .. } |
+            InvalidClass(ref mut position, _, _) |
+            InvalidAttribute(ref mut position, _, _) |
+            InvalidAttributeName(ref mut position, _) |
+            InvalidSpread(ref mut position, _, _) |
+            CompPassedToComp(ref mut position, _) |
+            DuplicateArgument(ref mut position, _) |
+            FunctionError(ref mut position, _) |
+            IndexOutOfBounds(ref mut position, _, _, _) |
+            MissingVariable(ref mut position, _, _) |
+            NegativeIndex(ref mut position, _, _) |
+            NoSuchArgument(ref mut position, _) |
+            NoSuchComponent(ref mut position, _) |
+            NoSuchFunction(ref mut position, _) |
+            NotAnObjectOrNull(ref mut position, _, _) |
+            NotAnInteger(ref mut position, _, _) |
+            NotIterable(ref mut position, _, _) |
+            RecursiveComponent(ref mut position, _) |
+            UnknownFilter(ref mut position, _) |
+            UnsafeUrl(ref mut position, _) |
+            VoidElementWithChildren(ref mut position, _) => {
+                if position.is_none() {
+                    *position = span;
+                }
+            }
+        }
+        self
+    }
 }
 
 impl error::Error for CodegenError {
@@ -290,17 +1276,35 @@ impl error::Error for CodegenError {
         use self::CodegenError::*;
 
         match *self {
+            ArgumentCountMismatch { .. } => {
+                "Wrong number of arguments passed to the component: "
+            }
             AstError(ref error) => error.description(),
-            CompPassedToComp(_) => {
+            CompPassedToComp(_, _) => {
                 "Currently you cannot cannot pass a component to another component: "
             }
             FromUtf8Error(ref error) => error.description(),
-            FunctionError(_) => "Function produced error: ",
+            FunctionError(_, _) => "Function produced error: ",
+            InvalidComparison { .. } => "Condition compares values that can't be compared: ",
+            InvalidClass(_, _, _) => "Classes have to be strings: ",
+            InvalidAttribute(_, _, _) => "Attributes have to be strings, numbers, or booleans: ",
+            InvalidAttributeName(_, _) => "Attribute names can't contain whitespace, or symbols: ",
+            InvalidSpread(_, _, _) => "Attributes can only be spread from an object, or null: ",
+            IndexOutOfBounds(_, _, _, _) => "Array index is past the end of the array: ",
             IoError(ref error) => error.description(),
-            NoSuchComponent(_) => "Component called doesn't exist in the current template: ",
-            NoSuchFunction(_) => "Function called doesn't exist in the current template: ",
-            NotAnObjectOrNull(_) => "JSON passed in wasn't an object, or was null: ",
-            WrongNumberOfArguments(_, _) => "Incorrect number of arguments passed in: ",
+            DuplicateArgument(_, _) => "Argument was passed both by position, and by name: ",
+            MissingVariable(_, _, _) => "Variable doesn't exist in the JSON passed in: ",
+            NegativeIndex(_, _, _) => "Arrays can't be indexed with a negative number: ",
+            NoSuchArgument(_, _) => "Component doesn't have a parameter with the name: ",
+            NoSuchComponent(_, _) => "Component called doesn't exist in the current template: ",
+            NoSuchFunction(_, _) => "Function called doesn't exist in the current template: ",
+            NotAnObjectOrNull(_, _, _) => "JSON passed in wasn't an object, or was null: ",
+            NotAnInteger(_, _, _) => "Range bound wasn't an integer: ",
+            NotIterable(_, _, _) => "JSON looped over wasn't an array, or object: ",
+            RecursiveComponent(_, _) => "Component calls itself, through the components: ",
+            UnknownFilter(_, _) => "Filter isn't registered: ",
+            UnsafeUrl(_, _) => "Variables can't make a URL run JavaScript: ",
+            VoidElementWithChildren(_, _) => "Void elements can't have any children: ",
         }
     }
 }
@@ -311,23 +1315,406 @@ impl fmt::Display for CodegenError {
         use std::error::Error;
 
         let msg = match *self {
-            AstError(ref error) => error.to_string(),
-            CompPassedToComp(ref name) => format!("{} NAME: {}", self.description(), name),
+            ArgumentCountMismatch { ref component, expected, found, .. } => {
+                format!("{} NAME: {} EXPECTED: {} FOUND: {}",
+                        self.description(),
+                        component,
+                        expected,
+                        found)
+            }
+            // Parse errors already include where they occurred.
+            AstError(ref error) => return write!(f, "{}", error),
+            CompPassedToComp(_, ref name) => format!("{} NAME: {}", self.description(), name),
             FromUtf8Error(ref error) => error.to_string(),
-            FunctionError(ref error) => format!("{} ERROR: {}", self.description(), error),
-            IoError(ref error) => error.to_string(),
-            NoSuchComponent(ref name) | NoSuchFunction(ref name) | NotAnObjectOrNull(ref name) => {
-                format!("{} NAME: {}", self.description(), name)
+            FunctionError(_, ref error) => format!("{} ERROR: {}", self.description(), error),
+            InvalidComparison { comparison, left, right, .. } => {
+                format!("{} {} {} {}", self.description(), left, comparison, right)
             }
-            WrongNumberOfArguments(expected, actual) => {
-                format!("{} EXPECTED: {} ACTUAL: {}",
+            IndexOutOfBounds(_, ref name, index, length) => {
+                format!("{} NAME: {} (index {} but array has {} elements)",
                         self.description(),
-                        expected,
-                        actual)
+                        name,
+                        index,
+                        length)
+            }
+            IoError(ref error) => error.to_string(),
+            NegativeIndex(_, ref name, ref index) => {
+                format!("{} NAME: {} INDEX: {}", self.description(), name, index)
             }
+            DuplicateArgument(_, ref name) |
+            InvalidAttributeName(_, ref name) |
+            NoSuchArgument(_, ref name) |
+            NoSuchComponent(_, ref name) |
+            NoSuchFunction(_, ref name) |
+            UnknownFilter(_, ref name) => format!("{} NAME: {}", self.description(), name),
+            InvalidAttribute(_, ref name, kind) |
+            InvalidClass(_, ref name, kind) |
+            InvalidSpread(_, ref name, kind) |
+            NotAnInteger(_, ref name, kind) |
+            NotIterable(_, ref name, kind) => {
+                format!("{} NAME: {} TYPE: {}", self.description(), name, kind)
+            }
+            MissingVariable(_, ref name, ref segment) |
+            NotAnObjectOrNull(_, ref name, ref segment) if name != segment => {
+                format!("{} NAME: {} SEGMENT: {}", self.description(), name, segment)
+            }
+            MissingVariable(_, ref name, _) |
+            NotAnObjectOrNull(_, ref name, _) => format!("{} NAME: {}", self.description(), name),
+            RecursiveComponent(_, ref cycle) => format!("{} {}", self.description(), cycle),
+            UnsafeUrl(_, ref url) => format!("{} URL: {}", self.description(), url),
+            VoidElementWithChildren(_, ref tag) => format!("{} TAG: {}", self.description(), tag),
         };
 
-        write!(f, "{}", msg)
+        match self.span() {
+            Some(span) => write!(f, "{} AT: {}", msg, span),
+            None => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Writes the element's class attribute, unless it doesn't have any classes.
+fn write_classes(classes: &[String],
+                 escape: fn(&str) -> String,
+                 out: &mut io::Write)
+                 -> Result<(), CodegenError> {
+    if classes.is_empty() {
+        return Ok(());
+    }
+
+    html_try!(write!(out, " class=\""));
+    let mut classes_iter = classes.iter();
+    html_try!(write!(out, "{}", escape(classes_iter.next().unwrap())));
+
+    for class in classes_iter {
+        if !class.is_empty() {
+            html_try!(write!(out, " {}", escape(&*class)));
+        }
+    }
+    html_try!(write!(out, "\""));
+    Ok(())
+}
+
+/// Escapes the characters that would end, or break out of a quoted attribute value.
+fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("&quot;"),
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Compares two values. Numbers are compared with numbers, and strings with strings. A number
+/// compared with a string is an error, as is ordering other types, except `null`, which is
+/// never less, or greater than anything.
+fn compare(left: &Value, comparison: Comparison, right: &Value) -> Result<bool, CodegenError> {
+    let ordering = match (left, right) {
+        (&Value::String(ref left), &Value::String(ref right)) => Some(left.cmp(right)),
+        _ => {
+            match (json_number(left), json_number(right)) {
+                (Some(left), Some(right)) => left.partial_cmp(&right),
+                _ => None,
+            }
+        }
+    };
+
+    let mismatch = CodegenError::InvalidComparison {
+        comparison: comparison,
+        left: json_type(left),
+        right: json_type(right),
+        span: None,
+    };
+    match (json_type(left), json_type(right)) {
+        ("number", "string") | ("string", "number") => return Err(mismatch),
+        _ => {}
+    }
+
+    match (comparison, ordering) {
+        (Comparison::Equal, Some(ordering)) => Ok(ordering == Ordering::Equal),
+        (Comparison::NotEqual, Some(ordering)) => Ok(ordering != Ordering::Equal),
+        (Comparison::Less, Some(ordering)) => Ok(ordering == Ordering::Less),
+        (Comparison::LessOrEqual, Some(ordering)) => Ok(ordering != Ordering::Greater),
+        (Comparison::Greater, Some(ordering)) => Ok(ordering == Ordering::Greater),
+        (Comparison::GreaterOrEqual, Some(ordering)) => Ok(ordering != Ordering::Less),
+        (Comparison::Equal, None) => Ok(left == right),
+        (Comparison::NotEqual, None) => Ok(left != right),
+        (_, None) if *left == Value::Null || *right == Value::Null => Ok(false),
+        (_, None) => Err(mismatch),
+    }
+}
+
+/// The items of a loop over nothing.
+fn no_loop_items() -> (u64, LoopItems) {
+    (0, Box::new(iter::empty::<(Value, Value)>()))
+}
+
+/// The value of a JSON number, for comparing numbers of different types.
+fn json_number(value: &Value) -> Option<f64> {
+    match *value {
+        Value::I64(number) => Some(number as f64),
+        Value::U64(number) => Some(number as f64),
+        Value::F64(number) => Some(number),
+        _ => None,
+    }
+}
+
+/// Whether the name can be written as an attribute's name, which can't be empty, or contain
+/// whitespace, control characters, quotes, `<`, `>`, `/`, or `=`.
+fn is_attribute_name(name: &str) -> bool {
+    !name.is_empty() &&
+    !name.chars().any(|character| {
+        character.is_whitespace() || character.is_control() ||
+        "\"'<>/=".contains(character)
+    })
+}
+
+/// The name of the JSON type of the value, for errors.
+fn json_type(value: &Value) -> &'static str {
+    match *value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::I64(_) | Value::U64(_) | Value::F64(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Whether the token is a block element, or a doctype, whose surrounding whitespace is removed
+/// when minifying.
+fn is_block(token: &Token) -> bool {
+    match *token {
+        Token::Html(ref element) => element.is_block(),
+        Token::Doctype(_) => true,
+        _ => false,
+    }
+}
+
+/// Collapses each run of whitespace in the text into a single space.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    for character in text.chars() {
+        if !character.is_whitespace() {
+            collapsed.push(character);
+        } else if !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+    }
+    collapsed
+}
+
+/// Whether the token is written inline with the text around it.
+fn is_inline(token: &Token) -> bool {
+    match *token {
+        Token::Text(_) |
+        Token::Raw(_) |
+        Token::Variable(_, _, _) |
+        Token::RawVariable(_, _, _) |
+        Token::Function(_) => true,
+        _ => false,
+    }
+}
+
+/// Escapes a variable written within a script, so it can be used within a JavaScript string,
+/// and can't close the script element.
+fn escape_script(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '/' if escaped.ends_with('<') => escaped.push_str("\\/"),
+            '\u{2028}' => escaped.push_str("\\u2028"),
+            '\u{2029}' => escaped.push_str("\\u2029"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Escapes a variable written within a style, so it can't break out of the declaration, or
+/// close the style element.
+fn escape_style(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        match character {
+            'a'...'z' | 'A'...'Z' | '0'...'9' | ' ' | '#' | '%' | ',' | '-' | '.' | '_' => {
+                escaped.push(character)
+            }
+            character => escaped.push_str(&*format!("\\{:x} ", character as u32)),
+        }
+    }
+    escaped
+}
+
+/// Percent encodes the control characters within a URL.
+fn encode_control_characters(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        if character.is_control() && (character as u32) < 0x80 {
+            encoded.push_str(&*format!("%{:02X}", character as u32));
+        } else {
+            encoded.push(character);
+        }
+    }
+    encoded
+}
+
+/// Whether the URL uses the `javascript:` scheme. Browsers ignore whitespace, and control
+/// characters within the scheme.
+fn is_javascript_url(url: &str) -> bool {
+    let scheme: String = url.chars()
+                            .filter(|character| !character.is_whitespace())
+                            .filter(|character| !character.is_control())
+                            .take(11)
+                            .collect();
+    scheme.to_lowercase() == "javascript:"
+}
+
+/// Used in place of `escape_attribute` for elements with raw attributes.
+fn raw_attribute(value: &str) -> String {
+    value.to_owned()
+}
+
+/// Breaks up any `--` in a comment, as it would end the comment early.
+fn escape_comment(comment: &str) -> String {
+    let mut escaped = String::with_capacity(comment.len());
+
+    for character in comment.chars() {
+        if character == '-' && escaped.ends_with('-') {
+            escaped.push(' ');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// Escapes the characters that would be read as markup in text.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Finds the value at the dotted path within the JSON. A number in the path indexes into an
+/// array, so `items.0.title` is the title of the first item.
+pub fn resolve_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in path.split('.') {
+        current = match index_value(current, segment) {
+            Ok(value) => value,
+            Err(_) => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Why a segment of a variable's name couldn't be resolved.
+enum IndexError {
+    /// The object has no field with the name.
+    Missing,
+    /// The value isn't an object, or an array, or is an array indexed by a name.
+    NotIndexable,
+    /// The index, and the length of the array.
+    OutOfBounds(usize, usize),
+    /// The array was indexed with a negative number.
+    Negative,
+}
+
+/// Gets the field of an object, or the item of an array named by `segment`. Whether the segment
+/// is a key, or an index is decided by the type of the value.
+fn index_value<'a>(value: &'a Value, segment: &str) -> Result<&'a Value, IndexError> {
+    match *value {
+        Value::Object(ref object) => {
+            match object.get(segment) {
+                Some(value) => Ok(value),
+                None => Err(IndexError::Missing),
+            }
+        }
+        Value::Array(ref array) => {
+            match segment.parse::<usize>() {
+                Ok(index) => {
+                    match array.get(index) {
+                        Some(value) => Ok(value),
+                        None => Err(IndexError::OutOfBounds(index, array.len())),
+                    }
+                }
+                Err(_) if segment.parse::<i64>().is_ok() => Err(IndexError::Negative),
+                Err(_) => Err(IndexError::NotIndexable),
+            }
+        }
+        _ => Err(IndexError::NotIndexable),
+    }
+}
+
+/// Finds the first call of a component, or function that a template doesn't have.
+struct UnknownCalls<'a> {
+    template: &'a Template,
+    unknown: Option<CodegenError>,
+}
+
+impl<'a> Visitor for UnknownCalls<'a> {
+    fn visit_component(&mut self, component_call: &ComponentCall) {
+        let name = component_call.name();
+        if self.unknown.is_none() && self.template.get_component(name).is_none() {
+            self.unknown = Some(CodegenError::NoSuchComponent(component_call.span(),
+                                                              String::from(name)));
+        }
+    }
+
+    fn visit_function(&mut self, function_call: &FunctionCall) {
+        let identifier = function_call.identifier();
+        let is_children = identifier == CHILDREN_MARKER && function_call.args().is_empty();
+        if self.unknown.is_none() && !is_children &&
+           self.template.get_function(identifier).is_none() {
+            self.unknown = Some(CodegenError::NoSuchFunction(function_call.span(),
+                                                             String::from(identifier)));
+        }
+    }
+}
+
+/// The error for the first call in `tokens` of a component, or function that `template`
+/// doesn't have, including calls within branches that wouldn't be rendered.
+pub fn find_unknown_call(tokens: &[Token], template: &Template) -> Option<CodegenError> {
+    let mut visitor = UnknownCalls {
+        template: template,
+        unknown: None,
+    };
+    walk(tokens, &mut visitor);
+    visitor.unknown
+}
+
+/// Converts JSON into a boolean, similar to JavaScript's truthiness.
+pub fn json_into_bool(json: &Value) -> bool {
+    match *json {
+        Value::Array(ref array) => !array.is_empty(),
+        Value::Null => false,
+        Value::Bool(ref boolean) => *boolean,
+        Value::I64(ref num) => *num != 0,
+        Value::U64(ref num) => *num != 0,
+        Value::F64(ref num) => *num != 0.0,
+        Value::String(ref string) => !string.is_empty(),
+        Value::Object(ref object) => !object.is_empty(),
     }
 }
 
@@ -4,15 +4,76 @@ use std::str::CharIndices;
 use super::*;
 use super::Lexeme::*;
 use super::Operator::*;
-/// Lexer
+/// Options changing how a template's source is read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseOptions {
+    /// The elements whose children are kept exactly as written, rather than being read as
+    /// template source, as with a raw block. Defaults to `script`, and `style`.
+    pub raw_tags: Vec<String>,
+    /// Whether `@name` is still a variable within raw blocks, and raw elements. Defaults to
+    /// `false`.
+    pub interpolate_raw: bool,
+    /// Whether an attribute given twice on an element keeps the last value, rather than being
+    /// an error. A `class` given twice is always merged. Defaults to `false`.
+    pub allow_duplicate_attributes: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            raw_tags: vec![String::from("script"), String::from("style")],
+            interpolate_raw: false,
+            allow_duplicate_attributes: false,
+        }
+    }
+}
+
+/// Splits a template's source into lexemes, the operators, and the words between them, each
+/// with the span of the source it was read from. Whitespace before an operator isn't kept, and
+/// `//`, and `/* */` comments are skipped.
 pub struct Lexer<'a> {
     input: Peekable<CharIndices<'a>>,
     output: Vec<Lexeme>,
+    line: usize,
+    column: usize,
+    end: Position,
+    /// Whether the last lexeme was a backslash, so a quote after it doesn't start a string.
+    escaped: bool,
+    options: ParseOptions,
+    /// Whether the lexer is within the head of an element named in `raw_tags`, so the brace
+    /// after it starts a raw block.
+    raw_element: bool,
+    /// Whether the lexer is within the attributes of a raw element, where a brace doesn't start
+    /// its children.
+    in_attributes: bool,
 }
 
 impl<'a> Lexer<'a> {
-    fn take(&mut self) -> Option<(usize, char)> {
-        self.input.next()
+    /// Takes the next character, and the position it was found at. A carriage return doesn't
+    /// move the column, so CRLF line endings are only counted as a single newline. The position
+    /// directly after the character is kept in `end`, for the span of the current lexeme.
+    fn take(&mut self) -> Option<(Position, char)> {
+        match self.input.next() {
+            Some((offset, character)) => {
+                let position = Position::new(self.line, self.column, offset);
+
+                if character == '\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else if character != CARRAGE_RETURN {
+                    self.column += 1;
+                }
+                self.end = Position::new(self.line, self.column, offset + character.len_utf8());
+
+                Some((position, character))
+            }
+            None => None,
+        }
+    }
+
+    /// The span from `start` up to, and including the last character taken.
+    fn span(&self, start: Position) -> Span {
+        Span::new(start, self.end)
     }
 
     fn peek(&mut self) -> Option<&(usize, char)> {
@@ -28,54 +89,549 @@ impl<'a> Lexer<'a> {
         self.output
     }
 
-    /// TODO
+    /// Reads all of `input`, the output of which is available from `output`.
     pub fn new(input: &'a str) -> Self {
+        Lexer::with_options(input, &ParseOptions::default())
+    }
+
+    /// Reads all of `input` with the options, the output of which is available from `output`.
+    pub fn with_options(input: &'a str, options: &ParseOptions) -> Self {
         let mut lexer = Lexer {
             input: input.char_indices().peekable(),
             output: Vec::new(),
+            line: 1,
+            column: 1,
+            end: Position::default(),
+            escaped: false,
+            options: options.clone(),
+            raw_element: false,
+            in_attributes: false,
         };
 
         while let Some(token) = lexer.take_token() {
+            let starts_raw_block = lexer.starts_raw_block(&token);
             lexer.push(token);
+
+            if starts_raw_block {
+                if let Some(close) = lexer.take_raw_text() {
+                    lexer.push(close);
+                }
+            }
         }
 
         lexer
     }
 
-    fn take_token(&mut self) -> Option<Lexeme> {
-        let mut leading_space = false;
+    /// Reads `input` into its lexemes.
+    pub fn tokenize(input: &str) -> Vec<Lexeme> {
+        Lexer::new(input).output()
+    }
+
+    /// Skips a comment, up to the end of the line. The opening `/` has already been taken.
+    fn skip_comment(&mut self) {
         while let Some(&(_, character)) = self.peek() {
-            if character.is_whitespace() || character == CARRAGE_RETURN {
-                let _ = self.take();
-                leading_space = true;
-            } else {
+            if character == '\n' {
+                break;
+            }
+            let _ = self.take();
+        }
+    }
+
+    /// Skips a block comment, up to, and including the `*/` that ends it. The opening `/` at
+    /// `position` has already been taken. A comment without a `*/` runs to the end of the
+    /// source, and is returned as an open comment, for the parser to report.
+    fn skip_block_comment(&mut self, position: Position) -> Option<Lexeme> {
+        let _ = self.take();
+        let span = self.span(position);
+        let mut star = false;
+        while let Some((_, character)) = self.take() {
+            if star && character == FORWARDSLASH {
+                return None;
+            }
+            star = character == STAR;
+        }
+        Some(OpenComment(span))
+    }
+
+    /// Whether the next character other than whitespace is a `{`, so the `.` just taken starts
+    /// a raw block.
+    fn is_raw_block(&mut self) -> bool {
+        let mut input = self.input.clone();
+        while let Some((_, character)) = input.next() {
+            if !character.is_whitespace() {
+                return character == OPENBRACE;
+            }
+        }
+        false
+    }
+
+    /// Takes a raw block's brace, and everything up to the brace that closes it, which is kept
+    /// as is in a single word, returning the closing brace. Braces within the block are only
+    /// counted, so that they don't close the block.
+    fn take_raw_block(&mut self) -> Option<Lexeme> {
+        let _ = self.take_whitespace();
+        if let Some((position, _)) = self.take() {
+            let span = self.span(position);
+            self.push(Symbol(span, OpenBrace));
+        }
+        self.take_raw_text()
+    }
+
+    /// Whether the lexeme is the brace starting the children of a raw element. Otherwise keeps
+    /// track of whether the lexer is within the head of one, which ends at anything that can't
+    /// be part of an element's head.
+    fn starts_raw_block(&mut self, lexeme: &Lexeme) -> bool {
+        match (self.output.last(), lexeme) {
+            (Some(&Symbol(_, ForwardSlash)), &Word(_, ref tag)) => {
+                self.raw_element = self.options.raw_tags.iter().any(|raw| raw == tag.trim());
+                self.in_attributes = false;
+                return false;
+            }
+            _ if !self.raw_element => return false,
+            (_, &Symbol(_, OpenBrace)) if !self.in_attributes => {
+                self.raw_element = false;
+                return true;
+            }
+            (_, &Symbol(_, OpenParam)) => self.in_attributes = true,
+            (_, &Symbol(_, CloseParam)) => self.in_attributes = false,
+            _ if self.in_attributes => {}
+            (Some(&Symbol(_, Dot)), &Word(_, _)) |
+            (Some(&Symbol(_, Pound)), &Word(_, _)) |
+            (_, &Symbol(_, Dot)) |
+            (_, &Symbol(_, Pound)) |
+            (_, &Symbol(_, Star)) |
+            (_, &Symbol(_, LessThan)) |
+            (_, &Symbol(_, GreaterThan)) => {}
+            _ => self.raw_element = false,
+        }
+        false
+    }
+
+    /// Takes everything up to the brace that closes a raw block, which is kept as is, returning
+    /// the closing brace. Braces within the block are only counted, so that they don't close
+    /// the block. An `@` followed by a name is still a variable if `interpolate_raw` is set.
+    fn take_raw_text(&mut self) -> Option<Lexeme> {
+        let interpolate = self.options.interpolate_raw;
+        let mut text = String::new();
+        let mut start = None;
+        let mut depth = 0;
+
+        while let Some(&(_, character)) = self.peek() {
+            match character {
+                OPENBRACE => depth += 1,
+                CLOSEBRACE if depth == 0 => {
+                    if let Some(start) = start {
+                        let span = self.span(start);
+                        self.push(RawText(span, text));
+                    }
+                    return match self.take() {
+                        Some((position, _)) => Some(Symbol(self.span(position), CloseBrace)),
+                        None => None,
+                    };
+                }
+                CLOSEBRACE => depth -= 1,
+                AT if interpolate && self.is_interpolation() => {
+                    if let Some(start) = start.take() {
+                        let span = self.span(start);
+                        self.push(RawText(span, text));
+                        text = String::new();
+                    }
+                    self.take_interpolation();
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some((position, character)) = self.take() {
+                if start.is_none() {
+                    start = Some(position);
+                }
+                text.push(character);
+            }
+        }
+
+        match start {
+            Some(start) => Some(RawText(self.span(start), text)),
+            None => None,
+        }
+    }
+
+    /// Whether a `|` just taken starts literal text, which it does when it's the first thing on
+    /// a line, or directly within a brace, rather than following a variable as a filter.
+    fn is_literal_text(&self, leading_whitespace: &str) -> bool {
+        if leading_whitespace.contains('\n') {
+            return true;
+        }
+
+        match self.output.last() {
+            Some(&Symbol(_, OpenBrace)) => true,
+            Some(&Word(_, ref text)) |
+            Some(&Literal(_, ref text)) => text[text.trim_right().len()..].contains('\n'),
+            _ => false,
+        }
+    }
+
+    /// Takes literal text up to the end of the line, or the brace that closes the block it's
+    /// within. Operators within the text are kept as is, other than an `@` followed by a name,
+    /// which is still a variable if `interpolate` is set. The text keeps the whitespace after
+    /// it, so the line breaks between lines of literal text are kept, though not the indentation
+    /// before the `|` of the next line.
+    fn take_literal_text(&mut self, interpolate: bool) {
+        let mut text = String::new();
+        let mut start = None;
+        let mut depth = 0;
+
+        while let Some(&(_, character)) = self.peek() {
+            match character {
+                '\n' => {
+                    let position = self.end;
+                    let whitespace = self.take_whitespace();
+                    let span = Span::new(start.unwrap_or(position), position);
+                    match self.peek() {
+                        Some(&(_, PIPE)) => {
+                            let line_end = whitespace.rfind('\n').map_or(0, |index| index + 1);
+                            text.push_str(&whitespace[..line_end]);
+                        }
+                        _ => text.push_str(&*whitespace),
+                    }
+                    self.push(Literal(span, text));
+                    return;
+                }
+                CLOSEBRACE if depth == 0 => break,
+                CLOSEBRACE => depth -= 1,
+                OPENBRACE => depth += 1,
+                AT if interpolate && self.is_interpolation() => {
+                    if let Some(start) = start.take() {
+                        let span = self.span(start);
+                        self.push(Literal(span, text));
+                        text = String::new();
+                    }
+                    self.take_interpolation();
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some((position, character)) = self.take() {
+                if character != CARRAGE_RETURN {
+                    if start.is_none() {
+                        start = Some(position);
+                    }
+                    text.push(character);
+                }
+            }
+        }
+
+        if let Some(start) = start {
+            let span = self.span(start);
+            self.push(Literal(span, text));
+        }
+    }
+
+    /// Whether the next character is an `@` followed by the start of a name.
+    fn is_interpolation(&mut self) -> bool {
+        let mut input = self.input.clone();
+        let _ = input.next();
+        match input.next() {
+            Some((_, character)) => is_name_character(character),
+            None => false,
+        }
+    }
+
+    /// Takes an `@`, and the name after it, including any members separated by a `.`.
+    fn take_interpolation(&mut self) {
+        if let Some((position, _)) = self.take() {
+            let span = self.span(position);
+            self.push(Symbol(span, At));
+        }
+
+        loop {
+            let mut name = String::new();
+            let mut start = None;
+            while let Some(&(_, character)) = self.peek() {
+                if !is_name_character(character) {
+                    break;
+                }
+                if let Some((position, character)) = self.take() {
+                    if start.is_none() {
+                        start = Some(position);
+                    }
+                    name.push(character);
+                }
+            }
+            if let Some(start) = start {
+                let span = self.span(start);
+                self.push(Word(span, name));
+            }
+
+            let mut input = self.input.clone();
+            match (input.next(), input.next()) {
+                (Some((_, DOT)), Some((_, character))) if is_name_character(character) => {
+                    if let Some((position, _)) = self.take() {
+                        let span = self.span(position);
+                        self.push(Symbol(span, Dot));
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Takes the whitespace up to the next lexeme. Carriage returns are dropped, so CRLF line
+    /// endings are the same as newlines.
+    fn take_whitespace(&mut self) -> String {
+        let mut whitespace = String::new();
+        while let Some(&(_, character)) = self.peek() {
+            if !character.is_whitespace() {
                 break;
             }
+            let _ = self.take();
+            if character != CARRAGE_RETURN {
+                whitespace.push(character);
+            }
+        }
+        whitespace
+    }
+
+    /// Whether a `'` is a quote, which it is where it starts a value, after a `=`, `(`, or `,`.
+    /// Otherwise it's an apostrophe, and part of the text.
+    fn is_single_quote(&self) -> bool {
+        match self.output.last() {
+            Some(&Symbol(_, Equals)) |
+            Some(&Symbol(_, OpenParam)) |
+            Some(&Symbol(_, Comma)) => true,
+            _ => false,
+        }
+    }
+
+    /// Takes the number that's next in the input, along with the whitespace after it. Returns
+    /// `None`, taking nothing, if the input doesn't start with a number, or the number runs on
+    /// into a word, as in `3px`.
+    fn take_number(&mut self, leading_whitespace: &str) -> Option<Lexeme> {
+        let length = match number_length(self.input.clone()) {
+            Some(length) => length,
+            None => return None,
+        };
+
+        let mut number = leading_whitespace.to_owned();
+        let mut start = None;
+        for _ in 0..length {
+            if let Some((position, character)) = self.take() {
+                if start.is_none() {
+                    start = Some(position);
+                }
+                number.push(character);
+            }
+        }
+
+        match start {
+            Some(start) => {
+                let span = self.span(start);
+                number.push_str(&*self.take_whitespace());
+                Some(Number(span, number))
+            }
+            None => None,
+        }
+    }
+
+    /// Whether the string started by the `quote` just taken is closed on the same line.
+    fn is_closed_string(&self, quote: char) -> bool {
+        let mut input = self.input.clone();
+        while let Some((_, character)) = input.next() {
+            match character {
+                '\n' => return false,
+                BACKSLASH => {
+                    if let Some((_, '\n')) = input.next() {
+                        return false;
+                    }
+                }
+                character if character == quote => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Takes a string started by the `quote` at `start`, up to, and including the quote that
+    /// closes it, as a single lexeme. A string that isn't closed on the same line is only the
+    /// quote, so the parser can point to where it starts.
+    fn take_string(&mut self, start: Position, quote: char, operator: Operator) -> Lexeme {
+        if !self.is_closed_string(quote) {
+            return Symbol(self.span(start), operator);
+        }
+
+        let mut text = String::new();
+        while let Some((_, character)) = self.take() {
+            match character {
+                BACKSLASH => self.take_escape(&mut text),
+                character if character == quote => break,
+                character => text.push(character),
+            }
+        }
+        Str(self.span(start), operator, text)
+    }
+
+    /// Takes the escape after a backslash within a string, pushing the character it stands for
+    /// onto `text`. `\"`, `\'`, `\n`, `\t`, and `\u{..}` with up to six hex digits are
+    /// replaced, while `\\`, and `\@` are kept as is for the parser, as whether an `@` was
+    /// escaped decides if it starts a variable. A backslash before anything else is kept.
+    fn take_escape(&mut self, text: &mut String) {
+        let escaped = match self.peek() {
+            Some(&(_, character)) => character,
+            None => return text.push(BACKSLASH),
+        };
+
+        let replacement = match escaped {
+            DOUBLEQUOTE | SINGLEQUOTE => Some(escaped),
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'u' => {
+                match self.unicode_escape() {
+                    Some((character, length)) => {
+                        for _ in 0..length {
+                            let _ = self.take();
+                        }
+                        return text.push(character);
+                    }
+                    None => None,
+                }
+            }
+            BACKSLASH | AT => {
+                let _ = self.take();
+                text.push(BACKSLASH);
+                return text.push(escaped);
+            }
+            _ => None,
+        };
+
+        match replacement {
+            Some(character) => {
+                let _ = self.take();
+                text.push(character);
+            }
+            None => text.push(BACKSLASH),
+        }
+    }
+
+    /// The character of a `u{..}` escape that's next in the input, and the number of characters
+    /// it's written with, if it's a valid escape.
+    fn unicode_escape(&self) -> Option<(char, usize)> {
+        let mut input = self.input.clone();
+        match (input.next(), input.next()) {
+            (Some((_, 'u')), Some((_, OPENBRACE))) => {}
+            _ => return None,
+        }
+
+        let mut digits = String::new();
+        while let Some((_, character)) = input.next() {
+            match character {
+                CLOSEBRACE if !digits.is_empty() => {
+                    return u32::from_str_radix(&*digits, 16)
+                               .ok()
+                               .and_then(::std::char::from_u32)
+                               .map(|character| (character, digits.len() + 3));
+                }
+                character if character.is_digit(16) && digits.len() < 6 => {
+                    digits.push(character)
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Takes the next lexeme. A word keeps the whitespace on either side of it, which the
+    /// parser collapses, or keeps as is, while the whitespace before a symbol is dropped.
+    fn take_token(&mut self) -> Option<Lexeme> {
+        let leading_whitespace = self.take_whitespace();
+
+        let escaped = self.escaped;
+        self.escaped = false;
+
+        if let Some(&Symbol(_, Equals)) = self.output.last() {
+            if let Some(number) = self.take_number(&*leading_whitespace) {
+                return Some(number);
+            }
         }
 
         match self.take() {
-            Some((index, AMPERSAND)) => Some(Symbol(index, Ampersand)),
-            Some((index, AT)) => Some(Symbol(index, At)),
-            Some((index, BACKSLASH)) => Some(Symbol(index, BackSlash)),
-            Some((index, COMMA)) => Some(Symbol(index, Comma)),
-            Some((index, CLOSEBRACE)) => Some(Symbol(index, CloseBrace)),
-            Some((index, CLOSEPARAM)) => Some(Symbol(index, CloseParam)),
-            Some((index, DOLLAR)) => Some(Symbol(index, Dollar)),
-            Some((index, DOT)) => Some(Symbol(index, Dot)),
-            Some((index, DOUBLEQUOTE)) => Some(Symbol(index, Quote)),
-            Some((index, EQUALS)) => Some(Symbol(index, Equals)),
-            Some((index, FORWARDSLASH)) => Some(Symbol(index, ForwardSlash)),
-            Some((index, OPENBRACE)) => Some(Symbol(index, OpenBrace)),
-            Some((index, OPENPARAM)) => Some(Symbol(index, OpenParam)),
-            Some((index, POUND)) => Some(Symbol(index, Pound)),
-            Some((index, STAR)) => Some(Symbol(index, Star)),
-            Some((index, character)) => {
-                let mut word = if leading_space {
-                    ' '.to_string()
+            Some((position, AMPERSAND)) => Some(Symbol(self.span(position), Ampersand)),
+            Some((position, AT)) => Some(Symbol(self.span(position), At)),
+            Some((position, BACKSLASH)) => {
+                // An apostrophe is already text where it can't start a string, so the backslash
+                // before it is dropped, leaving it part of the word after it.
+                if let Some(&(_, SINGLEQUOTE)) = self.peek() {
+                    if !self.is_single_quote() {
+                        return self.take_token();
+                    }
+                }
+                self.escaped = true;
+                Some(Symbol(self.span(position), BackSlash))
+            }
+            Some((position, COMMA)) => Some(Symbol(self.span(position), Comma)),
+            Some((position, CLOSEBRACE)) => Some(Symbol(self.span(position), CloseBrace)),
+            Some((position, CLOSEPARAM)) => Some(Symbol(self.span(position), CloseParam)),
+            Some((position, DOLLAR)) => Some(Symbol(self.span(position), Dollar)),
+            Some((position, DOT)) => {
+                let span = self.span(position);
+                if self.is_raw_block() {
+                    self.push(Symbol(span, Dot));
+                    self.take_raw_block()
                 } else {
-                    String::new()
+                    Some(Symbol(span, Dot))
+                }
+            }
+            Some((position, DOUBLEQUOTE)) if escaped => Some(Symbol(self.span(position), Quote)),
+            Some((position, DOUBLEQUOTE)) => Some(self.take_string(position, DOUBLEQUOTE, Quote)),
+            Some((position, SINGLEQUOTE)) if escaped => {
+                Some(Symbol(self.span(position), SingleQuote))
+            }
+            Some((position, SINGLEQUOTE)) if self.is_single_quote() => {
+                Some(self.take_string(position, SINGLEQUOTE, SingleQuote))
+            }
+            Some((position, EQUALS)) => Some(Symbol(self.span(position), Equals)),
+            Some((position, FORWARDSLASH)) => {
+                let (is_comment, is_block_comment) = match self.peek() {
+                    Some(&(_, FORWARDSLASH)) => (true, false),
+                    Some(&(_, STAR)) => (false, true),
+                    _ => (false, false),
                 };
 
+                if is_comment {
+                    self.skip_comment();
+                    self.take_token()
+                } else if is_block_comment {
+                    match self.skip_block_comment(position) {
+                        Some(open_comment) => Some(open_comment),
+                        None => self.take_token(),
+                    }
+                } else {
+                    Some(Symbol(self.span(position), ForwardSlash))
+                }
+            }
+            Some((position, GREATERTHAN)) => Some(Symbol(self.span(position), GreaterThan)),
+            Some((position, LESSTHAN)) => Some(Symbol(self.span(position), LessThan)),
+            Some((position, OPENBRACE)) => Some(Symbol(self.span(position), OpenBrace)),
+            Some((position, OPENPARAM)) => Some(Symbol(self.span(position), OpenParam)),
+            Some((position, PIPE)) => {
+                if self.is_literal_text(&*leading_whitespace) {
+                    let interpolate = match self.peek() {
+                        Some(&(_, PIPE)) => {
+                            let _ = self.take();
+                            false
+                        }
+                        _ => true,
+                    };
+                    self.take_literal_text(interpolate);
+                    self.take_token()
+                } else {
+                    Some(Symbol(self.span(position), Pipe))
+                }
+            }
+            Some((position, POUND)) => Some(Symbol(self.span(position), Pound)),
+            Some((position, STAR)) => Some(Symbol(self.span(position), Star)),
+            Some((position, character)) => {
+                let mut word = leading_whitespace;
                 word.push(character);
 
                 while let Some(&(_, character)) = self.peek() {
@@ -83,9 +639,8 @@ impl<'a> Lexer<'a> {
                         // The following case is for determining if a character divides words or
                         // if it is packaged with the words. So things like "Hello}" comes out
                         // as Text: "Hello" Operator: "}"
-                        ch if !ch.is_alphanumeric() && !ch.is_whitespace() && ch != '-' &&
-                              ch != '_' => {
-                            return Some(Word(index, word));
+                        ch if !is_name_character(ch) && !ch.is_whitespace() => {
+                            return Some(Word(self.span(position), word));
                         }
                         ch => {
                             if !ch.is_whitespace() {
@@ -98,27 +653,81 @@ impl<'a> Lexer<'a> {
                     }
                 }
 
-                if let Some(&(_, ch)) = self.peek() {
-                    if ch == '\r' {
-                        let _ = self.take();
-                    } else if ch.is_whitespace() {
-                        word.push(ch);
-                        let _ = self.take();
-                    }
-                }
-                Some(Word(index, word))
+                let span = self.span(position);
+                word.push_str(&*self.take_whitespace());
+                Some(Word(span, word))
             }
             None => None,
         }
     }
 }
 
-#[allow(unused_imports)]
+/// The number of characters in the number at the start of `input`, an optional `-`, followed by
+/// digits with an optional fraction, where the digits before the `.` can be left out.
+fn number_length(mut input: Peekable<CharIndices>) -> Option<usize> {
+    let mut length = 0;
+    let mut digits = 0;
+    let mut decimal = false;
+
+    if let Some(&(_, '-')) = input.peek() {
+        let _ = input.next();
+        length += 1;
+    }
+
+    while let Some(&(_, character)) = input.peek() {
+        match character {
+            character if character.is_digit(10) => digits += 1,
+            // A `.` is only part of the number when a digit follows it.
+            DOT if !decimal => {
+                let mut lookahead = input.clone();
+                let _ = lookahead.next();
+                match lookahead.next() {
+                    Some((_, next)) if next.is_digit(10) => decimal = true,
+                    _ => break,
+                }
+            }
+            character if is_name_character(character) => return None,
+            _ => break,
+        }
+        let _ = input.next();
+        length += 1;
+    }
+
+    if digits == 0 {
+        None
+    } else {
+        Some(length)
+    }
+}
+
+/// Whether the character can be part of a word, such as a variable's name.
+pub fn is_name_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '-' || character == '_'
+}
+
+#[allow(dead_code, unused_imports)]
 mod tests {
-    use super::Lexer;
+    use super::{Lexer, ParseOptions};
     use compiler::tokens::Lexeme;
-    use compiler::tokens::Lexeme::{Word, Symbol};
+    use compiler::tokens::Lexeme::{Literal, Number, OpenComment, RawText, Str, Word, Symbol};
     use compiler::tokens::Operator::*;
+    use compiler::tokens::{Position, Span};
+
+    /// Position of a character on the first line of an ASCII source.
+    fn pos(offset: usize) -> Position {
+        Position::new(1, offset + 1, offset)
+    }
+
+    /// Span of a lexeme on the first line of an ASCII source.
+    fn span(offset: usize, len: usize) -> Span {
+        Span::new(pos(offset), pos(offset + len))
+    }
+
+    /// Span of an ASCII lexeme on any line.
+    fn line_span(line: usize, column: usize, offset: usize, len: usize) -> Span {
+        Span::new(Position::new(line, column, offset),
+                  Position::new(line, column + len, offset + len))
+    }
 
     #[test]
     fn ignore_spaces() {
@@ -131,162 +740,447 @@ mod tests {
     fn ampersand_operator() {
         let lexer = Lexer::new("&");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, Ampersand)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Ampersand)]);
     }
 
     #[test]
     fn at_operator() {
         let lexer = Lexer::new("@");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, At)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), At)]);
     }
     #[test]
     fn back_slash_operator() {
         let lexer = Lexer::new("\\");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, BackSlash)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), BackSlash)]);
     }
     #[test]
     fn close_brace_operator() {
         let lexer = Lexer::new("}");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, CloseBrace)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), CloseBrace)]);
     }
     #[test]
     fn close_param_operator() {
         let lexer = Lexer::new(")");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, CloseParam)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), CloseParam)]);
     }
     #[test]
     fn dollar_operator() {
         let lexer = Lexer::new("$");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, Dollar)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Dollar)]);
+    }
+    #[test]
+    fn pipe_operator() {
+        let lexer = Lexer::new("|");
+
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Pipe)]);
     }
     #[test]
     fn dot_operator() {
         let lexer = Lexer::new(".");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, Dot)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Dot)]);
     }
     #[test]
     fn equals_operator() {
         let lexer = Lexer::new("=");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, Equals)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Equals)]);
     }
     #[test]
     fn forward_slash_operator() {
         let lexer = Lexer::new("/");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, ForwardSlash)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), ForwardSlash)]);
+    }
+    #[test]
+    fn greater_than_operator() {
+        let lexer = Lexer::new(">");
+
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), GreaterThan)]);
+    }
+    #[test]
+    fn less_than_operator() {
+        let lexer = Lexer::new("<");
+
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), LessThan)]);
     }
     #[test]
     fn open_brace_operator() {
         let lexer = Lexer::new("{");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, OpenBrace)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), OpenBrace)]);
     }
     #[test]
     fn open_param_operator() {
         let lexer = Lexer::new("(");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, OpenParam)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), OpenParam)]);
     }
     #[test]
     fn pound_operator() {
         let lexer = Lexer::new("#");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, Pound)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Pound)]);
     }
     #[test]
     fn quote_operator() {
         let lexer = Lexer::new("\"");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, Quote)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Quote)]);
     }
     #[test]
     fn star_operator() {
         let lexer = Lexer::new("*");
 
-        assert_eq!(lexer.output(), vec![Symbol(0, Star)]);
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Star)]);
+    }
+    #[test]
+    fn comma_operator() {
+        let lexer = Lexer::new(",");
+
+        assert_eq!(lexer.output(), vec![Symbol(span(0, 1), Comma)]);
     }
     #[test]
     fn all_operators() {
-        let lexer = Lexer::new("&@\\})$.=/{(#\"*,");
-        let expected = vec![Symbol(0, Ampersand),
-                            Symbol(1, At),
-                            Symbol(2, BackSlash),
-                            Symbol(3, CloseBrace),
-                            Symbol(4, CloseParam),
-                            Symbol(5, Dollar),
-                            Symbol(6, Dot),
-                            Symbol(7, Equals),
-                            Symbol(8, ForwardSlash),
-                            Symbol(9, OpenBrace),
-                            Symbol(10, OpenParam),
-                            Symbol(11, Pound),
-                            Symbol(12, Quote),
-                            Symbol(13, Star),
-                            Symbol(14, Comma)];
-
-        for (actual, expected) in lexer.output().iter().zip(expected.iter()) {
-            assert_eq!(actual, expected);
-        }
+        let expected = vec![Symbol(span(0, 1), Ampersand),
+                            Symbol(span(1, 1), At),
+                            Symbol(span(2, 1), BackSlash),
+                            Symbol(span(3, 1), CloseBrace),
+                            Symbol(span(4, 1), CloseParam),
+                            Symbol(span(5, 1), Dollar),
+                            Symbol(span(6, 1), Dot),
+                            Symbol(span(7, 1), Equals),
+                            Symbol(span(8, 1), ForwardSlash),
+                            Symbol(span(9, 1), OpenBrace),
+                            Symbol(span(10, 1), OpenParam),
+                            Symbol(span(11, 1), Pound),
+                            Symbol(span(12, 1), Quote),
+                            Symbol(span(13, 1), Star),
+                            Symbol(span(14, 1), Comma),
+                            Symbol(span(15, 1), Pipe),
+                            Symbol(span(16, 1), LessThan),
+                            Symbol(span(17, 1), GreaterThan)];
+
+        assert_eq!(Lexer::tokenize("&@\\})$.=/{(#\"*,|<>"), expected);
+    }
+    #[test]
+    fn tokenize() {
+        assert_eq!(Lexer::tokenize("/p{Hi}"), Lexer::new("/p{Hi}").output());
+        assert_eq!(Lexer::tokenize("@name"),
+                   vec![Symbol(span(0, 1), At), Word(span(1, 4), "name".to_owned())]);
     }
+    #[test]
+    fn chained_classes() {
+        let lexer = Lexer::new("/p.col-6.btn-primary");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), ForwardSlash),
+                        Word(span(1, 1), "p".to_owned()),
+                        Symbol(span(2, 1), Dot),
+                        Word(span(3, 5), "col-6".to_owned()),
+                        Symbol(span(8, 1), Dot),
+                        Word(span(9, 11), "btn-primary".to_owned())]);
+    }
+
+    #[test]
+    fn single_quotes() {
+        let lexer = Lexer::new("(a='\"')'s");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), OpenParam),
+                        Word(span(1, 1), "a".to_owned()),
+                        Symbol(span(2, 1), Equals),
+                        Str(span(3, 3), SingleQuote, "\"".to_owned()),
+                        Symbol(span(6, 1), CloseParam),
+                        Word(span(7, 2), "'s".to_owned())]);
+    }
+
+    #[test]
+    fn escaped_single_quotes() {
+        assert_eq!(Lexer::tokenize(r"a=\'b\' It\'s"),
+                   vec![Word(span(0, 1), "a".to_owned()),
+                        Symbol(span(1, 1), Equals),
+                        Symbol(span(2, 1), BackSlash),
+                        Symbol(span(3, 1), SingleQuote),
+                        Word(span(4, 1), "b".to_owned()),
+                        Word(span(6, 1), "' ".to_owned()),
+                        Word(span(8, 2), "It".to_owned()),
+                        Word(span(11, 2), "'s".to_owned())]);
+    }
+
+    #[test]
+    fn hyphenated_words() {
+        let lexer = Lexer::new("#main-nav(aria-label=x)");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), Pound),
+                        Word(span(1, 8), "main-nav".to_owned()),
+                        Symbol(span(9, 1), OpenParam),
+                        Word(span(10, 10), "aria-label".to_owned()),
+                        Symbol(span(20, 1), Equals),
+                        Word(span(21, 1), "x".to_owned()),
+                        Symbol(span(22, 1), CloseParam)]);
+    }
+
     #[test]
     fn word() {
         let lexer = Lexer::new("Hello");
 
-        assert_eq!(lexer.output(), vec![Word(0, "Hello".to_owned())]);
+        assert_eq!(lexer.output(), vec![Word(span(0, 5), "Hello".to_owned())]);
     }
     #[test]
     fn words() {
         let lexer = Lexer::new("The Lord Of The Rings");
 
         assert_eq!(lexer.output(),
-                   vec![Word(0, "The ".to_owned()),
-                        Word(4, "Lord ".to_owned()),
-                        Word(9, "Of ".to_owned()),
-                        Word(12, "The ".to_owned()),
-                        Word(16, "Rings".to_owned())]);
+                   vec![Word(span(0, 3), "The ".to_owned()),
+                        Word(span(4, 4), "Lord ".to_owned()),
+                        Word(span(9, 2), "Of ".to_owned()),
+                        Word(span(12, 3), "The ".to_owned()),
+                        Word(span(16, 5), "Rings".to_owned())]);
+    }
+    #[test]
+    fn hyphenated_word() {
+        let lexer = Lexer::new(".btn-primary");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), Dot), Word(span(1, 11), "btn-primary".to_owned())]);
+    }
+    #[test]
+    fn word_with_digits() {
+        let lexer = Lexer::new("/h1.col-6");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), ForwardSlash),
+                        Word(span(1, 2), "h1".to_owned()),
+                        Symbol(span(3, 1), Dot),
+                        Word(span(4, 5), "col-6".to_owned())]);
+    }
+    #[test]
+    fn hyphenated_attribute_key() {
+        let lexer = Lexer::new("(data-role=main)");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), OpenParam),
+                        Word(span(1, 9), "data-role".to_owned()),
+                        Symbol(span(10, 1), Equals),
+                        Word(span(11, 4), "main".to_owned()),
+                        Symbol(span(15, 1), CloseParam)]);
     }
     #[test]
     fn words_and_operators() {
         let lexer = Lexer::new("@{Hello}.");
 
         assert_eq!(lexer.output(),
-                   vec![Symbol(0, At),
-                        Symbol(1, OpenBrace),
-                        Word(2, "Hello".to_string()),
-                        Symbol(7, CloseBrace),
-                        Symbol(8, Dot)]);
+                   vec![Symbol(span(0, 1), At),
+                        Symbol(span(1, 1), OpenBrace),
+                        Word(span(2, 5), "Hello".to_string()),
+                        Symbol(span(7, 1), CloseBrace),
+                        Symbol(span(8, 1), Dot)]);
     }
     #[test]
     fn hello_world() {
         let lexer = Lexer::new("/html{ /body { /p{Hello /u{World}!}}}");
-        let expected_tokens = vec![Symbol(0, ForwardSlash),
-                                   Word(1, "html".to_owned()),
-                                   Symbol(5, OpenBrace),
-                                   Symbol(7, ForwardSlash),
-                                   Word(8, "body ".to_owned()),
-                                   Symbol(13, OpenBrace),
-                                   Symbol(15, ForwardSlash),
-                                   Word(16, "p".to_owned()),
-                                   Symbol(17, OpenBrace),
-                                   Word(18, "Hello ".to_owned()),
-                                   Symbol(24, ForwardSlash),
-                                   Word(25, "u".to_owned()),
-                                   Symbol(26, OpenBrace),
-                                   Word(27, "World".to_owned()),
-                                   Symbol(32, CloseBrace),
-                                   Word(33, "!".to_owned()),
-                                   Symbol(34, CloseBrace),
-                                   Symbol(35, CloseBrace),
-                                   Symbol(36, CloseBrace)];
+        let expected_tokens = vec![Symbol(span(0, 1), ForwardSlash),
+                                   Word(span(1, 4), "html".to_owned()),
+                                   Symbol(span(5, 1), OpenBrace),
+                                   Symbol(span(7, 1), ForwardSlash),
+                                   Word(span(8, 4), "body ".to_owned()),
+                                   Symbol(span(13, 1), OpenBrace),
+                                   Symbol(span(15, 1), ForwardSlash),
+                                   Word(span(16, 1), "p".to_owned()),
+                                   Symbol(span(17, 1), OpenBrace),
+                                   Word(span(18, 5), "Hello ".to_owned()),
+                                   Symbol(span(24, 1), ForwardSlash),
+                                   Word(span(25, 1), "u".to_owned()),
+                                   Symbol(span(26, 1), OpenBrace),
+                                   Word(span(27, 5), "World".to_owned()),
+                                   Symbol(span(32, 1), CloseBrace),
+                                   Word(span(33, 1), "!".to_owned()),
+                                   Symbol(span(34, 1), CloseBrace),
+                                   Symbol(span(35, 1), CloseBrace),
+                                   Symbol(span(36, 1), CloseBrace)];
         for (actual, expected) in lexer.output().iter().zip(expected_tokens.iter()) {
             assert_eq!(actual, expected);
         }
     }
+
+    #[test]
+    fn line_and_column() {
+        let lexer = Lexer::new("/p\n  @name");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(line_span(1, 1, 0, 1), ForwardSlash),
+                        Word(line_span(1, 2, 1, 1), "p\n  ".to_owned()),
+                        Symbol(line_span(2, 3, 5, 1), At),
+                        Word(line_span(2, 4, 6, 4), "name".to_owned())]);
+    }
+    #[test]
+    fn crlf_is_a_single_line() {
+        let lexer = Lexer::new("/p\r\n\r\n@name");
+
+        assert_eq!(lexer.output()[2], Symbol(line_span(3, 1, 6, 1), At));
+    }
+    #[test]
+    fn raw_block() {
+        let lexer = Lexer::new("/p. {a{/@}}@");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), ForwardSlash),
+                        Word(span(1, 1), "p".to_owned()),
+                        Symbol(span(2, 1), Dot),
+                        Symbol(span(4, 1), OpenBrace),
+                        RawText(span(5, 5), "a{/@}".to_owned()),
+                        Symbol(span(10, 1), CloseBrace),
+                        Symbol(span(11, 1), At)]);
+    }
+
+    #[test]
+    fn raw_element() {
+        let lexer = Lexer::new("/script(a=\"{\").b#c*{d{}/}/p{e}");
+        let output = lexer.output();
+
+        assert_eq!(output[5], Str(span(10, 3), Quote, "{".to_owned()));
+        assert_eq!(output[13], RawText(span(20, 4), "d{}/".to_owned()));
+        assert_eq!(output[14], Symbol(span(24, 1), CloseBrace));
+        assert_eq!(output[17], Symbol(span(27, 1), OpenBrace));
+        assert_eq!(output[18], Word(span(28, 1), "e".to_owned()));
+    }
+
+    #[test]
+    fn raw_element_without_children() {
+        let lexer = Lexer::new("/script(src=a) /p{b}");
+
+        assert_eq!(lexer.output()[10], Word(span(18, 1), "b".to_owned()));
+    }
+
+    #[test]
+    fn interpolate_raw() {
+        let options = ParseOptions { interpolate_raw: true, ..ParseOptions::default() };
+        let lexer = Lexer::with_options("/style{a: @b.c;}", &options);
+
+        assert_eq!(&lexer.output()[3..],
+                   &[RawText(span(7, 3), "a: ".to_owned()),
+                     Symbol(span(10, 1), At),
+                     Word(span(11, 1), "b".to_owned()),
+                     Symbol(span(12, 1), Dot),
+                     Word(span(13, 1), "c".to_owned()),
+                     RawText(span(14, 1), ";".to_owned()),
+                     Symbol(span(15, 1), CloseBrace)][..]);
+    }
+
+    #[test]
+    fn literal_text() {
+        let lexer = Lexer::new("{| (a) @b.c.}");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), OpenBrace),
+                        Literal(span(2, 5), " (a) ".to_owned()),
+                        Symbol(span(7, 1), At),
+                        Word(span(8, 1), "b".to_owned()),
+                        Symbol(span(9, 1), Dot),
+                        Word(span(10, 1), "c".to_owned()),
+                        Literal(span(11, 1), ".".to_owned()),
+                        Symbol(span(12, 1), CloseBrace)]);
+    }
+
+    #[test]
+    fn numbers() {
+        let lexer = Lexer::new("(a=-.5 b=3px)");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), OpenParam),
+                        Word(span(1, 1), "a".to_owned()),
+                        Symbol(span(2, 1), Equals),
+                        Number(span(3, 3), "-.5 ".to_owned()),
+                        Word(span(7, 1), "b".to_owned()),
+                        Symbol(span(8, 1), Equals),
+                        Word(span(9, 3), "3px".to_owned()),
+                        Symbol(span(12, 1), CloseParam)]);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let lexer = Lexer::new(r#"(a="\"b\\ \n\t\u{e9}\@c \d")"#);
+
+        assert_eq!(lexer.output()[3],
+                   Str(span(3, 24), Quote, "\"b\\\\ \n\t\u{e9}\\@c \\d".to_owned()));
+    }
+
+    #[test]
+    fn unclosed_string() {
+        let lexer = Lexer::new("\"a\n\"");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), Quote),
+                        Word(span(1, 1), "a\n".to_owned()),
+                        Symbol(line_span(2, 1, 3, 1), Quote)]);
+    }
+
+    #[test]
+    fn pipe_after_variable() {
+        let lexer = Lexer::new("{@a|b}");
+
+        assert_eq!(lexer.output()[3], Symbol(span(3, 1), Pipe));
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let lexer = Lexer::new("/p{é}");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), ForwardSlash),
+                        Word(span(1, 1), "p".to_owned()),
+                        Symbol(span(2, 1), OpenBrace),
+                        Word(Span::new(pos(3), Position::new(1, 5, 5)), "é".to_owned()),
+                        Symbol(Span::new(Position::new(1, 5, 5), Position::new(1, 6, 6)),
+                               CloseBrace)]);
+    }
+
+    #[test]
+    fn comments() {
+        let lexer = Lexer::new("// A comment\n/// Also a comment\n/p // A trailing comment");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(line_span(3, 1, 32, 1), ForwardSlash),
+                        Word(line_span(3, 2, 33, 1), "p ".to_owned())]);
+    }
+
+    #[test]
+    fn comments_in_quotes() {
+        let lexer = Lexer::new(r#"/a(href="http://a.b") "\"//" //"#);
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(span(0, 1), ForwardSlash),
+                        Word(span(1, 1), "a".to_owned()),
+                        Symbol(span(2, 1), OpenParam),
+                        Word(span(3, 4), "href".to_owned()),
+                        Symbol(span(7, 1), Equals),
+                        Str(span(8, 12), Quote, "http://a.b".to_owned()),
+                        Symbol(span(20, 1), CloseParam),
+                        Str(span(22, 6), Quote, "\"//".to_owned())]);
+    }
+
+    #[test]
+    fn block_comments() {
+        let lexer = Lexer::new("/* A\n** comment */ / p/**/{/*}");
+
+        assert_eq!(lexer.output(),
+                   vec![Symbol(line_span(2, 15, 19, 1), ForwardSlash),
+                        Word(line_span(2, 17, 21, 1), " p".to_owned()),
+                        Symbol(line_span(2, 22, 26, 1), OpenBrace),
+                        OpenComment(line_span(2, 23, 27, 2))]);
+    }
+
+    #[test]
+    fn block_comments_in_quotes() {
+        let output = Lexer::tokenize(r#"/a(title="/* A */")"#);
+
+        assert_eq!(output[5], Str(span(9, 9), Quote, "/* A */".to_owned()));
+    }
 }
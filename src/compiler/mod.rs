@@ -2,8 +2,10 @@ pub mod lexer;
 pub mod parser;
 pub mod codegen;
 pub mod tokens;
+pub mod visitor;
 
 pub use self::lexer::*;
 pub use self::parser::*;
 pub use self::codegen::*;
 pub use self::tokens::*;
+pub use self::visitor::*;
@@ -1,7 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
 use std::iter::Peekable;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::vec::IntoIter;
 
+use serde_json;
+use serde_json::Value;
+
+use super::{is_name_character, Lexer, ParseOptions};
+
 use super::tokens::*;
 use super::tokens::AstError::*;
 use super::tokens::Lexeme::*;
@@ -11,6 +21,22 @@ use super::tokens::Token::*;
 /// Shortens Result type
 pub type AstResult = Result<Token, AstError>;
 
+/// The names of common doctypes, and the declarations they're written as.
+const DOCTYPES: [(&'static str, &'static str); 5] =
+    [("html5", "html"),
+     ("html4-strict",
+      concat!(r#"HTML PUBLIC "-//W3C//DTD HTML 4.01//EN" "#,
+              r#""http://www.w3.org/TR/html4/strict.dtd""#)),
+     ("html4-transitional",
+      concat!(r#"HTML PUBLIC "-//W3C//DTD HTML 4.01 Transitional//EN" "#,
+              r#""http://www.w3.org/TR/html4/loose.dtd""#)),
+     ("xhtml-strict",
+      concat!(r#"html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "#,
+              r#""http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd""#)),
+     ("xhtml-transitional",
+      concat!(r#"html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "#,
+              r#""http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd""#))];
+
 macro_rules! unexpected_eof {
     ($token:expr) => {
         return Err(UnexpectedEof($token));
@@ -18,108 +44,220 @@ macro_rules! unexpected_eof {
 }
 
 macro_rules! get_identifer {
-    ($token:expr, $index:expr, $unexpected:expr) => {
+    ($token:expr, $span:expr, $unexpected:expr) => {
         match $token {
             Some(Word(_, text)) => text,
             Some(unexpected_token) => {
                 return Err($unexpected(unexpected_token))
             }
-            None => return Err(UnexpectedEof(Symbol($index, At))),
+            None => return Err(UnexpectedEof(Symbol($span, At))),
         };
     }
 }
 
 macro_rules! get_namespaced_identifer {
-    ($this:expr, $index:expr, $unexpected:expr, $previous:expr) => {
+    ($this:expr, $span:expr, $unexpected:expr, $previous:expr) => {
         match $this.take() {
-            Some(Word(index, text)) => {
+            Some(Word(span, text)) => {
                 let mut new_text = text.clone();
-                while let Some(Symbol(_, Dot)) = $this.peek() {
+                while let Some(&Symbol(_, Dot)) = $this.peek() {
                     let _ = $this.take();
                     new_text.push('.');
 
                     match $this.take() {
                         Some(Word(_, member)) => new_text.push_str(&*member),
                         Some(unexpected_token) => return Err($unexpected(unexpected_token)),
-                        None => return Err(UnexpectedEof(Symbol(index, Dot))),
+                        None => return Err(UnexpectedEof(Symbol(span, Dot))),
                     }
                 }
                 new_text
             }
             Some(unexpected_token) => return Err($unexpected(unexpected_token)),
-            None => return Err(UnexpectedEof(Symbol($index, $previous))),
+            None => return Err(UnexpectedEof(Symbol($span, $previous))),
         } 
     }
 }
 
-macro_rules! get_children {
-    ($token:expr, $parent:expr) => 
-    {{
-        let mut depth: usize = 0;
-        let mut open_brace_index: usize = 0;
-        let mut close_brace_index: usize = 0;
-        let mut children = Vec::new();
-        while let Some(token) = $token {
-            match token {
-                Symbol(index, OpenBrace) => {
-                    depth += 1;
-
-                    if depth != 0 {
-                        children.push(Symbol(index, OpenBrace));
-                    }
-                    open_brace_index = index;
-                }
-                Symbol(index, CloseBrace) => {
-                    if depth == 0 {
-                        break;
-                    } else {
-                        depth -= 1;
-                        children.push(Symbol(index, CloseBrace));
-                    }
-                    close_brace_index = index;
-                }
-                t => children.push(t),
-            }
-        }
-
-        if depth > 0 {
-            return Err(UnclosedOpenBraces(open_brace_index));
-        } else if depth != 0 {
-            return Err(UnclosedCloseBraces(close_brace_index));
-        }
-        if !children.is_empty() {
-            $parent.add_children(&mut Parser::new(children).output());
-        }
-    }}
-}
-
-
 /// The struct detailing the parser itself.
 pub struct Parser {
     input: Peekable<IntoIter<Lexeme>>,
     output: Vec<AstResult>,
     components: HashMap<String, Component>,
+    /// Whether the parser is parsing the children of a block, where a component followed by
+    /// braces is a call passing children, rather than a definition.
+    nested: bool,
+    /// The directory imports are resolved against, if the source has one.
+    directory: Option<PathBuf>,
+    /// Whether the whitespace in text is kept as written, rather than each run of whitespace
+    /// being collapsed into a single space, such as within a `pre` element.
+    preserve_whitespace: bool,
+    /// The files being included, from the template down to the file being parsed, so an
+    /// `@include` of any of them can be reported, rather than never ending.
+    includes: Vec<PathBuf>,
+    /// The path of the base template given by `@extends`, and where it was given.
+    extends: Option<(Span, String)>,
+    /// The elements that can't have any children.
+    void_elements: Vec<String>,
+    /// Where the last lexeme taken ended.
+    end: Option<Position>,
+    /// Whether the last lexeme taken kept the whitespace after it, as words, numbers, and
+    /// literal text do.
+    kept_whitespace: bool,
+    /// The options the template's source was read with, which files it includes are also read
+    /// with.
+    options: ParseOptions,
 }
 
 impl Parser {
     /// Generates Parser from Lexer
     pub fn new(lexemes: Vec<Lexeme>) -> Self {
-        let mut parser = Parser::new_parser(lexemes);
+        Parser::run(Parser::new_parser(lexemes, false, None, false))
+    }
+
+    /// Generates Parser from Lexer, with `void_elements` as the elements that can't have any
+    /// children, rather than the void elements of HTML, and with the parsing `options`.
+    /// Relative imports, and includes are resolved against the directory of `file`, if there is
+    /// one.
+    pub fn with_options(lexemes: Vec<Lexeme>,
+                        file: Option<&Path>,
+                        void_elements: Vec<String>,
+                        options: &ParseOptions)
+                        -> Self {
+        let mut parser = match file {
+            Some(file) => Parser::file_parser(lexemes, file),
+            None => Parser::new_parser(lexemes, false, None, false),
+        };
+        parser.void_elements = void_elements;
+        parser.options = options.clone();
+        Parser::run(parser)
+    }
+
+    /// Creates the parser for the source of `file`, without parsing it.
+    fn file_parser(lexemes: Vec<Lexeme>, file: &Path) -> Self {
+        let directory = match file.parent() {
+            Some(directory) => directory.to_path_buf(),
+            None => PathBuf::new(),
+        };
+        let mut parser = Parser::new_parser(lexemes, false, Some(directory), false);
+        parser.includes.push(canonical(file));
+        parser
+    }
+
+    /// Generates Parser for the children of a block.
+    fn new_nested(&self, lexemes: Vec<Lexeme>, preserve_whitespace: bool) -> Self {
+        let mut parser = Parser::new_parser(lexemes,
+                                            true,
+                                            self.directory.clone(),
+                                            preserve_whitespace);
+        parser.includes = self.includes.clone();
+        parser.void_elements = self.void_elements.clone();
+        parser.options = self.options.clone();
+        Parser::run(parser)
+    }
+
+    fn run(mut parser: Parser) -> Self {
         loop {
             match parser.parse_token() {
                 Err(Eof) => break,
+                Err(error) => {
+                    parser.push(Err(error));
+                    parser.recover();
+                }
                 token => parser.push(token),
             }
         }
+
+        if let Some((span, path)) = parser.extends.take() {
+            parser.extend(span, path);
+        }
         parser
     }
 
-    fn new_parser(lexemes: Vec<Lexeme>) -> Self {
+    /// Parses the lexemes, returning either the full AST, or every error found in the source.
+    pub fn parse(lexemes: Vec<Lexeme>) -> Result<Vec<Token>, Vec<AstError>> {
+        Parser::new(lexemes).result()
+    }
+
+    /// Parses the lexemes, returning every token that could be parsed, along with every error
+    /// found in the source, so a tool can report every problem in one pass.
+    ///
+    /// ```
+    /// extern crate polly;
+    ///
+    /// use polly::{Lexer, Parser};
+    /// fn main() {
+    ///     let source = "$(broken)\n&{Broken}\n/p{Fine}";
+    ///     let (tokens, errors) = Parser::parse_all(Lexer::tokenize(source));
+    ///     assert_eq!(errors.len(), 2);
+    ///     assert_eq!(tokens.len(), 1);
+    /// }
+    /// ```
+    pub fn parse_all(lexemes: Vec<Lexeme>) -> (Vec<Token>, Vec<AstError>) {
+        Parser::new(lexemes).into_parts()
+    }
+
+    fn new_parser(lexemes: Vec<Lexeme>,
+                  nested: bool,
+                  directory: Option<PathBuf>,
+                  preserve_whitespace: bool)
+                  -> Self {
+        // A comment left open takes the rest of the source, so it's reported before anything
+        // else.
+        let mut lexemes = lexemes;
+        let mut output = Vec::new();
+        if let Some(&OpenComment(span)) = lexemes.last() {
+            output.push(Err(UnclosedComment(span)));
+            let _ = lexemes.pop();
+        }
+
         Parser {
             input: lexemes.into_iter().peekable(),
-            output: Vec::new(),
+            output: output,
             components: HashMap::new(),
+            nested: nested,
+            directory: directory,
+            preserve_whitespace: preserve_whitespace,
+            includes: Vec::new(),
+            extends: None,
+            void_elements: VOID_ELEMENTS.iter().map(|tag| String::from(*tag)).collect(),
+            end: None,
+            kept_whitespace: false,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// The text, and variables of a string, for it to be rendered.
+    fn string_tokens(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        for segment in split_string(text) {
+            let token = match segment {
+                Segment::Literal(text) => Text(self.whitespace(text)),
+                Segment::Variable(ref name) if name.starts_with('!') => {
+                    RawVariable(name[1..].to_owned(), Vec::new(), SourceSpan(None))
+                }
+                Segment::Variable(name) => Variable(name, Vec::new(), SourceSpan(None)),
+            };
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    /// Collapses each run of whitespace in `text` into a single space, unless the parser is
+    /// preserving whitespace.
+    fn whitespace(&self, text: String) -> String {
+        if self.preserve_whitespace {
+            return text;
+        }
+
+        let mut collapsed = String::with_capacity(text.len());
+        for character in text.chars() {
+            if !character.is_whitespace() {
+                collapsed.push(character);
+            } else if !collapsed.ends_with(' ') {
+                collapsed.push(' ');
+            }
         }
+        collapsed
     }
 
     /// Pushes a new AstResult onto the output Vector
@@ -129,22 +267,159 @@ impl Parser {
 
     /// A wrapper function around the input. taking the next element from the iterator.
     fn take(&mut self) -> Option<Lexeme> {
-        self.input.next()
-    }
-    /// Performs a lookahead of the iterator.
-    // This function should probably be refactored to not clone a token every time it's called.
-    // Currently if you replace it with a reference, it creates a borrow, that messes up the
-    // parser's current borrow structure.
-    fn peek(&mut self) -> Option<Lexeme> {
-        match self.input.peek() {
-            Some(token) => Some(token.clone()),
-            None => None,
+        let lexeme = self.input.next();
+        if let Some(ref lexeme) = lexeme {
+            self.end = Some(lexeme.span().end);
+            self.kept_whitespace = match *lexeme {
+                Word(_, _) | Number(_, _) | Literal(_, _) => true,
+                _ => false,
+            };
         }
+        lexeme
+    }
+    /// Performs a lookahead of the iterator, without cloning the lexeme.
+    fn peek(&mut self) -> Option<&Lexeme> {
+        self.input.peek()
     }
     /// Output result vector
     pub fn output(self) -> Vec<AstResult> {
         self.output
     }
+
+    /// Consumes the parser, returning the AST if there were no errors, otherwise returns all of
+    /// the errors, including the ones found within children, and component definitions.
+    pub fn result(self) -> Result<Vec<Token>, Vec<AstError>> {
+        let (tokens, errors) = self.into_parts();
+
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Consumes the parser, splitting the output into the tokens, and the errors.
+    fn into_parts(self) -> (Vec<Token>, Vec<AstError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+
+        for result in self.output {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+        (tokens, errors)
+    }
+
+    /// Parses a brace block, which must be the next token. `previous` is the lexeme before the
+    /// block, used if the file ends before the block starts.
+    fn parse_block(&mut self, previous: Lexeme) -> Result<Vec<Token>, AstError> {
+        match self.take() {
+            Some(Symbol(span, OpenBrace)) => self.parse_children(span),
+            Some(unexpected_token) => Err(UnexpectedToken(unexpected_token)),
+            None => Err(UnexpectedEof(previous)),
+        }
+    }
+
+    /// Collects every lexeme up to the brace that closes the already consumed brace at `open`,
+    /// and parses them as children. Braces within the block are kept for the child parser, and
+    /// braces escaped with a backslash aren't counted.
+    fn parse_children(&mut self, open: Span) -> Result<Vec<Token>, AstError> {
+        let preserve_whitespace = self.preserve_whitespace;
+        self.parse_children_with(open, preserve_whitespace)
+    }
+
+    /// Parses a block's children like `parse_children`, with whether the children keep their
+    /// whitespace as written.
+    fn parse_children_with(&mut self,
+                           open: Span,
+                           preserve_whitespace: bool)
+                           -> Result<Vec<Token>, AstError> {
+        let mut open_braces = vec![open];
+        let mut children = Vec::new();
+        let mut escaped = false;
+
+        while let Some(lexeme) = self.take() {
+            let escapes = is_escape(&lexeme, escaped);
+            match lexeme {
+                Symbol(_, OpenBrace) | Symbol(_, CloseBrace) if escaped => {}
+                Symbol(span, OpenBrace) => open_braces.push(span),
+                Symbol(_, CloseBrace) => {
+                    let _ = open_braces.pop();
+
+                    if open_braces.is_empty() {
+                        let mut parser = self.new_nested(children, preserve_whitespace);
+                        // Components imported within the block are available to the template.
+                        for (name, component) in parser.components.drain() {
+                            self.components.insert(name, component);
+                        }
+                        return Ok(self.split_children(parser.output()));
+                    }
+                }
+                _ => {}
+            }
+            escaped = escapes;
+            children.push(lexeme);
+        }
+
+        match open_braces.last() {
+            Some(&span) => Err(UnclosedOpenBraces(span)),
+            None => Err(UnclosedOpenBraces(open)),
+        }
+    }
+
+    /// Separates the parsed children from their errors. The errors are pushed onto the output,
+    /// so they are reported alongside the rest of the template.
+    fn split_children(&mut self, children: Vec<AstResult>) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        for child in children {
+            match child {
+                Ok(token) => tokens.push(token),
+                Err(error) => self.push(Err(error)),
+            }
+        }
+        tokens
+    }
+
+    /// Skips ahead to the start of the next element, component, function, variable, or line, so
+    /// that an error doesn't cascade into more errors for the rest of the statement. Any brace
+    /// blocks in between are skipped entirely.
+    fn recover(&mut self) {
+        let mut depth: usize = 0;
+        let line = match self.peek() {
+            Some(lexeme) => lexeme.position().line,
+            None => return,
+        };
+
+        let mut escaped = false;
+        while let Some(lexeme) = self.peek() {
+            if depth == 0 && lexeme.position().line > line {
+                break;
+            }
+
+            let escapes = is_escape(lexeme, escaped);
+            match *lexeme {
+                Symbol(_, OpenBrace) | Symbol(_, CloseBrace) if escaped => {}
+                Symbol(_, OpenBrace) => depth += 1,
+                Symbol(_, CloseBrace) => {
+                    if depth == 0 {
+                        let _ = self.take();
+                        break;
+                    }
+                    depth -= 1;
+                }
+                Symbol(_, ForwardSlash) |
+                Symbol(_, Ampersand) |
+                Symbol(_, Dollar) |
+                Symbol(_, At) if depth == 0 && !escaped => break,
+                _ => {}
+            }
+            escaped = escapes;
+            let _ = self.take();
+        }
+    }
     /// Get all the components the parser found.
     pub fn get_components(&self) -> HashMap<String, Component> {
         self.components.clone()
@@ -152,288 +427,2985 @@ impl Parser {
 
     /// Only parse components out of the source.
     pub fn component_pass(lexemes: Vec<Lexeme>) -> HashMap<String, Component> {
-        let mut parser = Parser::new_parser(lexemes);
+        Parser::parse_component_definitions(lexemes).components
+    }
+
+    /// Only parse components out of the source, returning every error found in their
+    /// definitions.
+    pub fn parse_components(lexemes: Vec<Lexeme>)
+                            -> Result<HashMap<String, Component>, Vec<AstError>> {
+        let parser = Parser::parse_component_definitions(lexemes);
+        let mut errors = Vec::new();
+
+        for result in parser.output {
+            if let Err(error) = result {
+                errors.push(error);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(parser.components)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn parse_component_definitions(lexemes: Vec<Lexeme>) -> Self {
+        let mut parser = Parser::new_parser(lexemes, false, None, false);
         loop {
             match parser.take() {
-                Some(Symbol(index, Ampersand)) => {
-                    let _ = parser.parse_component(true, index);
+                Some(Symbol(span, Ampersand)) => {
+                    if let Err(error) = parser.parse_component(true, span) {
+                        parser.push(Err(error));
+                    }
                 }
                 None => break,
                 _ => {}
             }
         }
 
-        parser.components
+        parser
     }
 
-    fn parse_component(&mut self, allow_definition: bool, index: usize) -> AstResult {
-        let name = get_namespaced_identifer!(self, index, InvalidComponent, Ampersand);
+    fn parse_component(&mut self, allow_definition: bool, span: Span) -> AstResult {
+        let name = get_namespaced_identifer!(self, span, InvalidComponent, Ampersand);
         let mut component = Component::new(name);
+        // The first default value, and named argument, as defaults are only allowed in
+        // definitions, and named arguments are only allowed in calls.
+        let mut first_default = None;
+        let mut first_named = None;
+        // A parameter declared twice, or a quoted string, which are only errors in a definition.
+        let mut first_duplicate = None;
+        let mut first_literal = None;
+        let mut named = BTreeMap::new();
+        let mut children = Vec::new();
 
-        while let Some(token) = self.peek() {
-            match token {
-                Symbol(_, OpenParam) => {
+        'component: while let Some(token) = self.peek() {
+            match *token {
+                Symbol(open, OpenParam) => {
                     let _ = self.take();
-                    while let Some(token) = self.take() {
+                    let result = self.parse_arguments(open, |parser, token| {
                         match token {
-                            Symbol(index, At) => {
+                            Symbol(span, At) => {
+                                if first_named.is_some() {
+                                    return Err(PositionalAfterNamed(Symbol(span, At)));
+                                }
 
-                                let identifier = get_identifer!(self.take(),
-                                                                index,
+                                let identifier_span = match parser.peek() {
+                                    Some(lexeme) => lexeme.span(),
+                                    None => span,
+                                };
+                                let identifier = get_identifer!(parser.take(),
+                                                                span,
                                                                 UnexpectedToken);
-                                component.add_arg_value(identifier);
-                            }
-                            Symbol(_, CloseParam) => {
-                                match self.peek() {
-                                    Some(Symbol(_, OpenBrace)) => break,
-                                    _ => {
-                                        return Ok(CompCall((ComponentCall::from_component(component))));
+                                let identifier = identifier.trim().to_owned();
+
+                                if first_duplicate.is_none() &&
+                                   component.args().iter().any(|arg| arg.value() == identifier) {
+                                    first_duplicate = Some(Word(identifier_span,
+                                                                identifier.clone()));
+                                }
+
+                                match parser.peek() {
+                                    Some(&Symbol(equals, Equals)) => {
+                                        let _ = parser.take();
+                                        let equals = Symbol(equals, Equals);
+                                        let default = match parser.parse_default(equals.clone()) {
+                                            Ok(default) => default,
+                                            Err(error) => return Err(error),
+                                        };
+                                        component.add_arg_with_default(identifier, default);
+
+                                        if first_default.is_none() {
+                                            first_default = Some(equals);
+                                        }
                                     }
+                                    _ => component.add_arg_value(identifier),
+                                }
+                            }
+                            Str(span, quote, text) => {
+                                if first_named.is_some() {
+                                    return Err(PositionalAfterNamed(Str(span, quote, text)));
+                                }
+
+                                component.add_arg_literal(unescape_string(&text));
+                                if first_literal.is_none() {
+                                    first_literal = Some(Str(span, quote, text));
+                                }
+                            }
+                            Word(span, name) => {
+                                let (name, variable) = match parser.parse_named_argument(span,
+                                                                                         name) {
+                                    Ok(argument) => argument,
+                                    Err(error) => return Err(error),
+                                };
+
+                                if named.insert(name.clone(), variable).is_some() {
+                                    return Err(DuplicateArgument(Word(span, name)));
+                                }
+                                if first_named.is_none() {
+                                    first_named = Some(Word(span, name));
                                 }
                             }
-                            Symbol(_, Comma) => {}
                             unexpected_token => return Err(UnexpectedToken(unexpected_token)),
                         }
+                        Ok(())
+                    });
+                    if let Err(error) = result {
+                        return Err(error);
+                    }
+                    match self.peek() {
+                        Some(&Symbol(_, OpenBrace)) => {}
+                        _ => break 'component,
                     }
                 }
-                token @ Symbol(_, OpenBrace) => {
+                Symbol(span, OpenBrace) => {
                     let _ = self.take();
+                    children = match self.parse_children(span) {
+                        Ok(children) => children,
+                        Err(error) => return Err(error),
+                    };
+
                     if allow_definition {
-                        get_children!(self.take(), component);
-                        break;
-                    } else {
-                        return Err(ExpectedCompCall(token));
+                        if let Some(name) = first_named {
+                            return Err(UnexpectedToken(name));
+                        }
+                        if let Some(parameter) = first_duplicate {
+                            return Err(DuplicateArgument(parameter));
+                        }
+                        if let Some(quote) = first_literal {
+                            return Err(UnexpectedToken(quote));
+                        }
+                        component.add_children(&mut children);
+                        self.components.insert(component.name().into(), component);
+                        return Ok(Text(String::new()));
                     }
+                    break;
                 }
-                unexpected_token => return Err(UnexpectedToken(unexpected_token)),
+                _ => break,
             }
         }
-        if allow_definition {
-            self.components.insert(component.name().into(), component);
-            Ok(Text(String::new()))
-        } else {
-            // This unreachable, because with allow_definition = false, we should either get a
-            // CompCall, or a ExpectedCompCall error.
-            unreachable!()
+
+        if let Some(equals) = first_default {
+            return Err(UnexpectedToken(equals));
+        }
+        // Anything other than a definition is a call, including a bare `&name`.
+        let mut call = ComponentCall::from_component(component);
+        for (name, variable) in named {
+            let _ = call.add_named_value(name, variable);
         }
+        call.add_children(&mut children);
+        Ok(CompCall(call))
     }
 
-    fn parse_element(&mut self, index: usize) -> AstResult {
-        let tag = get_identifer!(self.take(), index, InvalidElement);
-        let mut element = Element::new(tag.trim().to_owned());
-
-        'element: while let Some(token) = self.take() {
-            match token {
-                Symbol(index, Ampersand) => {
-                    let identifier = get_namespaced_identifer!(self,
-                                                               index,
-                                                               ExpectedCompCall,
-                                                               Ampersand);
-                    let mut component_call = ComponentCall::new(identifier);
-
-                    if let Some(Symbol(_, OpenParam)) = self.peek() {
-                        let _ = self.take();
-                        while let Some(symbol) = self.take() {
-                            match symbol {
-                                Symbol(_, CloseParam) => break,
-                                Symbol(index, At) => {
-                                    let identifier = get_identifer!(self.take(),
-                                                                    index,
-                                                                    ExpectedVariable);
-                                    component_call.add_value(identifier);
-                                }
-                                Symbol(_, Comma) => {}
-                                unexpected_token => return Err(UnexpectedToken(unexpected_token)),
-                            }
-                        }
-                    }
-                    element.add_resource(component_call)
-                }
-                Symbol(index, OpenParam) => {
-                    while let Some(token) = self.take() {
-                        match token {
-                            Symbol(_, CloseParam) => {
-                                match self.peek() {
-                                    Some(Symbol(_, OpenBrace)) => break,
-                                    _ => return Ok(Html(element)),
-                                }
-                            }
-                            Symbol(_, Quote) => {
-                                let key = format!("{}{}{}", '"', self.read_leading_quotes(), '"');
-                                element.add_attribute(key, String::from(""));
-                            }
-                            Word(_, key) => {
-                                let value = match self.peek() {
-                                    Some(Symbol(index, Equals)) => {
-                                        let _ = self.take();
-                                        match self.take() {
-                                            Some(Word(_, text)) => text,
-                                            Some(Symbol(_, Quote)) => self.read_leading_quotes(),
-                                            Some(unexpected_token) => {
-                                                return Err(InvalidTokenInAttributes(unexpected_token));
-                                            }
-                                            None => {
-                                                return unexpected_eof!(Symbol(index, Equals));
-                                            }
-                                        }
-                                    }
-                                    Some(Word(_, _)) => String::from(""),
-                                    Some(Symbol(_, CloseParam)) => String::from(""),
-                                    Some(Symbol(_, Quote)) => String::from(""),
-                                    Some(invalid_token) => {
-                                        return Err(InvalidTokenInAttributes(invalid_token))
-                                    }
-                                    None => return unexpected_eof!(Word(index, key)),
-                                };
-
-                                element.add_attribute(key, value);
-                            }
-                            invalid_token => return Err(InvalidTokenInAttributes(invalid_token)),
-                        }
-                    }
-                }
-                Symbol(index, Dot) => {
-                    match self.take() {
-                        Some(Word(_, class)) => element.add_class(class),
-                        Some(unexpected_token) => {
-                            return Err(NoNameAttachedToClass(unexpected_token))
-                        }
-                        None => return Err(UnexpectedEof(Symbol(index, Dot))),
+    /// Parses a list of arguments after its `(` at `open`, up to, and including the `)`,
+    /// passing the first lexeme of each argument to `parse_argument`. Arguments can be
+    /// separated by commas, and followed by one, but a comma without an argument before it is
+    /// an error.
+    fn parse_arguments<F>(&mut self, open: Span, mut parse_argument: F) -> Result<(), AstError>
+        where F: FnMut(&mut Parser, Lexeme) -> Result<(), AstError>
+    {
+        let mut after_argument = false;
+        loop {
+            match self.take() {
+                Some(Symbol(_, CloseParam)) => return Ok(()),
+                Some(Symbol(span, quote @ Quote)) |
+                Some(Symbol(span, quote @ SingleQuote)) => return Err(UnclosedQuote(span, quote)),
+                Some(Symbol(span, Comma)) => {
+                    if !after_argument {
+                        return Err(UnexpectedToken(Symbol(span, Comma)));
                     }
+                    after_argument = false;
                 }
-                Symbol(index, Pound) => {
-                    match self.take() {
-                        Some(Word(_, id)) => element.add_attribute(String::from("id"), id),
-                        Some(unexpected_token) => return Err(NoNameAttachedToId(unexpected_token)),
-                        None => return Err(UnexpectedEof(Symbol(index, Pound))),
+                Some(lexeme) => {
+                    if let Err(error) = parse_argument(self, lexeme) {
+                        return Err(error);
                     }
+                    after_argument = true;
                 }
-                Symbol(_, OpenBrace) => {
-                    get_children!(self.take(), element);
-                    break;
-                }
-                unexpected_token => return Err(UnexpectedToken(unexpected_token)),
+                None => unexpected_eof!(Symbol(open, OpenParam)),
             }
         }
-        Ok(Html(element))
     }
 
-    fn parse_escaped(&mut self) -> AstResult {
-        match self.peek() {
-            Some(Symbol(_, ref operator)) => {
-                let _ = self.take();
-                Ok(Text(operator.to_string()))
+    /// Parses a named argument of a component call, such as `title = @post_title`, returning
+    /// the name, and the variable passed to it.
+    fn parse_named_argument(&mut self,
+                            span: Span,
+                            name: String)
+                            -> Result<(String, String), AstError> {
+        let equals_span = match self.take() {
+            Some(Symbol(equals_span, Equals)) => equals_span,
+            Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+            None => unexpected_eof!(Word(span, name)),
+        };
+
+        match self.take() {
+            Some(Symbol(span, At)) => {
+                let variable = get_identifer!(self.take(), span, ExpectedVariable);
+                Ok((name.trim().to_owned(), variable.trim().to_owned()))
             }
-            Some(_) => Ok(Text(String::new())),
-            None => Err(Eof),
+            Some(unexpected_token) => Err(ExpectedVariable(unexpected_token)),
+            None => unexpected_eof!(Symbol(equals_span, Equals)),
         }
     }
 
-    fn parse_function(&mut self, index: usize) -> AstResult {
-        let identifier = get_namespaced_identifer!(self, index, InvalidFunctionCall, Dollar);
-        let mut func_call = FunctionCall::new(identifier);
-
+    /// Parses the default value of a component's parameter, after the `=`.
+    fn parse_default(&mut self, equals: Lexeme) -> AstResult {
         match self.take() {
-            Some(Symbol(_, OpenParam)) => {
-                while let Some(token) = self.take() {
-                    match token {
-                        Word(index, arg_name) => {
-                            match self.take() {
-                                Some(Symbol(index, Equals)) => {
-                                    match self.take() {
-                                        Some(Symbol(index, At)) => {
-                                            match self.take() {
-                                                Some(Word(_, identifier)) => {
-                                                    func_call.add_value_arg(arg_name, identifier);
-                                                }
-                                                Some(unexpected_token) => {
-                                                    return Err(ExpectedVariable(unexpected_token))
-                                                }
-                                                None => unexpected_eof!(Symbol(index, At)),
-                                            }
-                                        }
-                                        Some(Symbol(index, Ampersand)) => {
-                                            match self.take() {
-                                                Some(Word(_, identifier)) => {
-                                                    func_call.add_component_arg(arg_name,
-                                                                                identifier);
-                                                }
-                                                Some(unexpected_token) => {
-                                                    return Err(ExpectedCompCall(unexpected_token))
-                                                }
-                                                None => unexpected_eof!(Symbol(index, Ampersand)),
-                                            }
-                                        }
-                                        Some(unexpected_token) => {
-                                            return Err(UnexpectedToken(unexpected_token))
-                                        }
-                                        None => unexpected_eof!(Symbol(index, Equals)),
+            Some(Str(_, _, text)) => Ok(Text(unescape_string(&text))),
+            Some(Symbol(span, quote @ Quote)) |
+            Some(Symbol(span, quote @ SingleQuote)) => Err(UnclosedQuote(span, quote)),
+            Some(Word(_, text)) | Some(Number(_, text)) => Ok(Text(text.trim().to_owned())),
+            Some(unexpected_token) => Err(UnexpectedToken(unexpected_token)),
+            None => unexpected_eof!(equals),
+        }
+    }
 
-                                    }
-                                }
-                                Some(unexpected_token) => {
-                                    return Err(InvalidFunctionCall(unexpected_token))
-                                }
-                                None => unexpected_eof!(Word(index, arg_name)),
+    /// Parses an `@if`, or an `@unless` when `inverted`, which renders its block when the
+    /// condition is false.
+    fn parse_conditional(&mut self, span: Span, inverted: bool) -> AstResult {
+        let condition = match self.parse_condition(span) {
+            Ok(condition) if inverted => Expr::Not(Box::new(condition)),
+            Ok(condition) => condition,
+            Err(error) => return Err(error),
+        };
 
-                            }
-                        }
-                        Symbol(_, CloseParam) => break,
-                        Symbol(_, Comma) => {}
-                        unexpected_token => return Err(UnexpectedToken(unexpected_token)),
-                    }
-                }
+        match self.parse_block(Symbol(span, At)) {
+            Ok(then_branch) => {
+                Ok(Conditional {
+                    condition: condition,
+                    then_branch: then_branch,
+                    else_ifs: Vec::new(),
+                    else_branch: None,
+                    span: SourceSpan::default(),
+                })
             }
-            Some(unexpected_token) => return Err(InvalidFunctionCall(unexpected_token)),
-            None => unexpected_eof!(Symbol(index, Dollar)),
+            Err(error) => Err(error),
         }
-        Ok(Function(func_call))
     }
 
-
-
-    fn parse_text(&mut self, word: String) -> AstResult {
-        let mut text = String::from(word);
+    /// Parses the condition of an `@if`, up to the brace starting its block. Quoted strings are
+    /// read like attribute values, and the rest is split into operators, and operands.
+    fn parse_condition(&mut self, span: Span) -> Result<Expr, AstError> {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
         loop {
-            let peek = self.peek();
-            match peek {
-                Some(Word(_, ref peek_text)) => {
-                    text.push_str(&*peek_text);
-                    let _ = self.take();
+            match self.peek() {
+                Some(&Symbol(_, OpenBrace)) => break,
+                Some(_) => {}
+                None => unexpected_eof!(Symbol(span, At)),
+            }
+
+            match self.take() {
+                Some(Str(_, _, literal)) => {
+                    if !split_expression(&text, &mut tokens) {
+                        return Err(InvalidExpression(span));
+                    }
+                    text.clear();
+                    let literal = Value::String(unescape_string(&literal));
+                    tokens.push(ExprToken::Operand(Expr::Literal(literal)));
                 }
-                _ => return Ok(Text(text)),
+                Some(Symbol(open, quote @ Quote)) |
+                Some(Symbol(open, quote @ SingleQuote)) => return Err(UnclosedQuote(open, quote)),
+                Some(Word(_, word)) |
+                Some(RawText(_, word)) |
+                Some(Literal(_, word)) |
+                Some(Number(_, word)) => text.push_str(&*word),
+                Some(Symbol(_, operator)) => text.push_str(&*operator.to_string()),
+                Some(OpenComment(span)) => return Err(UnclosedComment(span)),
+                None => {}
             }
         }
-    }
 
-    /// 
-    fn parse_token(&mut self) -> AstResult {
-        match self.take() {
-            // concatenate all the word tokens that are adjacent to each other into a single "Text"
-            // token.
-            Some(Word(_, word)) => self.parse_text(word),
-            Some(Symbol(index, At)) => {
-                Ok(Variable(get_namespaced_identifer!(self, index, ExpectedVariable, At)))
-            }
-            Some(Symbol(index, ForwardSlash)) => self.parse_element(index),
-            Some(Symbol(_, BackSlash)) => self.parse_escaped(),
-            Some(Symbol(index, Ampersand)) => self.parse_component(true, index),
-            Some(Symbol(index, Dollar)) => self.parse_function(index),
-            Some(Symbol(_, operator)) => Ok(Text(operator.to_string())),
-            None => Err(Eof),
+        if !split_expression(&text, &mut tokens) {
+            return Err(InvalidExpression(span));
+        }
+
+        let mut index = 0;
+        match parse_or(&tokens, &mut index) {
+            Some(expression) => {
+                if index == tokens.len() {
+                    Ok(expression)
+                } else {
+                    Err(InvalidExpression(span))
+                }
+            }
+            None => Err(InvalidExpression(span)),
         }
     }
 
-    /// turns all Operators into text until it it reaches the first " or Quote operator.
-    fn read_leading_quotes(&mut self) -> String {
-        let mut value = String::new();
-        while let Some(token) = self.take() {
-            match token {
-                Symbol(_, Quote) => break,
-                Word(_, text) => value.push_str(&*text),
-                Symbol(_, operator) => value.push_str(&*operator.to_string()),
+    /// Parses an else block, and attaches it to the conditional, or loop directly before it.
+    /// `@else if` is the same as `@elseif`.
+    fn parse_else(&mut self, span: Span) -> AstResult {
+        if let Some(&Word(_, ref word)) = self.peek() {
+            if word.trim() == "if" {
+                let _ = self.take();
+                return self.parse_else_if(span);
             }
         }
-        value
+
+        let children = match self.parse_block(Symbol(span, At)) {
+            Ok(children) => children,
+            Err(error) => return Err(error),
+        };
+
+        match self.previous_block() {
+            Some(&mut Conditional { ref mut else_branch, .. }) |
+            Some(&mut Loop { ref mut else_branch, .. }) => {
+                if else_branch.is_none() {
+                    *else_branch = Some(children);
+                    return Ok(Text(String::new()));
+                }
+            }
+            _ => {}
+        }
+        Err(DanglingElse(span))
+    }
+
+    /// Parses an `@elseif`, and adds it to the conditional directly before it.
+    fn parse_else_if(&mut self, span: Span) -> AstResult {
+        let condition = match self.parse_condition(span) {
+            Ok(condition) => condition,
+            Err(error) => return Err(error),
+        };
+        let children = match self.parse_block(Symbol(span, At)) {
+            Ok(children) => children,
+            Err(error) => return Err(error),
+        };
+
+        if let Some(&mut Conditional { ref mut else_ifs, ref else_branch, .. }) =
+               self.previous_block() {
+            if else_branch.is_none() {
+                else_ifs.push((condition, children));
+                return Ok(Text(String::new()));
+            }
+        }
+        Err(DanglingElse(span))
+    }
+
+    /// The conditional, or loop directly before the current token, skipping the empty text left
+    /// in place of the `@else`, and `@elseif` blocks already attached to it.
+    fn previous_block(&mut self) -> Option<&mut Token> {
+        for result in self.output.iter_mut().rev() {
+            match *result {
+                Ok(Text(ref text)) if text.is_empty() => {}
+                Ok(ref mut token) => {
+                    return match *token {
+                        Conditional { .. } | Loop { .. } => Some(token),
+                        _ => None,
+                    };
+                }
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+
+    /// Parses `@switch @subject { @case value { } @default { } }`. A case's value is a quoted
+    /// string, or a word read as JSON, such as `3`, or `true`, which is otherwise a string.
+    fn parse_switch(&mut self, span: Span) -> AstResult {
+        let subject = match self.take() {
+            Some(Symbol(span, At)) => {
+                get_namespaced_identifer!(self, span, ExpectedVariable, At)
+            }
+            Some(unexpected_token) => return Err(ExpectedVariable(unexpected_token)),
+            None => unexpected_eof!(Symbol(span, At)),
+        };
+
+        let open = match self.take() {
+            Some(Symbol(span, OpenBrace)) => span,
+            Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+            None => unexpected_eof!(Word(span, subject)),
+        };
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        loop {
+            let at = match self.take() {
+                Some(Symbol(_, CloseBrace)) => break,
+                Some(Symbol(span, At)) => span,
+                Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+                None => return Err(UnclosedOpenBraces(open)),
+            };
+
+            let (keyword, lexeme) = match self.take() {
+                Some(Word(span, keyword)) => (keyword.trim().to_owned(), Word(span, keyword)),
+                Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+                None => unexpected_eof!(Symbol(at, At)),
+            };
+
+            match &*keyword {
+                "case" => {
+                    let value = match self.take() {
+                        Some(Str(_, _, text)) => Value::String(unescape_string(&text)),
+                        Some(Symbol(span, quote @ Quote)) |
+                        Some(Symbol(span, quote @ SingleQuote)) => {
+                            return Err(UnclosedQuote(span, quote))
+                        }
+                        Some(Word(_, text)) => {
+                            let text = text.trim();
+                            match serde_json::from_str(text) {
+                                Ok(value) => value,
+                                Err(_) => Value::String(text.to_owned()),
+                            }
+                        }
+                        Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+                        None => unexpected_eof!(lexeme),
+                    };
+
+                    match self.parse_block(lexeme) {
+                        Ok(children) => cases.push((value, children)),
+                        Err(error) => return Err(error),
+                    }
+                }
+                "default" if default.is_none() => {
+                    match self.parse_block(lexeme) {
+                        Ok(children) => default = Some(children),
+                        Err(error) => return Err(error),
+                    }
+                }
+                _ => return Err(UnexpectedToken(lexeme)),
+            }
+        }
+
+        Ok(Switch {
+            subject: subject.trim().to_owned(),
+            cases: cases,
+            default: default,
+            span: SourceSpan::default(),
+        })
+    }
+
+    fn parse_element(&mut self, span: Span) -> AstResult {
+        let tag = get_identifer!(self.take(), span, InvalidElement);
+        if tag.trim() == "!" {
+            return self.parse_html_comment(span);
+        }
+        if tag.trim().to_lowercase() == "!doctype" {
+            match self.peek() {
+                Some(&Symbol(_, OpenParam)) => {}
+                _ => {
+                    let doctype = self.parse_doctype(span);
+                    return if self.is_after_element() {
+                        Err(MisplacedDoctype(span))
+                    } else {
+                        Ok(doctype)
+                    };
+                }
+            }
+        }
+        let element = self.new_element(tag.trim().to_owned());
+        self.parse_element_after_tag(element)
+    }
+
+    /// Parses an element starting with a class, or an id rather than a tag, such as `.card{}`,
+    /// or `#main{}`, which is a `div`. If more text follows the name, as in `.5 is half`, it's
+    /// all text instead.
+    fn parse_implicit_div(&mut self, shorthand: Operator) -> AstResult {
+        let name = match self.take() {
+            Some(Word(_, name)) => name,
+            _ => String::new(),
+        };
+        if let Some(&Word(_, _)) = self.peek() {
+            return self.parse_text(format!("{}{}", shorthand, name));
+        }
+
+        let mut element = self.new_element(String::from("div"));
+        match shorthand {
+            Pound => element.add_attribute(String::from("id"), name.trim().to_owned()),
+            _ => element.add_class(name.trim().to_owned()),
+        }
+        self.parse_element_after_tag(element)
+    }
+
+    /// Whether the `.`, or `#` at `span` starts an implicit `div`, which it does when it's the
+    /// first thing on its line, and a name follows it directly, so a `.` ending a sentence is
+    /// still text. `previous_end` is where the lexeme before it ended.
+    fn starts_implicit_div(&mut self, previous_end: Option<Position>, span: Span) -> bool {
+        if let Some(end) = previous_end {
+            if end.line == span.start.line {
+                return false;
+            }
+        }
+
+        match self.peek() {
+            Some(&Word(_, ref name)) => !name.starts_with(char::is_whitespace),
+            _ => false,
+        }
+    }
+
+    /// Creates an element, which is void if its tag is one of the parser's void elements.
+    fn new_element(&self, tag: String) -> Element {
+        let mut element = Element::new(tag);
+        if !element.is_declaration() {
+            let void = self.void_elements.iter().any(|void| void == element.tag());
+            element.set_void(void);
+        }
+        element
+    }
+
+    /// Parses the rest of an element after its tag, such as its classes, attributes, and
+    /// children.
+    fn parse_element_after_tag(&mut self, mut element: Element) -> AstResult {
+        loop {
+            let previous_end = self.end;
+            let token = match self.take() {
+                Some(token) => token,
+                None => break,
+            };
+            match token {
+                Symbol(span, Ampersand) => {
+                    let identifier = get_namespaced_identifer!(self,
+                                                               span,
+                                                               ExpectedCompCall,
+                                                               Ampersand);
+                    let mut component_call = ComponentCall::new(identifier);
+
+                    if let Some(&Symbol(open, OpenParam)) = self.peek() {
+                        let _ = self.take();
+                        let result = self.parse_arguments(open, |parser, symbol| {
+                            match symbol {
+                                Symbol(span, At) => {
+                                    if !component_call.named_values().is_empty() {
+                                        return Err(PositionalAfterNamed(Symbol(span, At)));
+                                    }
+
+                                    let identifier = get_identifer!(parser.take(),
+                                                                    span,
+                                                                    ExpectedVariable);
+                                    component_call.add_value(identifier);
+                                }
+                                Str(span, quote, text) => {
+                                    if !component_call.named_values().is_empty() {
+                                        return Err(PositionalAfterNamed(Str(span, quote, text)));
+                                    }
+
+                                    component_call.add_literal_value(unescape_string(&text));
+                                }
+                                Word(span, name) => {
+                                    let (name, variable) =
+                                        match parser.parse_named_argument(span, name) {
+                                            Ok(argument) => argument,
+                                            Err(error) => return Err(error),
+                                        };
+
+                                    if component_call.add_named_value(name.clone(), variable)
+                                                     .is_some() {
+                                        return Err(DuplicateArgument(Word(span, name)));
+                                    }
+                                }
+                                unexpected_token => return Err(UnexpectedToken(unexpected_token)),
+                            }
+                            Ok(())
+                        });
+                        if let Err(error) = result {
+                            return Err(error);
+                        }
+                    }
+
+                    if let Some(&Symbol(span, OpenBrace)) = self.peek() {
+                        let _ = self.take();
+                        match self.parse_children(span) {
+                            Ok(mut children) => component_call.add_children(&mut children),
+                            Err(error) => return Err(error),
+                        }
+                        element.add_resource(component_call);
+                        break;
+                    }
+                    element.add_resource(component_call)
+                }
+                Symbol(_, Star) => element.set_raw_attributes(true),
+                Symbol(_, LessThan) => element.set_trim_after(true),
+                // A `>` after whitespace chains the element after it, as in `/ul > /li {}`,
+                // while one directly after the tag puts a space before the element.
+                Symbol(span, GreaterThan) => {
+                    let chained = previous_end != Some(span.start);
+                    match self.peek() {
+                        Some(&Symbol(_, ForwardSlash)) if chained => {
+                            return self.parse_chained_child(element, span)
+                        }
+                        Some(&Symbol(_, ForwardSlash)) => {
+                            element.set_space_before(true);
+                            break;
+                        }
+                        _ => element.set_space_before(true),
+                    }
+                }
+                Symbol(_, OpenParam) => {
+                    if let Err(error) = self.parse_attributes(&mut element) {
+                        self.push(Err(error));
+                        self.skip_attributes();
+                    }
+
+                    match self.peek() {
+                        Some(&Symbol(_, OpenBrace)) |
+                        Some(&Str(_, _, _)) |
+                        Some(&Symbol(_, Quote)) => {}
+                        Some(&Symbol(span, GreaterThan)) => {
+                            let chained = self.end != Some(span.start);
+                            let _ = self.take();
+                            match self.peek() {
+                                Some(&Symbol(_, ForwardSlash)) if chained => {
+                                    return self.parse_chained_child(element, span)
+                                }
+                                Some(&Symbol(_, ForwardSlash)) => {
+                                    element.set_space_before(true);
+                                    return Ok(Html(element));
+                                }
+                                _ => {}
+                            }
+                            // Otherwise the `>` is the text after the element.
+                            self.push(Ok(Html(element)));
+                            return Ok(Text(GreaterThan.to_string()));
+                        }
+                        _ => return Ok(Html(element)),
+                    }
+                }
+                // Quoted text is the element's only child, as in `/p "Hello @name"`.
+                Str(span, _, text) => {
+                    if element.is_void() {
+                        return Err(VoidElementWithChildren(span, String::from(element.tag())));
+                    }
+                    let preserve_whitespace = self.preserve_whitespace;
+                    self.preserve_whitespace = preserve_whitespace ||
+                                               element.preserves_whitespace();
+                    let mut children = self.string_tokens(&text);
+                    self.preserve_whitespace = preserve_whitespace;
+                    element.add_children(&mut children);
+
+                    if let Some(&Symbol(open, OpenBrace)) = self.peek() {
+                        return Err(UnexpectedToken(Symbol(open, OpenBrace)));
+                    }
+                    break;
+                }
+                Symbol(span, Dot) => {
+                    match self.peek() {
+                        // The lexer has already kept the raw block's text as is.
+                        Some(&Symbol(_, OpenBrace)) => {}
+                        Some(&Word(_, _)) => {
+                            if let Some(Word(_, class)) = self.take() {
+                                element.add_class(class.trim().to_owned());
+                            }
+                        }
+                        Some(unexpected_token) => {
+                            let unexpected_token = unexpected_token.clone();
+                            self.push(Err(NoNameAttachedToClass(unexpected_token)))
+                        }
+                        None => return Err(UnexpectedEof(Symbol(span, Dot))),
+                    }
+                }
+                Symbol(span, Pound) => {
+                    match self.peek() {
+                        Some(&Word(_, _)) => {
+                            if let Some(Word(id_span, id)) = self.take() {
+                                if element.attribute("id").is_some() {
+                                    self.push(Err(DuplicateId(Word(id_span, id))));
+                                } else {
+                                    let id = id.trim().to_owned();
+                                    element.add_attribute(String::from("id"), id);
+                                }
+                            }
+                        }
+                        Some(unexpected_token) => {
+                            let unexpected_token = unexpected_token.clone();
+                            self.push(Err(NoNameAttachedToId(unexpected_token)))
+                        }
+                        None => return Err(UnexpectedEof(Symbol(span, Pound))),
+                    }
+                }
+                Symbol(span, OpenBrace) => {
+                    if element.is_void() {
+                        return Err(VoidElementWithChildren(span, String::from(element.tag())));
+                    }
+                    let preserve_whitespace = self.preserve_whitespace ||
+                                              element.preserves_whitespace();
+                    match self.parse_children_with(span, preserve_whitespace) {
+                        Ok(mut children) => element.add_children(&mut children),
+                        Err(error) => return Err(error),
+                    }
+                    break;
+                }
+                Symbol(span, Quote) => return Err(UnclosedQuote(span, Quote)),
+                unexpected_token => return Err(UnexpectedToken(unexpected_token)),
+            }
+        }
+        Ok(Html(element))
+    }
+
+    /// Parses the element after the `>` at `span` as the only child of `element`, as in
+    /// `/ul > /li {}`.
+    fn parse_chained_child(&mut self, mut element: Element, span: Span) -> AstResult {
+        if element.is_void() {
+            return Err(VoidElementWithChildren(span, String::from(element.tag())));
+        }
+        let slash = match self.take() {
+            Some(Symbol(slash, ForwardSlash)) => slash,
+            Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+            None => return Err(UnexpectedEof(Symbol(span, GreaterThan))),
+        };
+        let preserve_whitespace = self.preserve_whitespace;
+        self.preserve_whitespace = preserve_whitespace || element.preserves_whitespace();
+        let child = self.parse_element(slash);
+        self.preserve_whitespace = preserve_whitespace;
+        match self.spanned(slash, child) {
+            Ok(child) => element.add_children(&mut vec![child]),
+            Err(error) => return Err(error),
+        }
+        Ok(Html(element))
+    }
+
+    /// Parses `/!doctype`, followed by the rest of the line, which defaults to `html`. The name
+    /// of a common doctype, such as `html5`, or `xhtml-strict` is replaced by its declaration.
+    fn parse_doctype(&mut self, span: Span) -> Token {
+        let mut doctype = String::new();
+        let mut end = span.end;
+        while let Some(lexeme_span) = self.peek().map(Lexeme::span) {
+            if lexeme_span.start.line != span.start.line {
+                break;
+            }
+            let lexeme = match self.take() {
+                Some(lexeme) => lexeme,
+                None => break,
+            };
+            match lexeme {
+                Word(_, text) | RawText(_, text) | Literal(_, text) => doctype.push_str(&*text),
+                lexeme => {
+                    // Whitespace before a symbol, or string isn't kept by the lexer.
+                    if lexeme_span.start.offset > end.offset && !doctype.ends_with(' ') {
+                        doctype.push(' ');
+                    }
+                    match lexeme {
+                        Str(_, _, text) => {
+                            doctype.push(DOUBLEQUOTE);
+                            doctype.push_str(&*unescape_string(&text));
+                            doctype.push(DOUBLEQUOTE);
+                        }
+                        Symbol(_, operator) => doctype.push_str(&*operator.to_string()),
+                        _ => {}
+                    }
+                }
+            }
+            end = lexeme_span.end;
+        }
+
+        match doctype.trim() {
+            "" => Doctype(String::from("html")),
+            doctype => {
+                match DOCTYPES.iter().find(|&&(name, _)| name == doctype) {
+                    Some(&(_, declaration)) => Doctype(declaration.to_owned()),
+                    None => Doctype(doctype.to_owned()),
+                }
+            }
+        }
+    }
+
+    /// Whether an element has already been parsed, or the parser is within one, where a
+    /// doctype can't be.
+    fn is_after_element(&self) -> bool {
+        self.nested ||
+        self.output.iter().any(|result| {
+            match *result {
+                Ok(Html(_)) => true,
+                _ => false,
+            }
+        })
+    }
+
+    /// Parses a HTML comment, which is either a brace block, or the rest of the line after the
+    /// `/!`.
+    fn parse_html_comment(&mut self, span: Span) -> AstResult {
+        if let Some(&Symbol(open, OpenBrace)) = self.peek() {
+            let _ = self.take();
+            return match self.parse_children(open) {
+                Ok(children) => Ok(Comment(children)),
+                Err(error) => Err(error),
+            };
+        }
+
+        let mut lexemes = Vec::new();
+        while let Some(line) = self.peek().map(|lexeme| lexeme.span().start.line) {
+            if line != span.start.line {
+                break;
+            }
+            if let Some(lexeme) = self.take() {
+                lexemes.push(lexeme);
+            }
+        }
+        let preserve_whitespace = self.preserve_whitespace;
+        let parser = self.new_nested(lexemes, preserve_whitespace);
+        Ok(Comment(self.split_children(parser.output())))
+    }
+
+    /// Parses the attributes of an element, up to, and including the closing parameter. Tokens
+    /// that aren't valid attributes are left in the input, so that a brace can still start the
+    /// element's children.
+    fn parse_attributes(&mut self, element: &mut Element) -> Result<(), AstError> {
+        loop {
+            match self.peek() {
+                Some(&Symbol(_, CloseParam)) => {
+                    let _ = self.take();
+                    return Ok(());
+                }
+                Some(&Str(_, _, _)) => {
+                    if let Some(Str(_, _, key)) = self.take() {
+                        let key = format!("{}{}{}", '"', unescape_string(&key), '"');
+                        element.add_boolean_attribute(key);
+                    }
+                }
+                Some(&Symbol(span, quote @ Quote)) |
+                Some(&Symbol(span, quote @ SingleQuote)) => return Err(UnclosedQuote(span, quote)),
+                // Spreads an object's pairs as attributes, as in `(**@attributes)`.
+                Some(&Symbol(span, Star)) => {
+                    let _ = self.take();
+                    match self.take() {
+                        Some(Symbol(_, Star)) => {}
+                        Some(unexpected_token) => {
+                            return Err(InvalidTokenInAttributes(unexpected_token))
+                        }
+                        None => unexpected_eof!(Symbol(span, Star)),
+                    }
+                    match self.take() {
+                        Some(Symbol(span, At)) => {
+                            let name = get_namespaced_identifer!(self, span, ExpectedVariable, At);
+                            element.add_spread(name.trim().to_owned());
+                        }
+                        Some(unexpected_token) => {
+                            return Err(InvalidTokenInAttributes(unexpected_token))
+                        }
+                        None => unexpected_eof!(Symbol(span, Star)),
+                    }
+                }
+                Some(&Word(_, _)) => {
+                    if let Some(Word(span, key)) = self.take() {
+                        if let Err(error) = self.parse_attribute(element, span, key) {
+                            return Err(error);
+                        }
+                    }
+                }
+                Some(invalid_token) => return Err(InvalidTokenInAttributes(invalid_token.clone())),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Parses the value of the attribute `key`, if it has one, and adds it to the element.
+    fn parse_attribute(&mut self,
+                       element: &mut Element,
+                       span: Span,
+                       key: String)
+                       -> Result<(), AstError> {
+        // Classes are merged, so only other attributes can be given twice.
+        let duplicate = !self.options.allow_duplicate_attributes && key.trim() != "class" &&
+                        element.attribute(key.trim()).is_some();
+        let question = match self.peek() {
+            Some(&Word(question, ref text)) if text.trim() == "?" => {
+                Some(Word(question, text.clone()))
+            }
+            _ => None,
+        };
+        if let Some(question) = question {
+            let _ = self.take();
+            match self.parse_attribute_condition(question) {
+                Ok(_) if duplicate => self.push(Err(DuplicateAttribute(Word(span, key)))),
+                Ok(condition) => element.add_conditional_attribute(key, condition),
+                Err(error) => return Err(error),
+            }
+            return Ok(());
+        }
+
+        let value = match self.peek() {
+            Some(&Symbol(span, Equals)) => {
+                let _ = self.take();
+                match self.peek() {
+                    Some(&Word(_, _)) |
+                    Some(&Number(_, _)) => {
+                        match self.take() {
+                            Some(Word(_, text)) | Some(Number(_, text)) => {
+                                Some(AttributeValue::Literal(text.trim().to_owned()))
+                            }
+                            _ => None,
+                        }
+                    }
+                    Some(&Str(_, _, _)) => {
+                        match self.take() {
+                            Some(Str(_, _, text)) => {
+                                Some(AttributeValue::from_segments(split_string(&text)))
+                            }
+                            _ => None,
+                        }
+                    }
+                    Some(&Symbol(span, quote @ Quote)) |
+                    Some(&Symbol(span, quote @ SingleQuote)) => {
+                        return Err(UnclosedQuote(span, quote))
+                    }
+                    // An unquoted variable, as in `(data-id=@id)`.
+                    Some(&Symbol(span, At)) => {
+                        let _ = self.take();
+                        let name = get_namespaced_identifer!(self, span, ExpectedVariable, At);
+                        let name = Segment::Variable(name.trim().to_owned());
+                        Some(AttributeValue::Interpolated(vec![name]))
+                    }
+                    Some(unexpected_token) => {
+                        return Err(InvalidTokenInAttributes(unexpected_token.clone()))
+                    }
+                    None => unexpected_eof!(Symbol(span, Equals)),
+                }
+            }
+            Some(&Word(_, _)) |
+            Some(&Symbol(_, CloseParam)) |
+            Some(&Str(_, _, _)) |
+            Some(&Symbol(_, Quote)) |
+            Some(&Symbol(_, SingleQuote)) => None,
+            Some(invalid_token) => return Err(InvalidTokenInAttributes(invalid_token.clone())),
+            None => unexpected_eof!(Word(span, key)),
+        };
+
+        match value {
+            _ if duplicate => self.push(Err(DuplicateAttribute(Word(span, key)))),
+            Some(value) => element.add_attribute(key, value),
+            None => element.add_boolean_attribute(key),
+        }
+        Ok(())
+    }
+
+    /// Reads the `= @variable` after the `question` mark of a conditional attribute, as in
+    /// `(disabled ?= @locked)`.
+    fn parse_attribute_condition(&mut self, question: Lexeme) -> Result<Expr, AstError> {
+        let span = match self.take() {
+            Some(Symbol(span, Equals)) => span,
+            Some(unexpected_token) => return Err(InvalidTokenInAttributes(unexpected_token)),
+            None => unexpected_eof!(question),
+        };
+
+        match self.take() {
+            Some(Symbol(span, At)) => {
+                let name = get_namespaced_identifer!(self, span, ExpectedVariable, At);
+                Ok(Expr::Variable(name.trim().to_owned()))
+            }
+            Some(unexpected_token) => Err(InvalidTokenInAttributes(unexpected_token)),
+            None => unexpected_eof!(Symbol(span, Equals)),
+        }
+    }
+
+    /// Skips the rest of an element's attributes after an error. Stops after the closing
+    /// parameter, or before any brace, so the element's children are still parsed.
+    fn skip_attributes(&mut self) {
+        while let Some(token) = self.peek() {
+            match *token {
+                Symbol(_, OpenBrace) | Symbol(_, CloseBrace) => break,
+                Symbol(_, CloseParam) => {
+                    let _ = self.take();
+                    break;
+                }
+                _ => {
+                    let _ = self.take();
+                }
+            }
+        }
+    }
+
+    /// Parses what follows the backslash at `span`, in text:
+    ///
+    /// - An operator is written as is, so `\{` is a brace, and `\\` is a backslash.
+    /// - An `n`, or a `t` directly after it is a newline, or a tab, as in `\n`.
+    /// - Any other word keeps the backslash, so `C:\Users` is written as is.
+    /// - A backslash at the end of the source is written as is.
+    fn parse_escaped(&mut self, span: Span) -> AstResult {
+        let backslash = Text(BackSlash.to_string());
+        let control = match self.peek() {
+            Some(&Symbol(_, _)) => None,
+            Some(&Word(word_span, ref word)) if word_span.start == span.end => {
+                match word.chars().next() {
+                    Some('n') => Some('\n'),
+                    Some('t') => Some('\t'),
+                    _ => return Ok(backslash),
+                }
+            }
+            _ => return Ok(backslash),
+        };
+
+        match (control, self.take()) {
+            (Some(control), Some(Word(_, word))) => {
+                self.push(Ok(Text(control.to_string())));
+                self.parse_text(word[1..].to_owned())
+            }
+            (_, Some(Symbol(_, operator))) => Ok(Text(operator.to_string())),
+            _ => Ok(backslash),
+        }
+    }
+
+    fn parse_function(&mut self, span: Span) -> AstResult {
+        let identifier = get_namespaced_identifer!(self, span, InvalidFunctionCall, Dollar);
+        if identifier.trim() == "import" {
+            return self.parse_import(span);
+        }
+        let mut func_call = FunctionCall::new(identifier);
+
+        match self.take() {
+            Some(Symbol(open, OpenParam)) => {
+                let result = self.parse_arguments(open, |parser, token| {
+                    match token {
+                        Word(span, arg_name) => {
+                            match parser.take() {
+                                Some(Symbol(span, Equals)) => {
+                                    match parser.take() {
+                                        Some(Symbol(span, At)) => {
+                                            match parser.take() {
+                                                Some(Word(_, identifier)) => {
+                                                    func_call.add_value_arg(arg_name, identifier);
+                                                }
+                                                Some(unexpected_token) => {
+                                                    return Err(ExpectedVariable(unexpected_token))
+                                                }
+                                                None => unexpected_eof!(Symbol(span, At)),
+                                            }
+                                        }
+                                        Some(Symbol(span, Ampersand)) => {
+                                            match parser.take() {
+                                                Some(Word(_, identifier)) => {
+                                                    func_call.add_component_arg(arg_name,
+                                                                                identifier);
+                                                }
+                                                Some(unexpected_token) => {
+                                                    return Err(ExpectedCompCall(unexpected_token))
+                                                }
+                                                None => {
+                                                    unexpected_eof!(Symbol(span, Ampersand))
+                                                }
+                                            }
+                                        }
+                                        Some(Str(_, _, text)) => {
+                                            let text = Value::String(unescape_string(&text));
+                                            func_call.add_literal_arg(arg_name, text)
+                                        }
+                                        Some(Symbol(span, quote @ Quote)) |
+                                        Some(Symbol(span, quote @ SingleQuote)) => {
+                                            return Err(UnclosedQuote(span, quote))
+                                        }
+                                        Some(Number(span, number)) => {
+                                            match number_value(&number) {
+                                                Some(value) => {
+                                                    func_call.add_literal_arg(arg_name, value)
+                                                }
+                                                None => {
+                                                    return Err(UnexpectedToken(Number(span,
+                                                                                      number)))
+                                                }
+                                            }
+                                        }
+                                        Some(Word(span, word)) => {
+                                            match parser.read_literal(span, word) {
+                                                Ok(value) => {
+                                                    func_call.add_literal_arg(arg_name, value)
+                                                }
+                                                Err(error) => return Err(error),
+                                            }
+                                        }
+                                        Some(unexpected_token) => {
+                                            return Err(UnexpectedToken(unexpected_token))
+                                        }
+                                        None => unexpected_eof!(Symbol(span, Equals)),
+
+                                    }
+                                }
+                                Some(unexpected_token) => {
+                                    return Err(InvalidFunctionCall(unexpected_token))
+                                }
+                                None => unexpected_eof!(Word(span, arg_name)),
+
+                            }
+                        }
+                        unexpected_token => return Err(UnexpectedToken(unexpected_token)),
+                    }
+                    Ok(())
+                });
+                if let Err(error) = result {
+                    return Err(error);
+                }
+            }
+            Some(unexpected_token) => return Err(InvalidFunctionCall(unexpected_token)),
+            None => unexpected_eof!(Symbol(span, Dollar)),
+        }
+
+        if let Some(&Symbol(span, OpenBrace)) = self.peek() {
+            let _ = self.take();
+            match self.parse_children(span) {
+                Ok(mut children) => func_call.add_children(&mut children),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(Function(func_call))
+    }
+
+    /// Reads `true`, `false`, or `null` passed to a function. A number is lexed as a `Number`
+    /// instead.
+    fn read_literal(&self, span: Span, word: String) -> Result<Value, AstError> {
+        match serde_json::from_str(word.trim()) {
+            Ok(Value::Array(_)) |
+            Ok(Value::Object(_)) |
+            Ok(Value::String(_)) |
+            Err(_) => Err(UnexpectedToken(Word(span, word))),
+            Ok(value) => Ok(value),
+        }
+    }
+
+    /// Parses `$import(path = "file.poly", as = namespace)`, adding every component from the
+    /// file as `namespace.name`. The namespace defaults to the file's name.
+    fn parse_import(&mut self, span: Span) -> AstResult {
+        let mut path = None;
+        let mut namespace = None;
+
+        match self.take() {
+            Some(Symbol(_, OpenParam)) => {}
+            Some(unexpected_token) => return Err(InvalidFunctionCall(unexpected_token)),
+            None => unexpected_eof!(Symbol(span, Dollar)),
+        }
+
+        loop {
+            match self.take() {
+                Some(Word(key_span, key)) => {
+                    match self.take() {
+                        Some(Symbol(_, Equals)) => {}
+                        Some(unexpected_token) => {
+                            return Err(InvalidFunctionCall(unexpected_token))
+                        }
+                        None => unexpected_eof!(Word(key_span, key)),
+                    }
+
+                    let value = match self.take() {
+                        Some(Str(_, _, value)) => unescape_string(&value),
+                        Some(Symbol(span, quote @ Quote)) |
+                        Some(Symbol(span, quote @ SingleQuote)) => {
+                            return Err(UnclosedQuote(span, quote))
+                        }
+                        Some(Word(_, value)) | Some(Number(_, value)) => value.trim().to_owned(),
+                        Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+                        None => unexpected_eof!(Word(key_span, key)),
+                    };
+
+                    match key.trim() {
+                        "path" => path = Some(value),
+                        "as" => namespace = Some(value),
+                        _ => return Err(UnexpectedToken(Word(key_span, key))),
+                    }
+                }
+                Some(Symbol(_, Comma)) => {}
+                Some(Symbol(_, CloseParam)) => break,
+                Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+                None => unexpected_eof!(Symbol(span, Dollar)),
+            }
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None => return Err(MissingImport(span, String::new())),
+        };
+        let namespace = match namespace {
+            Some(namespace) => namespace,
+            None => {
+                match Path::new(&path).file_stem() {
+                    Some(stem) => stem.to_string_lossy().into_owned(),
+                    None => return Err(MissingImport(span, path)),
+                }
+            }
+        };
+
+        let file = match self.directory {
+            Some(ref directory) => directory.join(&path),
+            None => return Err(ImportWithoutDirectory(span)),
+        };
+
+        let source = match read_source(span, &file, path) {
+            Ok(source) => source,
+            Err(error) => return Err(error),
+        };
+
+        let lexemes = Lexer::with_options(&source, &self.options).output();
+        let imported = Parser::component_pass(lexemes);
+
+        for (name, component) in namespace_components(imported, &namespace) {
+            if self.components.contains_key(&name) {
+                return Err(DuplicateComponent(span, name));
+            }
+            let _ = self.components.insert(name, component);
+        }
+
+        Ok(Text(String::new()))
+    }
+
+
+
+    fn parse_variable(&mut self, span: Span) -> AstResult {
+        let identifier = get_namespaced_identifer!(self, span, ExpectedVariable, At);
+
+        match identifier.trim() {
+            "each" => {
+                let token = self.parse_loop(span);
+                self.spanned(span, token)
+            }
+            "include" => self.parse_include(span),
+            "extends" => self.parse_extends(span),
+            "block" => self.parse_template_block(span),
+            "if" | "unless" => {
+                let token = self.parse_conditional(span, identifier.trim() == "unless");
+                self.spanned(span, token)
+            }
+            "elseif" => self.parse_else_if(span),
+            "else" => self.parse_else(span),
+            "switch" => {
+                let token = self.parse_switch(span);
+                self.spanned(span, token)
+            }
+            "case" | "default" => Err(CaseOutsideSwitch(span)),
+            _ => {
+                // The whitespace after the name is kept as text, so it's still in the output.
+                let name = identifier.trim_right().to_owned();
+                let mut whitespace = identifier[name.len()..].to_owned();
+                let mut end = end_of(span.end, &*name);
+
+                let mut filters = Vec::new();
+                while let Some(&Symbol(pipe, Pipe)) = self.peek() {
+                    let _ = self.take();
+                    match self.take() {
+                        Some(Word(filter_span, filter)) => {
+                            let length = filter.trim_right().len();
+                            whitespace = filter[length..].to_owned();
+                            end = end_of(filter_span.start, &filter[..length]);
+                            filters.push(filter.trim().to_owned());
+                        }
+                        Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+                        None => unexpected_eof!(Symbol(pipe, Pipe)),
+                    }
+                }
+
+                let source = SourceSpan(Some(Span::new(span.start, end)));
+                let variable = if name.starts_with('!') {
+                    RawVariable(name[1..].to_owned(), filters, source)
+                } else {
+                    Variable(name, filters, source)
+                };
+
+                if whitespace.is_empty() {
+                    Ok(variable)
+                } else {
+                    self.push(Ok(variable));
+                    Ok(Text(self.whitespace(whitespace)))
+                }
+            }
+        }
+    }
+
+    /// Parses `@include "file.poly"`, adding the file's tokens, and components in place of the
+    /// include. The path is resolved against the template's directory, and paths within the
+    /// included file against its own directory.
+    fn parse_include(&mut self, span: Span) -> AstResult {
+        let path = match self.take() {
+            Some(Str(_, _, path)) => unescape_string(&path),
+            Some(Symbol(open, quote @ Quote)) |
+            Some(Symbol(open, quote @ SingleQuote)) => return Err(UnclosedQuote(open, quote)),
+            Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+            None => unexpected_eof!(Symbol(span, At)),
+        };
+
+        let mut parser = match self.parse_file(span, path) {
+            Ok(parser) => parser,
+            Err(error) => return Err(error),
+        };
+        if let Err(error) = self.add_components(span, &mut parser) {
+            return Err(error);
+        }
+        for result in parser.output() {
+            self.push(result);
+        }
+        Ok(Text(String::new()))
+    }
+
+    /// Parses `@extends "base.poly"`, which renders the base template in place of this one,
+    /// with each `@block` this template defines replacing the base's block of the same name.
+    /// Anything else in this template isn't rendered.
+    fn parse_extends(&mut self, span: Span) -> AstResult {
+        if self.nested {
+            return Err(UnexpectedToken(Symbol(span, At)));
+        }
+
+        let path = match self.take() {
+            Some(Str(_, _, path)) => unescape_string(&path),
+            Some(Symbol(open, quote @ Quote)) |
+            Some(Symbol(open, quote @ SingleQuote)) => return Err(UnclosedQuote(open, quote)),
+            Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+            None => unexpected_eof!(Symbol(span, At)),
+        };
+        self.extends = Some((span, path));
+        Ok(Text(String::new()))
+    }
+
+    /// Parses `@block name { }`, a part of a base template that a template extending it can
+    /// replace.
+    fn parse_template_block(&mut self, span: Span) -> AstResult {
+        let name = get_identifer!(self.take(), span, ExpectedVariable);
+        let name = name.trim().to_owned();
+
+        match self.parse_block(Symbol(span, At)) {
+            Ok(body) => Ok(Block { name: name, body: body }),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Replaces the output with that of the base template at `path`, after replacing its
+    /// blocks with the blocks in the output. A block the base doesn't have is an error.
+    fn extend(&mut self, span: Span, path: String) {
+        let mut base = match self.parse_file(span, path) {
+            Ok(parser) => parser,
+            Err(error) => return self.push(Err(error)),
+        };
+        if let Err(error) = self.add_components(span, &mut base) {
+            self.push(Err(error));
+        }
+
+        let mut blocks = BTreeMap::new();
+        let mut errors = Vec::new();
+        for result in mem::replace(&mut self.output, Vec::new()) {
+            match result {
+                Ok(Block { name, body }) => {
+                    let _ = blocks.insert(name, body);
+                }
+                Ok(_) => {}
+                Err(error) => errors.push(Err(error)),
+            }
+        }
+
+        let mut found = HashSet::new();
+        for result in base.output() {
+            match result {
+                Ok(mut token) => {
+                    replace_block(&mut token, &blocks, &mut found);
+                    self.push(Ok(token));
+                }
+                Err(error) => self.push(Err(error)),
+            }
+        }
+        for name in blocks.keys() {
+            if !found.contains(name) {
+                self.push(Err(UnknownBlock(span, name.clone())));
+            }
+        }
+        self.output.extend(errors);
+    }
+
+    /// Reads, and parses the template at `path`, which is relative to the template's
+    /// directory, for an `@include`, or `@extends` at `span`. Paths within the file are relative
+    /// to the file's own directory.
+    fn parse_file(&self, span: Span, path: String) -> Result<Parser, AstError> {
+        let file = match self.directory {
+            Some(ref directory) => directory.join(&path),
+            None => return Err(ImportWithoutDirectory(span)),
+        };
+        let canonical_file = canonical(&file);
+        if self.includes.contains(&canonical_file) {
+            return Err(IncludeCycle(span, file));
+        }
+
+        let source = match read_source(span, &file, path) {
+            Ok(source) => source,
+            Err(error) => return Err(error),
+        };
+
+        let directory = match file.parent() {
+            Some(directory) => directory.to_path_buf(),
+            None => PathBuf::new(),
+        };
+        let mut parser = Parser::new_parser(Lexer::with_options(&source, &self.options).output(),
+                                            self.nested,
+                                            Some(directory),
+                                            self.preserve_whitespace);
+        parser.includes = self.includes.clone();
+        parser.includes.push(canonical_file);
+        parser.void_elements = self.void_elements.clone();
+        parser.options = self.options.clone();
+        Ok(Parser::run(parser))
+    }
+
+    /// Adds the components of a parsed file, where a name that's already taken is an error at
+    /// `span`.
+    fn add_components(&mut self, span: Span, parser: &mut Parser) -> Result<(), AstError> {
+        for (name, component) in parser.components.drain() {
+            if self.components.contains_key(&name) {
+                return Err(DuplicateComponent(span, name));
+            }
+            let _ = self.components.insert(name, component);
+        }
+        Ok(())
+    }
+
+    /// Parses `@each item in @items { }`, or `@each key, value in @items { }`, which also binds
+    /// each item's index, or key. The items can also be a range, such as `1..5`, or
+    /// `1..@page_count`.
+    fn parse_loop(&mut self, span: Span) -> AstResult {
+        let mut binding = get_identifer!(self.take(), span, ExpectedVariable);
+        let mut key = None;
+        if let Some(&Symbol(comma, Comma)) = self.peek() {
+            let _ = self.take();
+            key = Some(binding.trim().to_owned());
+            binding = get_identifer!(self.take(), comma, ExpectedVariable);
+        }
+
+        match self.take() {
+            Some(Word(_, ref keyword)) if keyword.trim() == "in" => {}
+            Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+            None => unexpected_eof!(Word(span, binding)),
+        }
+
+        let collection = match self.take() {
+            Some(Symbol(span, At)) => {
+                let name = get_namespaced_identifer!(self, span, ExpectedVariable, At);
+                Collection::Variable(name.trim().to_owned())
+            }
+            Some(Word(start_span, start)) => {
+                match self.parse_range(start_span, start) {
+                    Ok(range) => range,
+                    Err(error) => return Err(error),
+                }
+            }
+            Some(unexpected_token) => return Err(ExpectedVariable(unexpected_token)),
+            None => unexpected_eof!(Word(span, binding)),
+        };
+
+        match self.parse_block(Word(span, binding.clone())) {
+            Ok(body) => {
+                Ok(Loop {
+                    key: key,
+                    binding: binding.trim().to_owned(),
+                    collection: collection,
+                    body: body,
+                    else_branch: None,
+                    span: SourceSpan::default(),
+                })
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Parses the rest of a range after its start, which has to be an integer. The end is an
+    /// integer, or a variable.
+    fn parse_range(&mut self, span: Span, text: String) -> Result<Collection, AstError> {
+        let start = match text.trim().parse::<i64>() {
+            Ok(start) => Expr::Literal(integer_value(start)),
+            Err(_) => return Err(ExpectedVariable(Word(span, text))),
+        };
+
+        for _ in 0..2 {
+            match self.take() {
+                Some(Symbol(_, Dot)) => {}
+                Some(unexpected_token) => return Err(UnexpectedToken(unexpected_token)),
+                None => unexpected_eof!(Word(span, text)),
+            }
+        }
+
+        let end = match self.take() {
+            Some(Symbol(span, At)) => {
+                Expr::Variable(get_namespaced_identifer!(self, span, ExpectedVariable, At)
+                                   .trim()
+                                   .to_owned())
+            }
+            Some(Word(span, end)) => {
+                match end.trim().parse::<i64>() {
+                    Ok(end) => Expr::Literal(integer_value(end)),
+                    Err(_) => return Err(ExpectedVariable(Word(span, end))),
+                }
+            }
+            Some(unexpected_token) => return Err(ExpectedVariable(unexpected_token)),
+            None => unexpected_eof!(Word(span, text)),
+        };
+
+        Ok(Collection::Range(start, end))
+    }
+
+    fn parse_text(&mut self, word: String) -> AstResult {
+        let mut text = String::from(word);
+        loop {
+            match self.peek() {
+                Some(&Word(_, ref peek_text)) => text.push_str(&*peek_text),
+                _ => return Ok(Text(self.whitespace(text))),
+            }
+            let _ = self.take();
+        }
+    }
+
+    /// 
+    fn parse_token(&mut self) -> AstResult {
+        let previous_end = self.end;
+        let kept_whitespace = self.kept_whitespace;
+        match self.take() {
+            // concatenate all the word tokens that are adjacent to each other into a single "Text"
+            // token.
+            Some(Word(_, word)) | Some(Number(_, word)) => self.parse_text(word),
+            Some(RawText(_, text)) => Ok(Raw(text)),
+            Some(Literal(_, text)) => Ok(Text(text)),
+            Some(OpenComment(span)) => Err(UnclosedComment(span)),
+            // A string within text is written with its quotes, and any variables within it. The
+            // lexer drops the whitespace before it, which is put back unless the text before
+            // it kept it.
+            Some(Str(span, quote, text)) => {
+                let mut open = quote.to_string();
+                if !kept_whitespace && previous_end.map_or(false, |end| end != span.start) {
+                    open.insert(0, ' ');
+                }
+                self.push(Ok(Text(open)));
+                for token in self.string_tokens(&text) {
+                    self.push(Ok(token));
+                }
+                Ok(Text(quote.to_string()))
+            }
+            Some(Symbol(span, At)) => self.parse_variable(span),
+            Some(Symbol(span, ForwardSlash)) => {
+                let token = self.parse_element(span);
+                self.spanned(span, token)
+            }
+            Some(Symbol(span, Dot)) if self.starts_implicit_div(previous_end, span) => {
+                let token = self.parse_implicit_div(Dot);
+                self.spanned(span, token)
+            }
+            Some(Symbol(span, Pound)) if self.starts_implicit_div(previous_end, span) => {
+                let token = self.parse_implicit_div(Pound);
+                self.spanned(span, token)
+            }
+            Some(Symbol(span, BackSlash)) => self.parse_escaped(span),
+            Some(Symbol(span, CloseBrace)) => Err(UnclosedCloseBraces(span)),
+            Some(Symbol(span, Ampersand)) => {
+                let allow_definition = !self.nested;
+                let token = self.parse_component(allow_definition, span);
+                self.spanned(span, token)
+            }
+            Some(Symbol(span, Dollar)) => {
+                let token = self.parse_function(span);
+                self.spanned(span, token)
+            }
+            Some(Symbol(_, operator)) => Ok(Text(operator.to_string())),
+            None => Err(Eof),
+        }
+    }
+
+    /// Sets the span of a parsed token to run from the start of `start` to the end of the last
+    /// lexeme taken.
+    fn spanned(&self, start: Span, token: AstResult) -> AstResult {
+        match (token, self.end) {
+            (Ok(mut token), Some(end)) => {
+                token.set_span(Span::new(start.start, end));
+                Ok(token)
+            }
+            (token, _) => token,
+        }
+    }
+}
+
+/// Prefixes the name of every component with `namespace`, along with the calls the components
+/// make to each other.
+pub fn namespace_components(components: HashMap<String, Component>,
+                            namespace: &str)
+                            -> HashMap<String, Component> {
+    let names: HashSet<String> = components.keys().cloned().collect();
+    let mut namespaced = HashMap::new();
+
+    for (name, mut component) in components {
+        let name = format!("{}.{}", namespace, name);
+        namespace_calls(component.ast_mut(), namespace, &names);
+        component.set_name(name.clone());
+        let _ = namespaced.insert(name, component);
+    }
+    namespaced
+}
+
+/// The absolute path of `file`, so the same file is found however its path was written. The
+/// path is left as is if it can't be resolved.
+fn canonical(file: &Path) -> PathBuf {
+    match fs::canonicalize(file) {
+        Ok(path) => path,
+        Err(_) => file.to_path_buf(),
+    }
+}
+
+/// Reads the source of `file`, given as `path` by an `$import`, `@include`, or `@extends` at
+/// `span`. A file that doesn't exist is missing, and any other failure is reported as it is.
+fn read_source(span: Span, file: &Path, path: String) -> Result<String, AstError> {
+    let mut source = String::new();
+    let read = match File::open(file) {
+        Ok(mut file) => file.read_to_string(&mut source),
+        Err(error) => Err(error),
+    };
+    match read {
+        Ok(_) => Ok(source),
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Err(MissingImport(span, path)),
+        Err(error) => Err(UnreadableFile(span, file.to_path_buf(), error.to_string())),
+    }
+}
+
+/// The position after `text`, when it starts at `start`, and is on a single line.
+fn end_of(start: Position, text: &str) -> Position {
+    Position::new(start.line,
+                  start.column + text.chars().count(),
+                  start.offset + text.len())
+}
+
+/// Splits a string into its literal text, and the variables within it, written as `@name`,
+/// or `@!name` for a variable that isn't escaped. A `\@` is an `@` that doesn't start a
+/// variable, and `\\` is a backslash.
+fn split_string(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut value = String::new();
+    let mut characters = text.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        let mut lookahead = characters.clone();
+        match (character, lookahead.next(), lookahead.next()) {
+            (BACKSLASH, Some(escaped @ BACKSLASH), _) |
+            (BACKSLASH, Some(escaped @ AT), _) => {
+                let _ = characters.next();
+                value.push(escaped);
+            }
+            (AT, Some('!'), Some(next)) |
+            (AT, Some(next), _) if is_name_character(next) => {
+                if !value.is_empty() {
+                    segments.push(Segment::Literal(value));
+                    value = String::new();
+                }
+
+                let mut name = String::new();
+                if let Some(&'!') = characters.peek() {
+                    let _ = characters.next();
+                    name.push('!');
+                }
+                loop {
+                    while let Some(&character) = characters.peek() {
+                        if !is_name_character(character) {
+                            break;
+                        }
+                        let _ = characters.next();
+                        name.push(character);
+                    }
+
+                    // A `.` is only part of the name when a member follows it.
+                    let mut lookahead = characters.clone();
+                    match (lookahead.next(), lookahead.next()) {
+                        (Some(DOT), Some(member)) if is_name_character(member) => {
+                            let _ = characters.next();
+                            name.push(DOT);
+                        }
+                        _ => break,
+                    }
+                }
+                segments.push(Segment::Variable(name));
+            }
+            (character, _, _) => value.push(character),
+        }
+    }
+
+    if !value.is_empty() {
+        segments.push(Segment::Literal(value));
+    }
+    segments
+}
+
+/// The value of a number lexeme. A number that leaves out the digits before its `.`, such as
+/// `.5`, or `-.5`, has a zero put in front of it.
+fn number_value(number: &str) -> Option<Value> {
+    let number = number.trim();
+    let number = if number.starts_with("-.") {
+        format!("-0{}", &number[1..])
+    } else if number.starts_with('.') {
+        format!("0{}", number)
+    } else {
+        number.to_owned()
+    };
+    serde_json::from_str(&number).ok()
+}
+
+/// The text of a string, with any variables within it kept as written.
+fn unescape_string(text: &str) -> String {
+    let mut value = String::new();
+    for segment in split_string(text) {
+        match segment {
+            Segment::Literal(text) => value.push_str(&*text),
+            Segment::Variable(name) => {
+                value.push(AT);
+                value.push_str(&*name);
+            }
+        }
+    }
+    value
+}
+
+/// Whether the lexeme is a backslash escaping the lexeme after it, which it doesn't if it's
+/// itself `escaped`.
+fn is_escape(lexeme: &Lexeme, escaped: bool) -> bool {
+    match *lexeme {
+        Symbol(_, BackSlash) => !escaped,
+        _ => false,
+    }
+}
+
+/// A piece of an `@if` condition.
+#[derive(Clone, Debug)]
+enum ExprToken {
+    /// A variable, or literal.
+    Operand(Expr),
+    /// An operator, or parenthesis.
+    Op(&'static str),
+}
+
+/// Splits the unquoted text of a condition into operators, and operands, returning false if it
+/// contains anything else. The `@` before a variable is optional.
+fn split_expression(text: &str, tokens: &mut Vec<ExprToken>) -> bool {
+    let mut characters = text.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        let token = match character {
+            '@' => continue,
+            character if character.is_whitespace() => continue,
+            '(' => ExprToken::Op("("),
+            ')' => ExprToken::Op(")"),
+            '=' | '!' | '<' | '>' => {
+                let equals = characters.peek() == Some(&'=');
+                if equals {
+                    let _ = characters.next();
+                }
+
+                match (character, equals) {
+                    ('=', true) => ExprToken::Op("=="),
+                    ('!', true) => ExprToken::Op("!="),
+                    ('!', false) => ExprToken::Op("!"),
+                    ('<', true) => ExprToken::Op("<="),
+                    ('<', false) => ExprToken::Op("<"),
+                    ('>', true) => ExprToken::Op(">="),
+                    ('>', false) => ExprToken::Op(">"),
+                    _ => return false,
+                }
+            }
+            '&' | '|' => {
+                if characters.next() != Some(character) {
+                    return false;
+                }
+
+                if character == '&' {
+                    ExprToken::Op("&&")
+                } else {
+                    ExprToken::Op("||")
+                }
+            }
+            character if character.is_alphanumeric() || character == '_' || character == '-' => {
+                let mut word = character.to_string();
+                while let Some(&next) = characters.peek() {
+                    if next.is_alphanumeric() || next == '_' || next == '-' || next == '.' {
+                        word.push(next);
+                        let _ = characters.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                // Numbers, `true`, `false`, and `null` are literals, anything else is a variable.
+                match serde_json::from_str(&word) {
+                    Ok(value) => ExprToken::Operand(Expr::Literal(value)),
+                    Err(_) => ExprToken::Operand(Expr::Variable(word)),
+                }
+            }
+            _ => return false,
+        };
+        tokens.push(token);
+    }
+    true
+}
+
+/// Parses `left || right`, the operator with the lowest precedence.
+fn parse_or(tokens: &[ExprToken], index: &mut usize) -> Option<Expr> {
+    let mut left = match parse_and(tokens, index) {
+        Some(left) => left,
+        None => return None,
+    };
+
+    while let Some(&ExprToken::Op("||")) = tokens.get(*index) {
+        *index += 1;
+        left = match parse_and(tokens, index) {
+            Some(right) => Expr::Or(Box::new(left), Box::new(right)),
+            None => return None,
+        };
+    }
+    Some(left)
+}
+
+/// Parses `left && right`.
+fn parse_and(tokens: &[ExprToken], index: &mut usize) -> Option<Expr> {
+    let mut left = match parse_comparison(tokens, index) {
+        Some(left) => left,
+        None => return None,
+    };
+
+    while let Some(&ExprToken::Op("&&")) = tokens.get(*index) {
+        *index += 1;
+        left = match parse_comparison(tokens, index) {
+            Some(right) => Expr::And(Box::new(left), Box::new(right)),
+            None => return None,
+        };
+    }
+    Some(left)
+}
+
+/// Parses a comparison, such as `left == right`, or a single operand.
+fn parse_comparison(tokens: &[ExprToken], index: &mut usize) -> Option<Expr> {
+    let left = match parse_unary(tokens, index) {
+        Some(left) => left,
+        None => return None,
+    };
+
+    let comparison = match tokens.get(*index) {
+        Some(&ExprToken::Op(operator)) => Comparison::from_operator(operator),
+        _ => None,
+    };
+
+    match comparison {
+        Some(comparison) => {
+            *index += 1;
+            match parse_unary(tokens, index) {
+                Some(right) => Some(Expr::Compare(Box::new(left), comparison, Box::new(right))),
+                None => None,
+            }
+        }
+        None => Some(left),
+    }
+}
+
+/// Parses `!operand`, a parenthesised expression, or an operand.
+fn parse_unary(tokens: &[ExprToken], index: &mut usize) -> Option<Expr> {
+    let token = match tokens.get(*index) {
+        Some(token) => token.clone(),
+        None => return None,
+    };
+    *index += 1;
+
+    match token {
+        ExprToken::Operand(operand) => Some(operand),
+        ExprToken::Op("!") => {
+            match parse_unary(tokens, index) {
+                Some(operand) => Some(Expr::Not(Box::new(operand))),
+                None => None,
+            }
+        }
+        ExprToken::Op("(") => {
+            let expression = parse_or(tokens, index);
+            match tokens.get(*index) {
+                Some(&ExprToken::Op(")")) => {
+                    *index += 1;
+                    expression
+                }
+                _ => None,
+            }
+        }
+        ExprToken::Op(_) => None,
+    }
+}
+
+/// Prefixes calls to components from the same imported file with the file's namespace, so the
+/// components can still call each other.
+fn namespace_calls(tokens: &mut Vec<Token>, namespace: &str, names: &HashSet<String>) {
+    for token in tokens.iter_mut() {
+        match *token {
+            Html(ref mut element) => {
+                if let Some(ref mut call) = *element.resource_mut() {
+                    namespace_call(call, namespace, names);
+                }
+                namespace_calls(element.children_mut(), namespace, names);
+            }
+            CompCall(ref mut call) => namespace_call(call, namespace, names),
+            Function(ref mut call) => namespace_calls(call.children_mut(), namespace, names),
+            Conditional { ref mut then_branch, ref mut else_ifs, ref mut else_branch, .. } => {
+                namespace_calls(then_branch, namespace, names);
+                for &mut (_, ref mut children) in else_ifs.iter_mut() {
+                    namespace_calls(children, namespace, names);
+                }
+                if let Some(ref mut else_branch) = *else_branch {
+                    namespace_calls(else_branch, namespace, names);
+                }
+            }
+            Loop { ref mut body, ref mut else_branch, .. } => {
+                namespace_calls(body, namespace, names);
+                if let Some(ref mut else_branch) = *else_branch {
+                    namespace_calls(else_branch, namespace, names);
+                }
+            }
+            Switch { ref mut cases, ref mut default, .. } => {
+                for &mut (_, ref mut children) in cases.iter_mut() {
+                    namespace_calls(children, namespace, names);
+                }
+                if let Some(ref mut default) = *default {
+                    namespace_calls(default, namespace, names);
+                }
+            }
+            Comment(ref mut children) |
+            Block { body: ref mut children, .. } => namespace_calls(children, namespace, names),
+            _ => {}
+        }
+    }
+}
+
+/// Replaces the body of each block within the token that has a replacement in `blocks`,
+/// recording the name of every block found.
+fn replace_block(token: &mut Token,
+                 blocks: &BTreeMap<String, Vec<Token>>,
+                 found: &mut HashSet<String>) {
+    match *token {
+        Html(ref mut element) => replace_blocks(element.children_mut(), blocks, found),
+        CompCall(ref mut call) => replace_blocks(call.children_mut(), blocks, found),
+        Function(ref mut call) => replace_blocks(call.children_mut(), blocks, found),
+        Conditional { ref mut then_branch, ref mut else_ifs, ref mut else_branch, .. } => {
+            replace_blocks(then_branch, blocks, found);
+            for &mut (_, ref mut children) in else_ifs.iter_mut() {
+                replace_blocks(children, blocks, found);
+            }
+            if let Some(ref mut else_branch) = *else_branch {
+                replace_blocks(else_branch, blocks, found);
+            }
+        }
+        Loop { ref mut body, ref mut else_branch, .. } => {
+            replace_blocks(body, blocks, found);
+            if let Some(ref mut else_branch) = *else_branch {
+                replace_blocks(else_branch, blocks, found);
+            }
+        }
+        Switch { ref mut cases, ref mut default, .. } => {
+            for &mut (_, ref mut children) in cases.iter_mut() {
+                replace_blocks(children, blocks, found);
+            }
+            if let Some(ref mut default) = *default {
+                replace_blocks(default, blocks, found);
+            }
+        }
+        Comment(ref mut children) => replace_blocks(children, blocks, found),
+        Block { ref name, ref mut body } => {
+            // Blocks within the block are still found, even when it's replaced.
+            replace_blocks(body, blocks, found);
+            let _ = found.insert(name.clone());
+            if let Some(replacement) = blocks.get(name) {
+                *body = replacement.clone();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn replace_blocks(tokens: &mut Vec<Token>,
+                  blocks: &BTreeMap<String, Vec<Token>>,
+                  found: &mut HashSet<String>) {
+    for token in tokens.iter_mut() {
+        replace_block(token, blocks, found);
+    }
+}
+
+fn namespace_call(call: &mut ComponentCall, namespace: &str, names: &HashSet<String>) {
+    if names.contains(call.name()) {
+        let name = format!("{}.{}", namespace, call.name());
+        call.set_name(name);
+    }
+    namespace_calls(call.children_mut(), namespace, names);
+}
+
+#[allow(dead_code, unused_imports)]
+mod tests {
+    use super::Parser;
+    use compiler::Lexer;
+    use compiler::tokens::{ArgKey, AstError, AttributeValue, Collection, Comparison,
+                           ComponentCall, Expr, FunctionCall, Position, Segment, SourceSpan, Span,
+                           Token};
+    use compiler::tokens::AstError::*;
+    use compiler::tokens::Lexeme::{Symbol, Word};
+    use compiler::tokens::Operator::{At, Comma, Equals, OpenBrace, OpenParam, Pound, Quote,
+                                     SingleQuote};
+    use compiler::tokens::Token::*;
+    use serde_json::Value;
+
+    /// Span of a symbol on the first line of the source.
+    fn symbol_span(column: usize) -> Span {
+        Span::new(Position::new(1, column, column - 1), Position::new(1, column + 1, column))
+    }
+
+    fn parse(source: &str) -> Result<Vec<Token>, Vec<AstError>> {
+        Parser::parse(Lexer::tokenize(source))
+    }
+
+    #[test]
+    fn parse_valid() {
+        let tokens = parse("/div{/p{Hello @name}}").unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        match tokens[0] {
+            Html(ref element) => assert_eq!(element.children().len(), 1),
+            ref token => panic!("Expected an element, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn parse_large_template() {
+        let item = "/li.item(data-id=\"@item.id\" title='Item' hidden){@item.name | upper \
+                    /a(href=@item.url){Open} $format(value=1.5)}";
+        let expected = parse(item).unwrap();
+        let source: Vec<&str> = (0..2000).map(|_| item).collect();
+
+        let tokens = parse(&*source.join("\n")).unwrap();
+        assert_eq!(tokens.len(), 2000);
+        for token in &tokens {
+            assert_eq!(token, &expected[0]);
+        }
+    }
+
+    #[test]
+    fn parse_collects_nested_errors() {
+        let errors = parse("/div{/p.*{Hello}}").unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        match errors[0] {
+            NoNameAttachedToClass(_) => {}
+            ref error => panic!("Expected NoNameAttachedToClass, got: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn parse_recovers_after_error() {
+        let errors = parse("/p.*{Hello} /div#{World}").unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        match (&errors[0], &errors[1]) {
+            (&NoNameAttachedToClass(_), &NoNameAttachedToId(_)) => {}
+            errors => panic!("Expected a class, and an id error, got: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn duplicate_attribute() {
+        let errors = parse(r#"/input(name="a" required name="b" required)"#).unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        for error in &errors {
+            match *error {
+                DuplicateAttribute(_) => {}
+                ref error => panic!("Expected DuplicateAttribute, got: {:?}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_id() {
+        for source in &["/div#a#b{Hello}", "#a#b{Hello}"] {
+            let errors = parse(source).unwrap_err();
+
+            assert_eq!(errors.len(), 1);
+            match errors[0] {
+                DuplicateId(Word(_, ref id)) => assert_eq!(id, "b"),
+                ref error => panic!("Expected DuplicateId, got: {:?}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn id_at_eof() {
+        match parse("/div#") {
+            Err(ref errors) => assert_eq!(errors, &[UnexpectedEof(Symbol(symbol_span(5), Pound))]),
+            result => panic!("Expected an unexpected end of file, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_all_recovers_at_new_line() {
+        let source = "$(broken) after\nHello\n&{Broken}\n/span{Fine}";
+        let (tokens, errors) = Parser::parse_all(Lexer::tokenize(source));
+
+        match errors[..] {
+            [InvalidFunctionCall(_), InvalidComponent(_)] => {}
+            ref errors => panic!("Expected a function, and a component error, got: {:?}", errors),
+        }
+        assert_eq!(tokens[0], Text(String::from("Hello ")));
+        match tokens[1] {
+            Html(ref element) => assert_eq!(element.tag(), "span"),
+            ref token => panic!("Expected an element, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn conditional() {
+        let tokens = parse("@if logged_in {Hello} @else {Goodbye}").unwrap();
+
+        assert_eq!(tokens[0],
+                   Conditional {
+                       condition: Expr::Variable(String::from("logged_in")),
+                       then_branch: vec![Text(String::from("Hello"))],
+                       else_ifs: Vec::new(),
+                       else_branch: Some(vec![Text(String::from("Goodbye"))]),
+                       span: SourceSpan::default(),
+                   });
+    }
+
+    #[test]
+    fn conditional_expression() {
+        let source = r#"@if !@banned && @role == "admin" || (count >= 3 && count != -1.5) {Hi}"#;
+        let variable = |name: &str| Box::new(Expr::Variable(String::from(name)));
+        let literal = |value: Value| Box::new(Expr::Literal(value));
+
+        let admin = Expr::Compare(variable("role"),
+                                  Comparison::Equal,
+                                  literal(Value::String(String::from("admin"))));
+        let at_least = Expr::Compare(variable("count"),
+                                     Comparison::GreaterOrEqual,
+                                     literal(Value::U64(3)));
+        let not_equal = Expr::Compare(variable("count"),
+                                      Comparison::NotEqual,
+                                      literal(Value::F64(-1.5)));
+        let expected = Expr::Or(Box::new(Expr::And(Box::new(Expr::Not(variable("banned"))),
+                                                   Box::new(admin))),
+                                Box::new(Expr::And(Box::new(at_least), Box::new(not_equal))));
+
+        match parse(source).unwrap()[0] {
+            Conditional { ref condition, .. } => assert_eq!(condition, &expected),
+            ref token => panic!("Expected a conditional, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn invalid_condition() {
+        for source in &["@if {Hi}", "@if @a == {Hi}", "@if a & b {Hi}", "@if (a {Hi}"] {
+            match parse(source).unwrap_err()[0] {
+                InvalidExpression(span) => assert_eq!(span.start.column, 1),
+                ref error => panic!("Expected InvalidExpression for {}, got: {:?}", source, error),
+            }
+        }
+    }
+
+    #[test]
+    fn else_if_chain() {
+        let tokens = parse("@if a {A} @elseif b {B} @else if c {C} @else {D}").unwrap();
+        let variable = |name: &str| Expr::Variable(String::from(name));
+
+        assert_eq!(tokens[0],
+                   Conditional {
+                       condition: variable("a"),
+                       then_branch: vec![Text(String::from("A"))],
+                       else_ifs: vec![(variable("b"), vec![Text(String::from("B"))]),
+                                      (variable("c"), vec![Text(String::from("C"))])],
+                       else_branch: Some(vec![Text(String::from("D"))]),
+                       span: SourceSpan::default(),
+                   });
+    }
+
+    #[test]
+    fn unless() {
+        match parse("@unless a {A}").unwrap()[0] {
+            Conditional { ref condition, .. } => {
+                assert_eq!(condition,
+                           &Expr::Not(Box::new(Expr::Variable(String::from("a")))))
+            }
+            ref token => panic!("Expected a conditional, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn dangling_else_if() {
+        for source in &["/p{Hello} @elseif a {Goodbye}", "@if a {A} @else {B} @elseif c {C}"] {
+            match parse(source).unwrap_err()[0] {
+                DanglingElse(_) => {}
+                ref error => panic!("Expected DanglingElse for {}, got: {:?}", source, error),
+            }
+        }
+    }
+
+    #[test]
+    fn dangling_else() {
+        let errors = parse("/p{Hello} @else {Goodbye}").unwrap_err();
+
+        match errors[0] {
+            DanglingElse(span) => assert_eq!(span.start.column, 11),
+            ref error => panic!("Expected DanglingElse, got: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_every_independent_error() {
+        let source = r#"
+        /div {
+            /p.*{One}
+            /p#{Two}
+            /a(href=) {Three}
+            /a(href="page.html" {Four}
+            @{Five}
+            /p{Six}
+        }"#;
+        let errors = parse(source).unwrap_err();
+
+        assert_eq!(errors.len(), 5);
+    }
+
+    #[test]
+    fn element_kept_after_attribute_error() {
+        let output = Parser::new(Lexer::new("/a(href=) {Hello}").output()).output();
+
+        assert_eq!(output.len(), 2);
+        assert!(output[0].is_err());
+        match output[1] {
+            Ok(Html(ref element)) => assert_eq!(element.children(), &vec![Text("Hello".into())]),
+            ref token => panic!("Expected an element, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn each_loop() {
+        let tokens = parse("@each item in @list {/li{@item}}").unwrap();
+
+        match tokens[0] {
+            Loop { ref key, ref binding, ref collection, ref body, ref else_branch, .. } => {
+                assert_eq!(key, &None);
+                assert_eq!(binding, "item");
+                assert_eq!(collection, &Collection::Variable(String::from("list")));
+                assert_eq!(body.len(), 1);
+                assert_eq!(else_branch, &None);
+            }
+            ref token => panic!("Expected a loop, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn each_loop_key() {
+        match parse("@each name, value in @settings {@name}").unwrap()[0] {
+            Loop { ref key, ref binding, ref collection, .. } => {
+                assert_eq!(key, &Some(String::from("name")));
+                assert_eq!(binding, "value");
+                assert_eq!(collection, &Collection::Variable(String::from("settings")));
+            }
+            ref token => panic!("Expected a loop, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn each_loop_range() {
+        let range = |source: &str| {
+            match parse(source).unwrap()[0] {
+                Loop { ref collection, .. } => collection.clone(),
+                ref token => panic!("Expected a loop, got: {:?}", token),
+            }
+        };
+
+        assert_eq!(range("@each i in 1..5 {@i}"),
+                   Collection::Range(Expr::Literal(Value::U64(1)), Expr::Literal(Value::U64(5))));
+        assert_eq!(range("@each i in -2..@pages.count {@i}"),
+                   Collection::Range(Expr::Literal(Value::I64(-2)),
+                                     Expr::Variable(String::from("pages.count"))));
+        match parse("@each i in 1..five {@i}").unwrap_err()[0] {
+            ExpectedVariable(Word(_, ref word)) => assert_eq!(word.trim(), "five"),
+            ref error => panic!("Expected ExpectedVariable, got: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn each_loop_else() {
+        let tokens = parse("@each item in @list {/li{@item}} @else {/li{None}}").unwrap();
+
+        match tokens[0] {
+            Loop { else_branch: Some(ref else_branch), .. } => assert_eq!(else_branch.len(), 1),
+            ref token => panic!("Expected a loop with an else block, got: {:?}", token),
+        }
+        match parse("@each item in @list {A} @elseif b {B}").unwrap_err()[0] {
+            DanglingElse(_) => {}
+            ref error => panic!("Expected DanglingElse, got: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn switch() {
+        let source = r#"@switch @status { @case "active" {Active} @case 3 {Three} @default {?} }"#;
+        let tokens = parse(source).unwrap();
+
+        match tokens[0] {
+            Switch { ref subject, ref cases, ref default, .. } => {
+                assert_eq!(subject, "status");
+                assert_eq!(cases[0].0, Value::String(String::from("active")));
+                assert_eq!(cases[1].0, Value::U64(3));
+                assert_eq!(cases.len(), 2);
+                assert_eq!(default, &Some(vec![Text(String::from("?"))]));
+            }
+            ref token => panic!("Expected a switch, got: {:?}", token),
+        }
+    }
+
+    #[test]
+    fn case_outside_switch() {
+        match parse(r#"/p{@case "active" {Active}}"#).unwrap_err()[0] {
+            CaseOutsideSwitch(span) => assert_eq!(span.start.column, 4),
+            ref error => panic!("Expected CaseOutsideSwitch, got: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn nested_children() {
+        let tokens = parse("/div{/ul{/li{Hello}}}").unwrap();
+
+        let div = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        let ul = match div.children()[..] {
+            [Html(ref element)] => element,
+            ref children => panic!("Expected a single element, got: {:?}", children),
+        };
+        let li = match ul.children()[..] {
+            [Html(ref element)] => element,
+            ref children => panic!("Expected a single element, got: {:?}", children),
+        };
+        assert_eq!(li.children(), &vec![Text(String::from("Hello"))]);
+    }
+
+    #[test]
+    fn escaped_braces() {
+        let tokens = parse(r"/code { if (x) \{ y \} }").unwrap();
+
+        match tokens[0] {
+            Html(ref element) => {
+                let text: String = element.children()
+                                          .iter()
+                                          .map(|child| {
+                                              match *child {
+                                                  Text(ref text) => text.clone(),
+                                                  ref token => panic!("Expected text, got: {:?}",
+                                                                      token),
+                                              }
+                                          })
+                                          .collect();
+                assert_eq!(text, " if (x){ y }");
+                assert!(element.children().contains(&Text(String::from("{"))));
+                assert!(element.children().contains(&Text(String::from("}"))));
+            }
+            ref token => panic!("Expected an element, got: {:?}", token),
+        }
+        assert!(parse(r"/p{\\/b{Bold}}").is_ok());
+        assert!(parse(r"/p{\}").is_err());
+    }
+
+    #[test]
+    fn escape_table() {
+        let text = |text: &str| Text(String::from(text));
+
+        assert_eq!(parse(r"a\nb").unwrap(), vec![text("a"), text("\n"), text("b")]);
+        assert_eq!(parse(r"\\\tx").unwrap(), vec![text("\\"), text("\t"), text("x")]);
+        assert_eq!(parse(r"\Users").unwrap(), vec![text("\\"), text("Users")]);
+        assert_eq!(parse(r"a \").unwrap(), vec![text("a "), text("\\")]);
+    }
+
+    #[test]
+    fn unclosed_open_brace() {
+        let errors = parse("/div{/p{Hello}").unwrap_err();
+
+        assert_eq!(errors, vec![UnclosedOpenBraces(symbol_span(5))]);
+    }
+
+    #[test]
+    fn unclosed_nested_open_brace() {
+        let errors = parse("/div{/p{Hello}/p{World}").unwrap_err();
+
+        assert_eq!(errors, vec![UnclosedOpenBraces(symbol_span(5))]);
+    }
+
+    #[test]
+    fn unclosed_quote() {
+        let errors = parse(r#"/a(title="unterminated"#).unwrap_err();
+
+        assert_eq!(errors, vec![UnclosedQuote(symbol_span(10), Quote)]);
+    }
+
+    #[test]
+    fn unclosed_single_quote() {
+        let errors = parse("/a(title='unterminated\"").unwrap_err();
+
+        assert_eq!(errors, vec![UnclosedQuote(symbol_span(10), SingleQuote)]);
+    }
+
+    #[test]
+    fn string_across_lines() {
+        let errors = parse("/a(title=\"a\nb\")").unwrap_err();
+
+        assert_eq!(errors, vec![UnclosedQuote(symbol_span(10), Quote)]);
+    }
+
+    #[test]
+    fn unclosed_quote_in_condition() {
+        let errors = parse(r#"@if @name == "Polly {/p{Hi}}"#).unwrap_err();
+
+        assert_eq!(errors, vec![UnclosedQuote(symbol_span(14), Quote)]);
+    }
+
+    #[test]
+    fn unclosed_comment() {
+        let comment = Span::new(Position::new(1, 7, 6), Position::new(1, 9, 8));
+        assert_eq!(parse("/p{Hi}/* note").unwrap_err(), vec![UnclosedComment(comment)]);
+
+        let comment = Span::new(Position::new(1, 6, 5), Position::new(1, 8, 7));
+        assert_eq!(parse("/div{/*}").unwrap_err(),
+                   vec![UnclosedComment(comment), UnclosedOpenBraces(symbol_span(5))]);
+    }
+
+    #[test]
+    fn unclosed_close_brace() {
+        let errors = parse("/div{Hello}}").unwrap_err();
+
+        assert_eq!(errors, vec![UnclosedCloseBraces(symbol_span(12))]);
+    }
+
+    #[test]
+    fn bare_component_call() {
+        let tokens = parse("&foo bar").unwrap();
+
+        assert_eq!(tokens,
+                   vec![CompCall(ComponentCall::new(String::from("foo"))),
+                        Text(String::from("bar"))]);
+    }
+
+    #[test]
+    fn bare_component_call_in_element() {
+        let tokens = parse("/p{&foo bar}").unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        assert_eq!(element.children(),
+                   &vec![CompCall(ComponentCall::new(String::from("foo"))),
+                         Text(String::from("bar"))]);
+    }
+
+    #[test]
+    fn bare_component_call_at_eof() {
+        let tokens = parse("&foo").unwrap();
+
+        assert_eq!(tokens, vec![CompCall(ComponentCall::new(String::from("foo")))]);
+    }
+
+    #[test]
+    fn component_default_in_call() {
+        let errors = parse(r#"&card(@title = "None")"#).unwrap_err();
+
+        match errors[..] {
+            [UnexpectedToken(Symbol(_, Equals))] => {}
+            ref errors => panic!("Expected an unexpected '=', got: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn duplicate_parameter() {
+        let expected = Word(Span::new(Position::new(1, 16, 15), Position::new(1, 21, 20)),
+                            String::from("title"));
+        assert_eq!(parse("&card(@title, @title) {/h1{@title}}"),
+                   Err(vec![DuplicateArgument(expected)]));
+    }
+
+    #[test]
+    fn positional_after_named_argument() {
+        let errors = parse("&card(title = @title, @body)").unwrap_err();
+
+        match errors[..] {
+            [PositionalAfterNamed(Symbol(_, At))] => {}
+            ref errors => panic!("Expected a PositionalAfterNamed error, got: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn duplicate_named_argument() {
+        let errors = parse("/div&card(title = @title, title = @body)").unwrap_err();
+
+        match errors[..] {
+            [DuplicateArgument(Word(_, ref name))] => assert_eq!(name, "title"),
+            ref errors => panic!("Expected a DuplicateArgument error, got: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn interpolated_attribute() {
+        let tokens = parse(r#"/a(href="/users/@id/posts")"#).unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        let expected = AttributeValue::Interpolated(vec![Segment::Literal(String::from("/users/")),
+                                                         Segment::Variable(String::from("id")),
+                                                         Segment::Literal(String::from("/posts"))]);
+        assert_eq!(element.attribute("href"), Some(&Some(expected)));
+    }
+
+    #[test]
+    fn multiple_classes() {
+        let tokens = parse("/div.a.btn-primary.a.c").unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        assert_eq!(element.classes(), &["a", "btn-primary", "c"]);
+    }
+
+    #[test]
+    fn unquoted_variable_attribute() {
+        let tokens = parse("/a(data-id=@user.id)").unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        let name = Segment::Variable(String::from("user.id"));
+        let expected = AttributeValue::Interpolated(vec![name]);
+        assert_eq!(element.attribute("data-id"), Some(&Some(expected)));
+    }
+
+    #[test]
+    fn data_and_aria_attributes() {
+        let tokens = parse(r#"/button(data-user-id="@uid" type=button aria-label="Close")"#)
+                         .unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        let user_id = AttributeValue::Interpolated(vec![Segment::Variable(String::from("uid"))]);
+        let label = AttributeValue::Literal(String::from("Close"));
+        assert_eq!(element.data_attributes(),
+                   vec![&(String::from("data-user-id"), Some(user_id))]);
+        assert_eq!(element.aria_attributes(),
+                   vec![&(String::from("aria-label"), Some(label))]);
+    }
+
+    #[test]
+    fn conditional_attribute() {
+        let tokens = parse("/button(disabled ?= @form.locked type=submit)").unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        assert_eq!(element.attribute("disabled"), Some(&None));
+        assert_eq!(element.condition("disabled"),
+                   Some(&Expr::Variable(String::from("form.locked"))));
+        assert_eq!(element.condition("type"), None);
+        assert_eq!(element.to_string(),
+                   r#"/button(disabled ?= @form.locked type="submit"){}"#);
+    }
+
+    #[test]
+    fn chained_children() {
+        assert_eq!(parse("/ul > /li > /a(href=@url) { @title }").unwrap(),
+                   parse("/ul{/li{/a(href=@url) { @title }}}").unwrap());
+        assert_eq!(parse("/nav#main.menu > /ul.items > /li.item>{Home} and more").unwrap(),
+                   parse("/nav#main.menu{/ul.items{/li.item>{Home}}} and more").unwrap());
+        assert_eq!(parse("/p > /br").unwrap(), parse("/p{/br}").unwrap());
+        assert_eq!(parse(r#"/div(id="x") > /p(title="y") > /b{a}"#).unwrap(),
+                   parse(r#"/div(id="x"){/p(title="y"){/b{a}}}"#).unwrap());
+        assert_eq!(parse(r#"/b(id="x")> 3"#).unwrap()[1..],
+                   [Text(String::from(">")), Text(String::from(" 3"))]);
+        assert_eq!(parse("/a{A}/b> /i{I}").unwrap(), parse("/a{A}/b>{}/i{I}").unwrap());
+        assert_eq!(parse("/br> /img(src=x)").unwrap(), parse("/br>/img(src=x)").unwrap());
+    }
+
+    #[test]
+    fn trailing_commas() {
+        assert_eq!(parse("&card(@a, @b,)").unwrap(), parse("&card(@a, @b)").unwrap());
+        assert_eq!(parse("/div&card(@a, title=@t,)").unwrap(),
+                   parse("/div&card(@a, title=@t)").unwrap());
+        assert_eq!(parse("$f(a=@x, b=2,)").unwrap(), parse("$f(a=@x, b=2)").unwrap());
+        assert_eq!(parse("&card(@a,){/p{@a}}/div{&card(@b,)}").unwrap(),
+                   parse("&card(@a){/p{@a}}/div{&card(@b)}").unwrap());
+    }
+
+    #[test]
+    fn empty_arguments() {
+        assert_eq!(parse("&card()").unwrap(), parse("&card").unwrap());
+        assert_eq!(parse("/div&card( )").unwrap(), parse("/div&card").unwrap());
+        assert_eq!(parse("$f()").unwrap(), vec![Function(FunctionCall::new(String::from("f")))]);
+    }
+
+    #[test]
+    fn stray_commas() {
+        let stray = |source: &str, column: usize| {
+            assert_eq!(parse(source),
+                       Err(vec![UnexpectedToken(Symbol(symbol_span(column), Comma))]));
+        };
+
+        stray("&card(@a,, @b)", 10);
+        stray("&card(, @a)", 7);
+        stray("/div&card(@a,,@b)", 14);
+        stray("$f(a=@x,,b=@y)", 9);
+        stray("$f(,)", 4);
+    }
+
+    #[test]
+    fn unclosed_arguments() {
+        assert_eq!(parse("$f(a=@x"),
+                   Err(vec![UnexpectedEof(Symbol(symbol_span(3), OpenParam))]));
+    }
+
+    #[test]
+    fn quoted_text_child() {
+        assert_eq!(parse(r#"/p "Hello   @name!""#).unwrap(),
+                   parse("/p{Hello @name!}").unwrap());
+        assert_eq!(parse(r#"/a(href="/") "Home"/title "My site""#).unwrap(),
+                   parse(r#"/a(href="/"){Home}/title{My site}"#).unwrap());
+        assert_eq!(parse(r#"/ul > /li.item "One""#).unwrap(),
+                   parse("/ul{/li.item{One}}").unwrap());
+    }
+
+    #[test]
+    fn quoted_text_and_block() {
+        match parse(r#"/p "text" { more }"#) {
+            Err(ref errors) => {
+                assert_eq!(errors, &[UnexpectedToken(Symbol(symbol_span(11), OpenBrace))])
+            }
+            result => panic!("Expected UnexpectedToken, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn chained_child_of_void_element() {
+        match parse("/br > /b{Hi}") {
+            Err(ref errors) => {
+                assert_eq!(errors, &[VoidElementWithChildren(symbol_span(5), String::from("br"))])
+            }
+            result => panic!("Expected VoidElementWithChildren, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn classes_keep_their_position() {
+        for source in &["/a.link#home{Home}",
+                        "/a#home.link{Home}",
+                        r#"/input(type="text" class="wide" name="q")"#] {
+            let tokens = parse(source).unwrap();
+            let element = match tokens[0] {
+                Html(ref element) => element,
+                ref token => panic!("Expected an element, got: {:?}", token),
+            };
+            assert_eq!(parse(&*element.to_string()).unwrap(), tokens);
+        }
+
+        let tokens = parse("/a#home.link{Home}").unwrap();
+        assert_eq!(tokens[0].to_string(), "/a#home.link{Home}");
+    }
+
+    #[test]
+    fn attribute_spread() {
+        let tokens = parse(r#"/a(href="/" **@link.attributes){Home}"#).unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        assert_eq!(element.spreads(), &["link.attributes"]);
+        assert_eq!(element.to_string(), r#"/a(href="/" **@link.attributes){Home}"#);
+    }
+
+    #[test]
+    fn function_literal_arguments() {
+        let source = r#"$calc(base=10, ratio=1.5, offset=-2, enabled=true, label="hi",
+                        name=@name)"#;
+        let tokens = parse(source).unwrap();
+
+        let call = match tokens[0] {
+            Function(ref call) => call,
+            ref token => panic!("Expected a function call, got: {:?}", token),
+        };
+        let literal = |key: &str| {
+            match call.args().get(key) {
+                Some(&ArgKey::Literal(ref value)) => value.clone(),
+                arg => panic!("Expected a literal, got: {:?}", arg),
+            }
+        };
+        assert_eq!(literal("base"), Value::U64(10));
+        assert_eq!(literal("ratio"), Value::F64(1.5));
+        assert_eq!(literal("offset"), Value::I64(-2));
+        assert_eq!(literal("enabled"), Value::Bool(true));
+        assert_eq!(literal("label"), Value::String(String::from("hi")));
+        assert_eq!(call.args().get("name"), Some(&ArgKey::Json(String::from("name"))));
+        assert_eq!(call.to_string(),
+                   r#"$calc(base=10, enabled=true, label="hi", name=@name, offset=-2, ratio=1.5)"#);
+    }
+
+    #[test]
+    fn function_number_arguments() {
+        let tokens = parse("$calc(half=.5, below = -.5, count=-3, total=20)").unwrap();
+
+        let call = match tokens[0] {
+            Function(ref call) => call,
+            ref token => panic!("Expected a function call, got: {:?}", token),
+        };
+        let literal = |key: &str| {
+            match call.args().get(key) {
+                Some(&ArgKey::Literal(ref value)) => value.clone(),
+                arg => panic!("Expected a literal, got: {:?}", arg),
+            }
+        };
+        assert_eq!(literal("half"), Value::F64(0.5));
+        assert_eq!(literal("below"), Value::F64(-0.5));
+        assert_eq!(literal("count"), Value::I64(-3));
+        assert_eq!(literal("total"), Value::U64(20));
+    }
+
+    #[test]
+    fn function_invalid_literal() {
+        match parse("$calc(base=ten)") {
+            Err(ref errors) => {
+                match errors[0] {
+                    UnexpectedToken(Word(_, ref word)) => assert_eq!(word, "ten"),
+                    ref error => panic!("Expected an unexpected token, got: {:?}", error),
+                }
+            }
+            result => panic!("Expected an unexpected token, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn conditional_attribute_without_variable() {
+        match parse("/button(disabled ?= locked)") {
+            Err(ref errors) => {
+                match errors[0] {
+                    InvalidTokenInAttributes(Word(_, ref word)) => assert_eq!(word, " locked"),
+                    ref error => panic!("Expected an invalid token, got: {:?}", error),
+                }
+            }
+            result => panic!("Expected an invalid token, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn implicit_div() {
+        let tokens = parse("#main.wide {Hello. World}").unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        assert_eq!(element.tag(), "div");
+        assert_eq!(element.classes(), &["wide"]);
+        assert_eq!(element.children(),
+                   &vec![Text(String::from("Hello")), Text(String::from(".")),
+                         Text(String::from(" World"))]);
+    }
+
+    #[test]
+    fn dot_within_text() {
+        let tokens = parse("Hello .world").unwrap();
+
+        assert_eq!(tokens,
+                   vec![Text(String::from("Hello ")), Text(String::from(".")),
+                        Text(String::from("world"))]);
+    }
+
+    #[test]
+    fn classes_around_id() {
+        let tokens = parse("/div.card.col-6#main.shadow.large").unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        assert_eq!(element.classes(), &["card", "col-6", "shadow", "large"]);
+        assert_eq!(element.attribute("id"),
+                   Some(&Some(AttributeValue::Literal(String::from("main")))));
+    }
+
+    #[test]
+    fn component_call_with_children() {
+        let tokens = parse("/div{&panel(@title) {/p{Body}}}").unwrap();
+
+        let element = match tokens[0] {
+            Html(ref element) => element,
+            ref token => panic!("Expected an element, got: {:?}", token),
+        };
+        match element.children()[..] {
+            [CompCall(ref call)] => {
+                assert_eq!(call.name(), "panel");
+                assert_eq!(call.children().len(), 1);
+            }
+            ref children => panic!("Expected a component call, got: {:?}", children),
+        }
+    }
+
+    #[test]
+    fn element_span() {
+        let tokens = parse("Hi /p.intro{ @name }!").unwrap();
+
+        assert_eq!(tokens[1].span(),
+                   Some(Span::new(Position::new(1, 4, 3), Position::new(1, 21, 20))));
+        assert_eq!(tokens[0].span(), None);
+    }
+
+    #[test]
+    fn element_span_over_lines() {
+        let tokens = parse("/ul{\n  /li{ a }\n}").unwrap();
+
+        assert_eq!(tokens[0].span(),
+                   Some(Span::new(Position::new(1, 1, 0), Position::new(3, 2, 17))));
+        match tokens[0] {
+            Html(ref element) => {
+                assert_eq!(element.children()[0].span(),
+                           Some(Span::new(Position::new(2, 3, 7), Position::new(2, 11, 15))));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn variable_span() {
+        let tokens = parse("Hello @user.name|upper world").unwrap();
+
+        assert_eq!(tokens[1].span(),
+                   Some(Span::new(Position::new(1, 7, 6), Position::new(1, 23, 22))));
+    }
+
+    #[test]
+    fn component_call_span() {
+        let tokens = parse("/div{&card(@user)}").unwrap();
+
+        match tokens[0] {
+            Html(ref element) => {
+                assert_eq!(element.children()[0].span(),
+                           Some(Span::new(Position::new(1, 6, 5), Position::new(1, 18, 17))));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn block_span() {
+        let tokens = parse("@each item in @items {@item}").unwrap();
+        assert_eq!(tokens[0].span(),
+                   Some(Span::new(Position::new(1, 1, 0), Position::new(1, 29, 28))));
+
+        let tokens = parse("/div{@if a {A} @else {B}}").unwrap();
+        match tokens[0] {
+            Html(ref element) => {
+                let span = element.children()[0].span().unwrap();
+                assert_eq!(span.start, Position::new(1, 6, 5));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn spans_are_ignored_when_comparing() {
+        assert_eq!(parse("@name").unwrap(),
+                   vec![Variable(String::from("name"), Vec::new(), SourceSpan::default())]);
+    }
+
+    #[test]
+    fn variable_followed_by_whitespace() {
+        let tokens = parse("@name world").unwrap();
+
+        assert_eq!(tokens,
+                   vec![Variable(String::from("name"), Vec::new(), SourceSpan::default()),
+                        Text(String::from(" ")),
+                        Text(String::from("world"))]);
+    }
+
+    #[test]
+    fn collapses_whitespace() {
+        let tokens = parse("Hello   big\t\tworld\n\n  again @name \t\n").unwrap();
+
+        assert_eq!(tokens,
+                   vec![Text(String::from("Hello big world again ")),
+                        Variable(String::from("name"), Vec::new(), SourceSpan::default()),
+                        Text(String::from(" "))]);
+    }
+
+    #[test]
+    fn preserves_whitespace_in_pre() {
+        let tokens = parse("/pre{  a\n\t b  /span{ c   d }}  e   f").unwrap();
+
+        match tokens[0] {
+            Html(ref pre) => {
+                assert_eq!(pre.children()[0], Text(String::from("  a\n\t b  ")));
+                match pre.children()[1] {
+                    Html(ref span) => {
+                        assert_eq!(span.children(), &vec![Text(String::from(" c   d "))])
+                    }
+                    ref token => panic!("Expected a span, got: {:?}", token),
+                }
+            }
+            ref token => panic!("Expected a pre, got: {:?}", token),
+        }
+        assert_eq!(tokens[1], Text(String::from(" e f")));
+    }
+
+    /// Writes the tokens back out as source.
+    fn to_source(tokens: &[Token]) -> String {
+        tokens.iter().map(|token| token.to_string()).collect()
+    }
+
+    #[test]
+    fn display_element() {
+        let tokens = parse(r#"/p.a#b(c=d class="e f"){Hi @name}"#).unwrap();
+
+        assert_eq!(to_source(&tokens), r#"/p.a.e.f#b(c="d"){Hi @name}"#);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let source = r#"/!doctype html
+        /html {
+            /body.page#top(data-id="@id" lang=en "quoted" disabled) {
+                /p.intro.lead*{Hello, @name|upper|trim!}
+                /p{It's 'quoted'='b'}
+                /b<>{1 < 2} > 1
+                /script#main.{ if (a < b) { go(); } }
+                @block content {/p {Default}}
+                @!raw
+                /!{A comment}
+                /ul {
+                    @each item in @items {
+                        /li&card(@item, "a \"quote\" at \@", title = @title){ Card}
+                    }
+                }
+                @each item in @empty {Item} @else {Empty}
+                @each key, value in @settings {@key=@value}
+                @each page in 1..@pages {@page}
+                @if !@banned && (@role == "admin" || @count >= 3) {Admin}
+                @elseif @count < -1.5 {Negative}
+                @else {User}
+                @unless @a || @b {Neither}
+                @switch @status {@case "active" {Active} @case 0 {Banned} @default {Unknown}}
+                $format(value = @price, fallback = &empty) {Children}
+                \{escaped\}
+            }
+        }"#;
+        let tokens = parse(source).unwrap();
+        let formatted = to_source(&tokens);
+        let reparsed = parse(&formatted).unwrap();
+
+        assert_eq!(reparsed, tokens);
+        assert_eq!(to_source(&reparsed), formatted);
+    }
+
+    #[test]
+    fn display_expression_precedence() {
+        let conditions = ["@a == 2.0",
+                          "!(@a && @b)",
+                          "@a && (@b || @c)",
+                          "(@a || @b) && @c",
+                          "@a || @b && @c"];
+        for condition in &conditions {
+            let source = format!("@if {} {{}}", condition);
+            let tokens = parse(&source).unwrap();
+
+            assert_eq!(to_source(&tokens), source);
+        }
+    }
+
+    #[test]
+    fn display_component_definition() {
+        let source = r#"&card(@title, @size="big \"one\""){/p{@title}}"#;
+        let components = Parser::component_pass(Lexer::tokenize(source));
+        let formatted = components["card"].to_string();
+        let reparsed = Parser::component_pass(Lexer::tokenize(&formatted));
+
+        assert_eq!(formatted, source);
+        assert_eq!(reparsed, components);
     }
 }
@@ -4,6 +4,8 @@ use super::Component;
 pub enum ArgKey {
     Json(String),
     Comp(String),
+    /// A quoted string, number, `true`, `false`, or `null`, passed as is.
+    Literal(Value),
 }
 
 impl ArgKey {
@@ -11,6 +13,13 @@ impl ArgKey {
         use self::ArgKey::*;
         match *self {
             Json(ref string) | Comp(ref string) => string.clone(),
+            Literal(Value::String(ref string)) => string.clone(),
+            Literal(ref value) => {
+                match ::serde_json::to_string(value) {
+                    Ok(text) => text,
+                    Err(_) => String::new(),
+                }
+            }
         }
     }
 }
@@ -22,4 +31,6 @@ pub enum ArgValue {
     Json(Option<Value>),
     /// Component passed into the Function, or Component.
     Comp(Option<Component>),
+    /// The rendered HTML of the block following a Function call.
+    Children(String),
 }
@@ -1,8 +1,15 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::error;
+use std::path::PathBuf;
 
-use super::{ComponentCall, Element, FunctionCall, Lexeme};
+use serde_json::Value;
+
+use super::{ComponentCall, Element, Expr, FunctionCall, Lexeme, Operator, Position, SourceSpan,
+            Span};
+use super::expression::format_value;
+use super::consts::{BACKSLASH, SYMBOLS};
+use super::Operator::{CloseBrace, OpenBrace};
 use self::AstError::*;
 
 /// TODO
@@ -12,12 +19,212 @@ pub enum Token {
     Html(Element),
     /// TODO
     Text(String),
-    /// TODO
-    Variable(String),
+    /// The contents of a raw block, written as `/tag.{ }`, which are rendered as is, without
+    /// being parsed, or escaped.
+    Raw(String),
+    /// A variable, the filters it's passed through, and where it was parsed from, which is
+    /// escaped when rendered.
+    Variable(String, Vec<String>, SourceSpan),
+    /// A variable written as `@!name`, which is rendered as is, without being escaped.
+    RawVariable(String, Vec<String>, SourceSpan),
     /// TODO
     CompCall(ComponentCall),
     /// TODO
     Function(FunctionCall),
+    /// Renders `then_branch` if `condition` is truthy, otherwise the first of `else_ifs` whose
+    /// condition is truthy, otherwise renders `else_branch` if there is one.
+    Conditional {
+        /// The expression being checked.
+        condition: Expr,
+        /// The children rendered when the condition is true.
+        then_branch: Vec<Token>,
+        /// The condition, and children of each `@elseif`, in order.
+        else_ifs: Vec<(Expr, Vec<Token>)>,
+        /// The children rendered when the condition is false.
+        else_branch: Option<Vec<Token>>,
+        /// Where the `@if` was parsed from.
+        span: SourceSpan,
+    },
+    /// Renders `body` for each item in `collection`, with the item bound to `binding`, otherwise
+    /// renders `else_branch` if there are no items.
+    Loop {
+        /// The name each item's index, or key is bound to within the body, if it has one.
+        key: Option<String>,
+        /// The name each item is bound to within the body.
+        binding: String,
+        /// What's being iterated over.
+        collection: Collection,
+        /// The children rendered for each item.
+        body: Vec<Token>,
+        /// The children rendered when there are no items, or the collection is null.
+        else_branch: Option<Vec<Token>>,
+        /// Where the `@each` was parsed from.
+        span: SourceSpan,
+    },
+    /// Renders the children of the first case equal to the variable named by `subject`,
+    /// otherwise renders `default` if there is one.
+    Switch {
+        /// The name of the variable being compared.
+        subject: String,
+        /// The value of each case, and the children rendered when it matches.
+        cases: Vec<(Value, Vec<Token>)>,
+        /// The children rendered when no case matches.
+        default: Option<Vec<Token>>,
+        /// Where the `@switch` was parsed from.
+        span: SourceSpan,
+    },
+    /// A HTML comment, which is kept in the output as `<!-- -->`.
+    Comment(Vec<Token>),
+    /// A document type declaration, written as is within `<!DOCTYPE >`.
+    Doctype(String),
+    /// A named part of a base template, which a template extending it can replace.
+    Block {
+        /// The name a replacement is given with.
+        name: String,
+        /// The children rendered in place of the block.
+        body: Vec<Token>,
+    },
+}
+
+/// What a loop iterates over.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Collection {
+    /// The name of an array, or object.
+    Variable(String),
+    /// The integers from `start` up to, and including `end`, each of which is a literal, or a
+    /// variable.
+    Range(Expr, Expr),
+}
+
+impl Token {
+    /// Where the token was parsed from, if it was parsed, and is an element, a call, a
+    /// variable, a conditional, a loop, or a switch.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            Token::Html(ref element) => element.span(),
+            Token::CompCall(ref call) => call.span(),
+            Token::Function(ref call) => call.span(),
+            Token::Variable(_, _, SourceSpan(span)) |
+            Token::RawVariable(_, _, SourceSpan(span)) |
+            Token::Conditional { span: SourceSpan(span), .. } |
+            Token::Loop { span: SourceSpan(span), .. } |
+            Token::Switch { span: SourceSpan(span), .. } => span,
+            _ => None,
+        }
+    }
+
+    /// Sets where the token was parsed from, if it's an element, a call, a variable, a
+    /// conditional, a loop, or a switch.
+    pub fn set_span(&mut self, span: Span) {
+        match *self {
+            Token::Html(ref mut element) => element.set_span(span),
+            Token::CompCall(ref mut call) => call.set_span(span),
+            Token::Function(ref mut call) => call.set_span(span),
+            Token::Variable(_, _, ref mut source) |
+            Token::RawVariable(_, _, ref mut source) |
+            Token::Conditional { span: ref mut source, .. } |
+            Token::Loop { span: ref mut source, .. } |
+            Token::Switch { span: ref mut source, .. } => *source = SourceSpan(Some(span)),
+            _ => {}
+        }
+    }
+}
+
+impl Display for Collection {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Collection::Variable(ref name) => write!(f, "@{}", name),
+            Collection::Range(ref start, ref end) => write!(f, "{}..{}", start, end),
+        }
+    }
+}
+
+impl Display for Token {
+    /// Writes the token as template source, which parses back into the same token. No
+    /// whitespace is added, as whitespace within text is kept when parsing.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::Token::*;
+        match *self {
+            Html(ref element) => write!(f, "{}", element),
+            Text(ref text) => {
+                let mut escaped = String::new();
+                for character in text.chars() {
+                    if SYMBOLS.contains(&character) {
+                        escaped.push(BACKSLASH);
+                    }
+                    escaped.push(character);
+                }
+                write!(f, "{}", escaped)
+            }
+            Raw(ref text) => write!(f, "{}", text),
+            Variable(ref name, ref filters, _) => write!(f, "@{}{}", name, format_filters(filters)),
+            RawVariable(ref name, ref filters, _) => {
+                write!(f, "@!{}{}", name, format_filters(filters))
+            }
+            CompCall(ref call) => write!(f, "{}", call),
+            Function(ref call) => write!(f, "{}", call),
+            Conditional { ref condition, ref then_branch, ref else_ifs, ref else_branch, .. } => {
+                let mut source = format!("@if {} {}", condition, format_block(then_branch));
+                for &(ref condition, ref children) in else_ifs {
+                    source.push_str(&*format!("@elseif {} {}", condition, format_block(children)));
+                }
+                if let Some(ref else_branch) = *else_branch {
+                    source.push_str(&*format!("@else {}", format_block(else_branch)));
+                }
+                write!(f, "{}", source)
+            }
+            Loop { ref key, ref binding, ref collection, ref body, ref else_branch, .. } => {
+                let binding = match *key {
+                    Some(ref key) => format!("{}, {}", key, binding),
+                    None => binding.clone(),
+                };
+                let mut source = format!("@each {} in {} {}",
+                                         binding,
+                                         collection,
+                                         format_block(body));
+                if let Some(ref else_branch) = *else_branch {
+                    source.push_str(&*format!("@else {}", format_block(else_branch)));
+                }
+                write!(f, "{}", source)
+            }
+            Switch { ref subject, ref cases, ref default, .. } => {
+                let mut source = format!("@switch @{} {{", subject);
+                for &(ref value, ref children) in cases {
+                    source.push_str(&*format!("@case {} {}",
+                                              format_value(value),
+                                              format_block(children)));
+                }
+                if let Some(ref default) = *default {
+                    source.push_str(&*format!("@default {}", format_block(default)));
+                }
+                write!(f, "{}}}", source)
+            }
+            Comment(ref children) => write!(f, "/!{}", format_block(children)),
+            // The doctype is the rest of the line, so the line has to end after it.
+            Doctype(ref doctype) => write!(f, "/!doctype {}\n", doctype),
+            Block { ref name, ref body } => write!(f, "@block {} {}", name, format_block(body)),
+        }
+    }
+}
+
+/// Writes the tokens within braces, as the children of a block.
+pub fn format_block(tokens: &[Token]) -> String {
+    let mut source = String::from("{");
+    for token in tokens {
+        source.push_str(&*token.to_string());
+    }
+    source.push('}');
+    source
+}
+
+/// Writes the filters a variable is passed through, such as `|upper|trim`.
+fn format_filters(filters: &[String]) -> String {
+    let mut source = String::new();
+    for filter in filters {
+        source.push('|');
+        source.push_str(&*filter);
+    }
+    source
 }
 
 /// Errors defining all the errors that can be encountered while parsing.
@@ -25,6 +232,19 @@ pub enum Token {
 pub enum AstError {
     /// End of File
     Eof,
+    /// A @case, or @default outside of a @switch block.
+    CaseOutsideSwitch(Span),
+    /// An @else without an @if, or @each directly before it, or an @elseif without an @if.
+    DanglingElse(Span),
+    /// The same named argument was passed twice to a component.
+    DuplicateArgument(Lexeme),
+    /// An import added a component with a name that's already defined.
+    DuplicateComponent(Span, String),
+    /// An element was given the same attribute twice, other than `class`, as in
+    /// `/a(href="/" href="/home")`.
+    DuplicateAttribute(Lexeme),
+    /// An element was given a second id with the `#` shorthand, as in `/div#a#b`.
+    DuplicateId(Lexeme),
     /// Expected a Component name.
     ExpectedCompCall(Lexeme),
     /// Expected a Variable name.
@@ -35,27 +255,63 @@ pub enum AstError {
     InvalidElement(Lexeme),
     /// No name attached to function.
     InvalidFunctionCall(Lexeme),
+    /// An `$import`, or `@include` in a template that has no directory to resolve the path
+    /// against.
+    ImportWithoutDirectory(Span),
+    /// An `@include` of a file that's already being included, which would never end.
+    IncludeCycle(Span, PathBuf),
+    /// The condition of an @if isn't a valid expression.
+    InvalidExpression(Span),
     /// Token that isn't (, ), =, ", ', or a word. 
     InvalidTokenInAttributes(Lexeme),
+    /// The file passed to `$import`, or `@include` couldn't be read.
+    MissingImport(Span, String),
+    /// A `/!doctype` after an element, or within one, rather than at the start of the template.
+    MisplacedDoctype(Span),
     /// Having a . without anything following it up.
     NoNameAttachedToClass(Lexeme),
     /// Having a # without anything following it up.
     NoNameAttachedToId(Lexeme),
+    /// A positional argument after a named argument.
+    PositionalAfterNamed(Lexeme),
     /// Extra } braces
-    UnclosedCloseBraces(usize),
+    UnclosedCloseBraces(Span),
+    /// A `/*` without a matching `*/`, before the end of the source.
+    UnclosedComment(Span),
     /// Extra { braces
-    UnclosedOpenBraces(usize),
+    UnclosedOpenBraces(Span),
+    /// A quote without a matching closing quote, before the end of the source, and which quote
+    /// it was.
+    UnclosedQuote(Span, Operator),
     /// File ended while we tried to parse element.
     UnexpectedEof(Lexeme),
     /// Unknown token
     UnexpectedToken(Lexeme),
+    /// A template replaces a block that the template it extends doesn't have.
+    UnknownBlock(Span, String),
+    /// The file passed to `$import`, `@include`, or `@extends` exists, but couldn't be read,
+    /// along with why.
+    UnreadableFile(Span, PathBuf, String),
+    /// A void element, such as `img`, or `br` followed by a block of children.
+    VoidElementWithChildren(Span, String),
 }
 
 impl AstError {
-    /// The length of a token, for error printing.
+    /// The byte offset, and length of a token, for error printing.
     pub fn values(&self) -> (usize, usize) {
+        match self.span() {
+            Some(span) => (span.start.offset, span.len()),
+            None => (0, 0),
+        }
+    }
+
+    /// The region of the source where the error occurred, if the error has one.
+    pub fn span(&self) -> Option<Span> {
         match *self {
-            Eof => (0, 0),
+            Eof => None,
+            DuplicateArgument(ref lexeme) |
+            DuplicateAttribute(ref lexeme) |
+            DuplicateId(ref lexeme) |
             ExpectedCompCall(ref lexeme) |
             ExpectedVariable(ref lexeme) |
             InvalidComponent(ref lexeme) |
@@ -64,9 +320,32 @@ impl AstError {
             InvalidTokenInAttributes(ref lexeme) |
             NoNameAttachedToClass(ref lexeme) |
             NoNameAttachedToId(ref lexeme) |
+            PositionalAfterNamed(ref lexeme) |
             UnexpectedEof(ref lexeme) |
-            UnexpectedToken(ref lexeme) => (lexeme.index(), lexeme.length()),
-            UnclosedCloseBraces(index) | UnclosedOpenBraces(index) => (index, 1),
+            UnexpectedToken(ref lexeme) => Some(lexeme.span()),
+            CaseOutsideSwitch(span) |
+            DanglingElse(span) |
+            DuplicateComponent(span, _) |
+            ImportWithoutDirectory(span) |
+            IncludeCycle(span, _) |
+            InvalidExpression(span) |
+            MissingImport(span, _) |
+            MisplacedDoctype(span) |
+            UnclosedCloseBraces(span) |
+            UnclosedComment(span) |
+            UnclosedOpenBraces(span) |
+            UnclosedQuote(span, _) |
+            UnknownBlock(span, _) |
+            UnreadableFile(span, _, _) |
+            VoidElementWithChildren(span, _) => Some(span),
+        }
+    }
+
+    /// The position in the source where the error occurred.
+    pub fn position(&self) -> Position {
+        match self.span() {
+            Some(span) => span.start,
+            None => Position::default(),
         }
     }
 }
@@ -75,20 +354,45 @@ impl error::Error for AstError {
     fn description(&self) -> &str {
         match *self {
             Eof => "The file ended normally.",
-            ExpectedCompCall(_) | InvalidComponent(_) => "Component names can only be words.",
-            ExpectedVariable(_) => "Variable names can only be words.",
-            InvalidElement(_) => "Element names can only be words.",
-            InvalidFunctionCall(_) => "Function names can only be words.",
+            CaseOutsideSwitch(_) => "Found a @case, or @default outside of a @switch.",
+            DanglingElse(_) => "Found an @else, or @elseif without an @if before it.",
+            DuplicateArgument(_) => "The same argument was passed more than once.",
+            DuplicateComponent(_, _) => "A component with the same name is already defined.",
+            DuplicateAttribute(_) => "An element can only have each attribute once.",
+            DuplicateId(_) => "An element can only have one id.",
+            ExpectedCompCall(_) => "Expected a component call after '&'.",
+            ExpectedVariable(_) => "Expected a variable name after '@'.",
+            ImportWithoutDirectory(_) => {
+                "Can't import, or include a file in a template that wasn't loaded from a directory."
+            }
+            IncludeCycle(_, _) => "The file includes itself, or a file that includes it.",
+            InvalidComponent(_) => "Expected a component name after '&'.",
+            InvalidElement(_) => "Expected an element name after '/'.",
+            InvalidFunctionCall(_) => {
+                "Expected a function name after '$', followed by named arguments."
+            }
+            InvalidExpression(_) => {
+                "Expected a condition, such as 'active', '@count > 3', or '@role == \"admin\" && \
+                 !@banned'."
+            }
             InvalidTokenInAttributes(_) => {
-                "Attributes fields only accept words as single value, or as key-value word pairs, \
-                 or a \") which ends the attributes.\""
-            }
-            NoNameAttachedToClass(_) => "Class names can only be words.",
-            NoNameAttachedToId(_) => "Id names can only be words.",
-            UnclosedCloseBraces(_) => "You have an extra closing brace.",
-            UnclosedOpenBraces(_) => "You have an extra open brace.",
-            UnexpectedEof(_) => "File ended before an element is finished being parsed",
-            UnexpectedToken(_) => "Unknown token in use.",
+                "Attributes only accept single words, key-value pairs, or a ')' which ends the \
+                 attributes."
+            }
+            MissingImport(_, _) => "Couldn't read the imported, or included file.",
+            MisplacedDoctype(_) => "The doctype has to come before the first element.",
+            NoNameAttachedToClass(_) => "Expected a class name after '.'.",
+            NoNameAttachedToId(_) => "Expected an id after '#'.",
+            PositionalAfterNamed(_) => "Positional arguments have to come before named arguments.",
+            UnclosedCloseBraces(_) => "Found a closing brace without a matching open brace.",
+            UnclosedComment(_) => "Found a '/*' without a matching '*/'.",
+            UnclosedOpenBraces(_) => "Found an open brace without a matching closing brace.",
+            UnclosedQuote(..) => "Found a quote without a matching closing quote.",
+            UnexpectedEof(_) => "The file ended unexpectedly.",
+            UnexpectedToken(_) => "Unexpected token.",
+            UnknownBlock(_, _) => "The extended template has no block with the name.",
+            UnreadableFile(_, _, _) => "The imported, or included file couldn't be read.",
+            VoidElementWithChildren(_, _) => "Void elements can't have any children.",
         }
     }
 }
@@ -99,6 +403,15 @@ impl Display for AstError {
         use std::error::Error;
         let lexeme = match *self {
             Eof => return write!(f, "{}", self.description()),
+            UnexpectedEof(ref lexeme) => {
+                return write!(f,
+                              "Unexpected end of file after {}, at {}.",
+                              lexeme,
+                              lexeme.position())
+            }
+            DuplicateArgument(ref lexeme) |
+            DuplicateAttribute(ref lexeme) |
+            DuplicateId(ref lexeme) |
             ExpectedCompCall(ref lexeme) |
             ExpectedVariable(ref lexeme) |
             InvalidComponent(ref lexeme) |
@@ -107,13 +420,282 @@ impl Display for AstError {
             InvalidTokenInAttributes(ref lexeme) |
             NoNameAttachedToClass(ref lexeme) |
             NoNameAttachedToId(ref lexeme) |
-            UnexpectedEof(ref lexeme) |
+            PositionalAfterNamed(ref lexeme) |
             UnexpectedToken(ref lexeme) => lexeme,
-            UnclosedCloseBraces(_) | UnclosedOpenBraces(_) => {
-                return write!(f, "{}", self.description())
+            CaseOutsideSwitch(span) |
+            DanglingElse(span) |
+            ImportWithoutDirectory(span) |
+            InvalidExpression(span) |
+            MisplacedDoctype(span) => {
+                return write!(f, "{} Found at {}.", self.description(), span)
+            }
+            DuplicateComponent(span, ref name) |
+            MissingImport(span, ref name) |
+            UnknownBlock(span, ref name) |
+            VoidElementWithChildren(span, ref name) => {
+                return write!(f, "{} Got '{}', at {}.", self.description(), name, span)
+            }
+            IncludeCycle(span, ref path) => {
+                return write!(f,
+                              "{} Got '{}', at {}.",
+                              self.description(),
+                              path.display(),
+                              span)
+            }
+            UnclosedCloseBraces(span) => {
+                return write!(f,
+                              "'{}' without a matching '{}', at {}.",
+                              CloseBrace,
+                              OpenBrace,
+                              span)
+            }
+            UnclosedComment(span) => return write!(f, "'/*' without a matching '*/', at {}.", span),
+            UnclosedOpenBraces(span) => {
+                return write!(f,
+                              "'{}' without a matching '{}', at {}.",
+                              OpenBrace,
+                              CloseBrace,
+                              span)
+            }
+            UnclosedQuote(span, quote) => {
+                return write!(f, "'{}' without a matching '{}', at {}.", quote, quote, span)
+            }
+            UnreadableFile(span, ref path, ref error) => {
+                return write!(f,
+                              "{} Got '{}': {}, at {}.",
+                              self.description(),
+                              path.display(),
+                              error,
+                              span)
             }
-
         };
-        write!(f, "{}, Got: {}", self.description(), lexeme)
+        write!(f, "{} Got {}, at {}.", self.description(), lexeme, lexeme.position())
+    }
+}
+
+#[allow(dead_code, unused_imports)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::AstError::*;
+    use compiler::tokens::{Lexeme, Position, Span};
+    use compiler::tokens::Lexeme::{Symbol, Word};
+    use compiler::tokens::Operator::*;
+
+    fn span(line: usize, column: usize, offset: usize, len: usize) -> Span {
+        Span::new(Position::new(line, column, offset),
+                  Position::new(line, column + len, offset + len))
+    }
+
+    fn word(text: &str) -> Lexeme {
+        Word(span(2, 5, 12, text.trim().len()), text.to_owned())
+    }
+
+    fn symbol() -> Lexeme {
+        Symbol(span(2, 5, 12, 1), OpenBrace)
+    }
+
+    #[test]
+    fn eof() {
+        assert_eq!(Eof.to_string(), "The file ended normally.");
+    }
+
+    #[test]
+    fn case_outside_switch() {
+        assert_eq!(CaseOutsideSwitch(span(1, 1, 0, 1)).to_string(),
+                   "Found a @case, or @default outside of a @switch. Found at line 1, column 1.");
+    }
+
+    #[test]
+    fn dangling_else() {
+        assert_eq!(DanglingElse(span(3, 1, 20, 1)).to_string(),
+                   "Found an @else, or @elseif without an @if before it. Found at line 3, column \
+                    1.");
+    }
+
+    #[test]
+    fn duplicate_argument() {
+        assert_eq!(DuplicateArgument(word("title ")).to_string(),
+                   "The same argument was passed more than once. Got 'title', at line 2, column \
+                    5.");
+    }
+
+    #[test]
+    fn duplicate_component() {
+        assert_eq!(DuplicateComponent(span(1, 1, 0, 7), String::from("ui.card")).to_string(),
+                   "A component with the same name is already defined. Got 'ui.card', at line \
+                    1, column 1.");
+    }
+
+    #[test]
+    fn duplicate_attribute() {
+        assert_eq!(DuplicateAttribute(word("href")).to_string(),
+                   "An element can only have each attribute once. Got 'href', at line 2, column \
+                    5.");
+    }
+
+    #[test]
+    fn duplicate_id() {
+        assert_eq!(DuplicateId(word("b")).to_string(),
+                   "An element can only have one id. Got 'b', at line 2, column 5.");
+    }
+
+    #[test]
+    fn expected_comp_call() {
+        assert_eq!(ExpectedCompCall(symbol()).to_string(),
+                   "Expected a component call after '&'. Got '{', at line 2, column 5.");
+    }
+
+    #[test]
+    fn expected_variable() {
+        assert_eq!(ExpectedVariable(symbol()).to_string(),
+                   "Expected a variable name after '@'. Got '{', at line 2, column 5.");
+    }
+
+    #[test]
+    fn import_without_directory() {
+        assert_eq!(ImportWithoutDirectory(span(1, 1, 0, 1)).to_string(),
+                   "Can't import, or include a file in a template that wasn't loaded from a \
+                    directory. Found at line 1, column 1.");
+    }
+
+    #[test]
+    fn unknown_block() {
+        assert_eq!(UnknownBlock(span(1, 1, 0, 7), String::from("sidebar")).to_string(),
+                   "The extended template has no block with the name. Got 'sidebar', at line 1, \
+                    column 1.");
+    }
+
+    #[test]
+    fn unreadable_file() {
+        let error = UnreadableFile(span(1, 1, 0, 8),
+                                   PathBuf::from("partials"),
+                                   String::from("Is a directory (os error 21)"));
+        assert_eq!(error.to_string(),
+                   "The imported, or included file couldn't be read. Got 'partials': Is a \
+                    directory (os error 21), at line 1, column 1.");
+    }
+
+    #[test]
+    fn void_element_with_children() {
+        assert_eq!(VoidElementWithChildren(span(1, 4, 3, 1), String::from("br")).to_string(),
+                   "Void elements can't have any children. Got 'br', at line 1, column 4.");
+    }
+
+    #[test]
+    fn include_cycle() {
+        assert_eq!(IncludeCycle(span(1, 1, 0, 8), PathBuf::from("a/b.poly")).to_string(),
+                   "The file includes itself, or a file that includes it. Got 'a/b.poly', at \
+                    line 1, column 1.");
+    }
+
+    #[test]
+    fn invalid_expression() {
+        assert_eq!(InvalidExpression(span(1, 1, 0, 1)).to_string(),
+                   "Expected a condition, such as 'active', '@count > 3', or '@role == \"admin\" \
+                    && !@banned'. Found at line 1, column 1.");
+    }
+
+    #[test]
+    fn invalid_component() {
+        assert_eq!(InvalidComponent(symbol()).to_string(),
+                   "Expected a component name after '&'. Got '{', at line 2, column 5.");
+    }
+
+    #[test]
+    fn invalid_element() {
+        assert_eq!(InvalidElement(symbol()).to_string(),
+                   "Expected an element name after '/'. Got '{', at line 2, column 5.");
+    }
+
+    #[test]
+    fn invalid_function_call() {
+        assert_eq!(InvalidFunctionCall(word("foo ")).to_string(),
+                   "Expected a function name after '$', followed by named arguments. Got \
+                    'foo', at line 2, column 5.");
+    }
+
+    #[test]
+    fn invalid_token_in_attributes() {
+        assert_eq!(InvalidTokenInAttributes(symbol()).to_string(),
+                   "Attributes only accept single words, key-value pairs, or a ')' which ends \
+                    the attributes. Got '{', at line 2, column 5.");
+    }
+
+    #[test]
+    fn missing_import() {
+        assert_eq!(MissingImport(span(1, 1, 0, 7), String::from("ui.poly")).to_string(),
+                   "Couldn't read the imported, or included file. Got 'ui.poly', at line 1, \
+                    column 1.");
+    }
+
+    #[test]
+    fn misplaced_doctype() {
+        assert_eq!(MisplacedDoctype(span(2, 1, 10, 1)).to_string(),
+                   "The doctype has to come before the first element. Found at line 2, column 1.");
+    }
+
+    #[test]
+    fn no_name_attached_to_class() {
+        assert_eq!(NoNameAttachedToClass(symbol()).to_string(),
+                   "Expected a class name after '.'. Got '{', at line 2, column 5.");
+    }
+
+    #[test]
+    fn no_name_attached_to_id() {
+        assert_eq!(NoNameAttachedToId(symbol()).to_string(),
+                   "Expected an id after '#'. Got '{', at line 2, column 5.");
+    }
+
+    #[test]
+    fn positional_after_named() {
+        assert_eq!(PositionalAfterNamed(Symbol(span(2, 5, 12, 1), At)).to_string(),
+                   "Positional arguments have to come before named arguments. Got '@', at line \
+                    2, column 5.");
+    }
+
+    #[test]
+    fn unclosed_close_braces() {
+        assert_eq!(UnclosedCloseBraces(span(3, 1, 20, 1)).to_string(),
+                   "'}' without a matching '{', at line 3, column 1.");
+    }
+
+    #[test]
+    fn unclosed_comment() {
+        assert_eq!(UnclosedComment(span(2, 4, 10, 2)).to_string(),
+                   "'/*' without a matching '*/', at line 2, column 4.");
+    }
+
+    #[test]
+    fn unclosed_open_braces() {
+        assert_eq!(UnclosedOpenBraces(span(3, 1, 20, 1)).to_string(),
+                   "'{' without a matching '}', at line 3, column 1.");
+    }
+
+    #[test]
+    fn unclosed_quote() {
+        assert_eq!(UnclosedQuote(span(1, 10, 9, 1), Quote).to_string(),
+                   "'\"' without a matching '\"', at line 1, column 10.");
+        assert_eq!(UnclosedQuote(span(1, 10, 9, 1), SingleQuote).to_string(),
+                   "''' without a matching ''', at line 1, column 10.");
+    }
+
+    #[test]
+    fn unexpected_eof() {
+        assert_eq!(UnexpectedEof(Symbol(span(1, 1, 0, 1), At)).to_string(),
+                   "Unexpected end of file after '@', at line 1, column 1.");
+    }
+
+    #[test]
+    fn unexpected_token() {
+        assert_eq!(UnexpectedToken(word("bar")).to_string(),
+                   "Unexpected token. Got 'bar', at line 2, column 5.");
+    }
+
+    #[test]
+    fn error_span() {
+        assert_eq!(Eof.span(), None);
+        assert_eq!(UnexpectedToken(word("bar")).span(), Some(span(2, 5, 12, 3)));
+        assert_eq!(UnclosedOpenBraces(span(3, 1, 20, 1)).span(), Some(span(3, 1, 20, 1)));
     }
 }
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use super::consts::{AT, BACKSLASH, DOUBLEQUOTE};
+use self::AttributeValue::*;
+
+/// A piece of an attribute value, either text, or a variable to be rendered in its place.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    /// Text that is written as is.
+    Literal(String),
+    /// The name of a variable, such as `id` in `"/users/@id"`.
+    Variable(String),
+}
+
+/// The value of an element's attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeValue {
+    /// A value without any variables.
+    Literal(String),
+    /// A value containing variables, which are resolved when rendering.
+    Interpolated(Vec<Segment>),
+}
+
+impl AttributeValue {
+    /// Creates a value from its segments, which is only interpolated if there are variables.
+    pub fn from_segments(segments: Vec<Segment>) -> Self {
+        let is_literal = segments.iter().all(|segment| {
+            match *segment {
+                Segment::Literal(_) => true,
+                Segment::Variable(_) => false,
+            }
+        });
+
+        if is_literal {
+            let mut value = String::new();
+            for segment in segments {
+                if let Segment::Literal(text) = segment {
+                    value.push_str(&*text);
+                }
+            }
+            Literal(value)
+        } else {
+            Interpolated(segments)
+        }
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        Literal(value)
+    }
+}
+
+impl Display for AttributeValue {
+    /// Writes the value in quotes, as it's written in a template.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let value = match *self {
+            Literal(ref text) => escape_quoted(text),
+            Interpolated(ref segments) => {
+                let mut value = String::new();
+                for segment in segments {
+                    match *segment {
+                        Segment::Literal(ref text) => value.push_str(&*escape_quoted(text)),
+                        Segment::Variable(ref name) => {
+                            value.push(AT);
+                            value.push_str(&*name);
+                        }
+                    }
+                }
+                value
+            }
+        };
+
+        write!(f, "{}{}{}", DOUBLEQUOTE, value, DOUBLEQUOTE)
+    }
+}
+
+/// Writes `text` as a quoted string, which is read back as the same text.
+pub fn quote(text: &str) -> String {
+    Literal(text.to_owned()).to_string()
+}
+
+/// Escapes the quotes, and the `@` that would start a variable within a quoted string.
+fn escape_quoted(text: &str) -> String {
+    let mut escaped = String::new();
+    for character in text.chars() {
+        if character == DOUBLEQUOTE || character == AT {
+            escaped.push(BACKSLASH);
+        }
+        escaped.push(character);
+    }
+    escaped
+}
@@ -1,12 +1,21 @@
+use std::collections::BTreeMap;
 use std::convert::Into;
-use super::ArgKey;
-use compiler::AstResult;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+use super::{ArgKey, SourceSpan, Span, Token};
+use super::ast::format_block;
+use super::attribute::quote;
+use super::expression::format_value;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Component {
     name: String,
-    args: Vec<ArgKey>,
-    ast: Vec<AstResult>,
+    /// The parameters, paired with their default values.
+    args: Vec<(ArgKey, Option<Token>)>,
+    ast: Vec<Token>,
 }
 
 impl Component {
@@ -22,56 +31,187 @@ impl Component {
         &self.name
     }
 
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub fn args(&self) -> Vec<ArgKey> {
-        self.args.clone()
+        self.args.iter().map(|&(ref key, _)| key.clone()).collect()
+    }
+
+    /// The default values of the parameters, in the same order as `args`.
+    pub fn defaults(&self) -> Vec<Option<Token>> {
+        self.args.iter().map(|&(_, ref default)| default.clone()).collect()
     }
 
     pub fn number_of_args(&self) -> usize {
         self.args().len()
     }
 
-    pub fn ast(&self) -> Vec<AstResult> {
+    pub fn ast(&self) -> Vec<Token> {
         self.ast.clone()
     }
 
+    pub fn ast_mut(&mut self) -> &mut Vec<Token> {
+        &mut self.ast
+    }
+
     pub fn add_arg_value<V: Into<String>>(&mut self, value: V) {
-        self.args.push(ArgKey::Json(value.into()));
+        self.args.push((ArgKey::Json(value.into()), None));
+    }
+
+    /// Adds a parameter, which uses `default` when a call doesn't pass a value for it.
+    pub fn add_arg_with_default<V: Into<String>>(&mut self, value: V, default: Token) {
+        self.args.push((ArgKey::Json(value.into()), Some(default)));
+    }
+
+    /// Adds a quoted string, which is only valid when the component is a call.
+    pub fn add_arg_literal<T: Into<String>>(&mut self, value: T) {
+        self.args.push((ArgKey::Literal(Value::String(value.into())), None));
     }
-    pub fn add_children(&mut self, children: &mut Vec<AstResult>) {
+    pub fn add_children(&mut self, children: &mut Vec<Token>) {
         self.ast.append(children);
     }
 }
 
+/// A call of a component, such as `&card(@user){children}`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct ComponentCall {
     name: String,
     values: Vec<ArgKey>,
+    named_values: BTreeMap<String, ArgKey>,
+    children: Vec<Token>,
+    span: SourceSpan,
 }
 
 impl ComponentCall {
+    /// Creates a call of the component `name`, without any arguments.
     pub fn new(name: String) -> Self {
         ComponentCall {
             name: name.trim().into(),
             values: Vec::new(),
+            named_values: BTreeMap::new(),
+            children: Vec::new(),
+            span: SourceSpan::default(),
         }
     }
 
+    /// Creates a call of `component`, passing a variable of the same name to each parameter.
     pub fn from_component(component: Component) -> Self {
         ComponentCall {
             name: component.name().into(),
             values: component.args(),
+            named_values: BTreeMap::new(),
+            children: Vec::new(),
+            span: SourceSpan::default(),
         }
     }
 
+    /// Where the call was parsed from, if it was parsed.
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    /// Sets where the call was parsed from.
+    pub fn set_span(&mut self, span: Span) {
+        self.span = SourceSpan(Some(span));
+    }
+
+    /// The name of the component called.
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Sets the name of the component called.
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// The arguments passed by position.
     pub fn values(&self) -> &[ArgKey] {
         &self.values[..]
     }
 
+    /// The arguments passed by the name of the parameter.
+    pub fn named_values(&self) -> &BTreeMap<String, ArgKey> {
+        &self.named_values
+    }
+
+    /// The children passed from the call site, rendered in place of `$children()`.
+    pub fn children(&self) -> &Vec<Token> {
+        &self.children
+    }
+
+    /// The children passed from the call site, which can be changed.
+    pub fn children_mut(&mut self) -> &mut Vec<Token> {
+        &mut self.children
+    }
+
+    /// Moves `children` to the end of the call's children.
+    pub fn add_children(&mut self, children: &mut Vec<Token>) {
+        self.children.append(children);
+    }
+
+    /// Passes the variable `name`.
     pub fn add_value<V: Into<String>>(&mut self, name: V) {
         self.values.push(ArgKey::Json(name.into()));
     }
+
+    /// Passes a quoted string, rather than a variable.
+    pub fn add_literal_value<V: Into<String>>(&mut self, value: V) {
+        self.values.push(ArgKey::Literal(Value::String(value.into())));
+    }
+
+    /// Passes `value` to the parameter `name`, returning the value previously passed to it.
+    pub fn add_named_value<K: Into<String>, V: Into<String>>(&mut self,
+                                                             name: K,
+                                                             value: V)
+                                                             -> Option<ArgKey> {
+        self.named_values.insert(name.into(), ArgKey::Json(value.into()))
+    }
+}
+
+impl Display for Component {
+    /// Writes the definition as `&name(@parameter, @parameter="default"){body}`.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let parameters: Vec<String> = self.args
+                                          .iter()
+                                          .map(|&(ref key, ref default)| {
+                                              match *default {
+                                                  Some(Token::Text(ref text)) => {
+                                                      format!("{}={}", format_arg(key), quote(text))
+                                                  }
+                                                  _ => format_arg(key),
+                                              }
+                                          })
+                                          .collect();
+
+        write!(f, "&{}({}){}", self.name, parameters.join(", "), format_block(&self.ast))
+    }
+}
+
+impl Display for ComponentCall {
+    /// Writes the call as `&name(@value, "literal", name=@value){children}`. The parentheses
+    /// are always written, so that a word directly after the call isn't read as its name.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut arguments: Vec<String> = self.values.iter().map(format_arg).collect();
+        for (name, value) in &self.named_values {
+            arguments.push(format!("{}={}", name, format_arg(value)));
+        }
+
+        let mut source = format!("&{}({})", self.name, arguments.join(", "));
+        if !self.children.is_empty() {
+            source.push_str(&*format_block(&self.children));
+        }
+        write!(f, "{}", source)
+    }
+}
+
+/// Writes an argument as `@variable`, `&component`, or a quoted literal.
+pub fn format_arg(arg: &ArgKey) -> String {
+    match *arg {
+        ArgKey::Json(ref name) => format!("@{}", name),
+        ArgKey::Comp(ref name) => format!("&{}", name),
+        ArgKey::Literal(ref value) => format_value(value),
+    }
 }
@@ -22,12 +22,24 @@ pub const DOUBLEQUOTE: char = '"';
 pub const EQUALS: char = '=';
 /// The / character used to define elements
 pub const FORWARDSLASH: char = '/';
+/// The > character used to put a space before an element.
+pub const GREATERTHAN: char = '>';
+/// The < character used to trim the whitespace after an element.
+pub const LESSTHAN: char = '<';
 /// The { character used to signify the start of an elements children.
 pub const OPENBRACE: char = '{';
 /// The ( character used to signify the start of the attributes for an element, or start of a 
 /// function call.
 pub const OPENPARAM: char = '(';
+/// The | character used to pass a variable through filters.
+pub const PIPE: char = '|';
 /// The # character used to define CSS ids for an element.
 pub const POUND: char = '#';
+/// The ' character used for values within an attributes field, as an alternative to ".
+pub const SINGLEQUOTE: char = '\'';
 /// The * character used for multi line comments.
 pub const STAR: char = '*';
+/// Every character the lexer reads as a symbol, which has to be escaped when written as text.
+pub const SYMBOLS: [char; 19] = [AMPERSAND, AT, BACKSLASH, CLOSEBRACE, CLOSEPARAM, COMMA, DOLLAR,
+                                 DOT, DOUBLEQUOTE, EQUALS, FORWARDSLASH, GREATERTHAN, LESSTHAN,
+                                 OPENBRACE, OPENPARAM, PIPE, POUND, SINGLEQUOTE, STAR];
@@ -1,66 +1,403 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 
-use compiler::AstResult;
-use super::ComponentCall;
+use super::{AttributeValue, ComponentCall, Expr, Segment, SourceSpan, Span, Token};
+use super::ast::format_block;
+use super::attribute::quote;
+
+/// Elements that can't have any children, and so have no closing tag, from the WHATWG HTML
+/// standard, along with the obsolete ones browsers still treat as void.
+pub const VOID_ELEMENTS: [&'static str; 16] = ["area", "base", "br", "col", "embed", "hr", "img",
+                                               "input", "link", "meta", "param", "source",
+                                               "track", "wbr", "command", "keygen"];
+
+/// Elements whose text keeps its whitespace as written.
+const PRESERVE_WHITESPACE_ELEMENTS: [&'static str; 4] = ["pre", "textarea", "script", "style"];
+
+/// Elements that are laid out as blocks, so the whitespace around them isn't shown.
+const BLOCK_ELEMENTS: [&'static str; 46] = ["address", "article", "aside", "blockquote", "body",
+                                            "dd", "details", "dialog", "div", "dl", "dt",
+                                            "fieldset", "figcaption", "figure", "footer", "form",
+                                            "h1", "h2", "h3", "h4", "h5", "h6", "head", "header",
+                                            "hgroup", "hr", "html", "li", "link", "main", "meta",
+                                            "nav", "ol", "p", "pre", "section", "summary",
+                                            "table", "tbody", "td", "tfoot", "th", "thead",
+                                            "title", "tr", "ul"];
 
 /// The AST representation of a HTML element.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Element {
     tag: String,
     classes: Vec<String>,
-    attributes: HashMap<String, String>,
+    /// Attributes in the order they were written. Attributes without a value, such as
+    /// `disabled`, are stored as `None`.
+    attributes: Vec<(String, Option<AttributeValue>)>,
+    /// The conditions of attributes that are only written when their condition is true, such
+    /// as `disabled` in `(disabled ?= @locked)`.
+    conditions: Vec<(String, Expr)>,
+    /// Variables of objects whose pairs are written as attributes, as in `(**@attributes)`.
+    spreads: Vec<String>,
+    /// How many attributes were written before the first class.
+    class_index: usize,
     resource: Option<ComponentCall>,
-    children: Vec<AstResult>,
+    children: Vec<Token>,
+    void: bool,
+    raw_attributes: bool,
+    /// Whether the whitespace at the start of the text after the element is trimmed.
+    trim_after: bool,
+    /// Whether a space is written before the element.
+    space_before: bool,
+    /// Where the element was parsed from, from its `/` to its closing brace.
+    span: SourceSpan,
 }
 
 impl Element {
+    /// Creates an element without any classes, attributes, or children.
     pub fn new(tag: String) -> Self {
+        let void = tag.starts_with('!') || VOID_ELEMENTS.contains(&&*tag);
+
         Element {
             tag: tag,
             classes: Vec::new(),
-            attributes: HashMap::new(),
+            attributes: Vec::new(),
+            conditions: Vec::new(),
+            spreads: Vec::new(),
+            class_index: 0,
             resource: None,
             children: Vec::new(),
+            void: void,
+            raw_attributes: false,
+            trim_after: false,
+            space_before: false,
+            span: SourceSpan::default(),
         }
     }
 
+    /// Where the element was parsed from, if it was parsed.
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    /// Sets where the element was parsed from.
+    pub fn set_span(&mut self, span: Span) {
+        self.span = SourceSpan(Some(span));
+    }
+
+    /// The name of the element, such as `div`.
     pub fn tag(&self) -> &str {
         &*self.tag
     }
 
+    /// The classes, in the order they were written.
     pub fn classes(&self) -> &Vec<String> {
         &self.classes
     }
 
-    pub fn attributes(&self) -> &HashMap<String, String> {
+    /// The attributes other than `class`, in the order they were written.
+    pub fn attributes(&self) -> &Vec<(String, Option<AttributeValue>)> {
         &self.attributes
     }
 
+    /// The attributes starting with `prefix`, such as `data-`, in the order they were written.
+    pub fn attributes_with_prefix(&self, prefix: &str) -> Vec<&(String, Option<AttributeValue>)> {
+        self.attributes.iter().filter(|attribute| attribute.0.starts_with(prefix)).collect()
+    }
+
+    /// The `data-*` attributes, in the order they were written.
+    pub fn data_attributes(&self) -> Vec<&(String, Option<AttributeValue>)> {
+        self.attributes_with_prefix("data-")
+    }
+
+    /// The `aria-*` attributes, in the order they were written.
+    pub fn aria_attributes(&self) -> Vec<&(String, Option<AttributeValue>)> {
+        self.attributes_with_prefix("aria-")
+    }
+
+    /// Gets the value of an attribute, which is `Some(None)` for an attribute without a value.
+    pub fn attribute(&self, key: &str) -> Option<&Option<AttributeValue>> {
+        for &(ref name, ref value) in &self.attributes {
+            if name == key {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// The component called in place of the element's children, as in `/div&card`.
     pub fn resource(&self) -> &Option<ComponentCall> {
         &self.resource
     }
 
-    pub fn children(&self) -> &Vec<AstResult> {
+    /// The component called in place of the element's children, which can be changed.
+    pub fn resource_mut(&mut self) -> &mut Option<ComponentCall> {
+        &mut self.resource
+    }
+
+    /// The children within the element's braces.
+    pub fn children(&self) -> &Vec<Token> {
         &self.children
     }
 
+    /// The children within the element's braces, which can be changed.
+    pub fn children_mut(&mut self) -> &mut Vec<Token> {
+        &mut self.children
+    }
+
+    /// Whether the element is a void element, and won't be given a closing tag.
+    pub fn is_void(&self) -> bool {
+        self.void
+    }
+
+    /// Whether the element's text keeps its whitespace as written, such as `pre`.
+    pub fn preserves_whitespace(&self) -> bool {
+        PRESERVE_WHITESPACE_ELEMENTS.contains(&&*self.tag)
+    }
+
+    /// Whether the element is a declaration, such as `!DOCTYPE`, rather than an element.
+    pub fn is_declaration(&self) -> bool {
+        self.tag.starts_with('!')
+    }
+
+    /// Whether the element is laid out as a block, such as `div`, or `p`.
+    pub fn is_block(&self) -> bool {
+        BLOCK_ELEMENTS.contains(&&*self.tag)
+    }
+
+    /// Overrides whether the element is treated as a void element.
+    pub fn set_void(&mut self, void: bool) {
+        self.void = void;
+    }
+
+    /// Whether the attributes are written as is, without being escaped.
+    pub fn has_raw_attributes(&self) -> bool {
+        self.raw_attributes
+    }
+
+    /// Sets whether the attributes are written as is, without being escaped.
+    pub fn set_raw_attributes(&mut self, raw_attributes: bool) {
+        self.raw_attributes = raw_attributes;
+    }
+
+    /// Whether the whitespace at the start of the text after the element is trimmed, written as
+    /// `<` after the tag.
+    pub fn trims_after(&self) -> bool {
+        self.trim_after
+    }
+
+    /// Sets whether the whitespace at the start of the text after the element is trimmed.
+    pub fn set_trim_after(&mut self, trim_after: bool) {
+        self.trim_after = trim_after;
+    }
+
+    /// Whether a space is written before the element, written as `>` after the tag.
+    pub fn has_space_before(&self) -> bool {
+        self.space_before
+    }
+
+    /// Sets whether a space is written before the element.
+    pub fn set_space_before(&mut self, space_before: bool) {
+        self.space_before = space_before;
+    }
+
+    /// Sets the component called in place of the element's children.
     pub fn add_resource(&mut self, resource: ComponentCall) {
         self.resource = Some(resource);
     }
 
-    pub fn add_children(&mut self, children: &mut Vec<AstResult>) {
+    /// Moves `children` to the end of the element's children.
+    pub fn add_children(&mut self, children: &mut Vec<Token>) {
         self.children.append(children)
     }
 
+    /// Adds a class, unless the element already has it.
     pub fn add_class(&mut self, class: String) {
-        self.classes.push(class);
+        if self.classes.is_empty() {
+            self.class_index = self.attributes.len();
+        }
+        if !self.classes.contains(&class) {
+            self.classes.push(class);
+        }
+    }
+
+    /// Where the class attribute is written among the other attributes, which is where the
+    /// first class was written, or where a class attribute with variables was written.
+    pub fn class_index(&self) -> usize {
+        if !self.classes.is_empty() {
+            return self.class_index;
+        }
+        match self.attributes.iter().position(|attribute| attribute.0 == "class") {
+            Some(index) => index,
+            None => self.attributes.len(),
+        }
+    }
+
+    /// Adds an attribute, or replaces the value of one with the same name. A literal `class`
+    /// adds each of its classes instead, and a `class` with variables is joined to any earlier
+    /// one.
+    pub fn add_attribute<V: Into<AttributeValue>>(&mut self, key: String, value: V) {
+        match (key.trim(), value.into()) {
+            ("class", AttributeValue::Literal(classes)) => {
+                for class in classes.split_whitespace() {
+                    self.add_class(class.to_owned());
+                }
+            }
+            ("class", AttributeValue::Interpolated(mut segments)) => {
+                for attribute in self.attributes.iter_mut() {
+                    if let ("class", &mut Some(AttributeValue::Interpolated(ref mut existing))) =
+                           (&*attribute.0, &mut attribute.1) {
+                        existing.push(Segment::Literal(String::from(" ")));
+                        existing.append(&mut segments);
+                        return;
+                    }
+                }
+                self.set_attribute(String::from("class"),
+                                   Some(AttributeValue::Interpolated(segments)))
+            }
+            (key, value) => self.set_attribute(key.to_owned(), Some(value)),
+        }
+    }
+
+    /// Adds an attribute without a value, such as `disabled`, or `required`.
+    pub fn add_boolean_attribute(&mut self, key: String) {
+        self.set_attribute(key.trim().to_owned(), None);
+    }
+
+    /// Adds an attribute without a value, which is only written when `condition` is true.
+    pub fn add_conditional_attribute(&mut self, key: String, condition: Expr) {
+        let key = key.trim().to_owned();
+        self.set_attribute(key.clone(), None);
+        self.conditions.push((key, condition));
+    }
+
+    /// The condition of the attribute, if it's only written when the condition is true.
+    pub fn condition(&self, key: &str) -> Option<&Expr> {
+        self.conditions.iter().find(|condition| condition.0 == key).map(|condition| &condition.1)
+    }
+
+    /// The variables of objects whose pairs are written as attributes, in the order they were
+    /// written.
+    pub fn spreads(&self) -> &Vec<String> {
+        &self.spreads
+    }
+
+    /// Writes the pairs of the object in the variable `name` as attributes, other than those
+    /// the element already has.
+    pub fn add_spread(&mut self, name: String) {
+        self.spreads.push(name);
+    }
+
+    /// Appends the attribute, or replaces the value of an attribute with the same name, keeping
+    /// its original position. Any condition of the replaced attribute is removed.
+    fn set_attribute(&mut self, key: String, value: Option<AttributeValue>) {
+        self.conditions.retain(|condition| condition.0 != key);
+        for attribute in self.attributes.iter_mut() {
+            if attribute.0 == key {
+                attribute.1 = value;
+                return;
+            }
+        }
+        self.attributes.push((key, value));
     }
+}
+
+impl Display for Element {
+    /// Writes the element as `/tag.class#id(attribute="value"){children}`. Classes, and an id
+    /// that can't be written as a single word are written as attributes instead.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut source = format!("/{}", self.tag);
+        let mut rest = &self.attributes[..];
+        let class_index = self.class_index();
 
-    pub fn add_attribute(&mut self, key: String, value: String) {
-        if key == "class" {
-            self.classes.push(value);
-        } else {
-            self.attributes.insert(key.trim().to_owned(), value);
+        let id = match rest.first() {
+            Some(&(ref key, Some(AttributeValue::Literal(ref id)))) if key == "id" => Some(id),
+            _ => None,
+        };
+        let id = id.and_then(|id| if is_word(id) { Some(id) } else { None });
+        // An id written before the classes is still written before them.
+        if let Some(id) = id {
+            if class_index > 0 {
+                source.push('#');
+                source.push_str(&*id);
+                rest = &rest[1..];
+            }
+        }
+
+        let shorthand = class_index == self.attributes.len() - rest.len();
+        let mut classes = Vec::new();
+        for class in &self.classes {
+            if shorthand && classes.is_empty() && is_word(class) {
+                source.push('.');
+                source.push_str(&*class);
+            } else {
+                classes.push(format!("class={}", quote(class)));
+            }
+        }
+
+        if let Some(id) = id {
+            if class_index == 0 {
+                source.push('#');
+                source.push_str(&*id);
+                rest = &rest[1..];
+            }
+        }
+
+        // Classes that can't be shorthand are written as attributes where they were written.
+        let mut index = self.attributes.len() - rest.len();
+        let mut attributes = Vec::new();
+        if class_index <= index {
+            attributes.append(&mut classes);
+        }
+        for &(ref key, ref value) in rest {
+            if index == class_index {
+                attributes.append(&mut classes);
+            }
+            index += 1;
+            match (value, self.condition(key)) {
+                (&Some(ref value), _) => attributes.push(format!("{}={}", key, value)),
+                (&None, Some(condition)) => attributes.push(format!("{} ?= {}", key, condition)),
+                (&None, None) => attributes.push(key.clone()),
+            }
+        }
+        attributes.append(&mut classes);
+        for name in &self.spreads {
+            attributes.push(format!("**@{}", name));
         }
+
+        if self.raw_attributes {
+            source.push('*');
+        }
+        if self.trim_after {
+            source.push('<');
+        }
+        if self.space_before {
+            source.push('>');
+        }
+        if let Some(ref resource) = self.resource {
+            source.push_str(&*resource.to_string());
+            // The resource's children end the element, which then can't have its own.
+            if !resource.children().is_empty() {
+                return write!(f, "{}", source);
+            }
+        }
+        if !attributes.is_empty() {
+            source.push_str(&*format!("({})", attributes.join(" ")));
+        }
+        // Raw text is only read as is within a raw block.
+        if let Some(&Token::Raw(_)) = self.children.first() {
+            source.push('.');
+        }
+        // A void element can't be given a block, even an empty one.
+        if self.void && self.children.is_empty() {
+            return write!(f, "{}", source);
+        }
+        write!(f, "{}{}", source, format_block(&self.children))
     }
 }
+
+/// Whether the text would be read as a single word, so it can follow a `.`, or `#`.
+fn is_word(text: &str) -> bool {
+    !text.is_empty() &&
+    text.chars().all(|character| {
+        character.is_alphanumeric() || character == '-' || character == '_'
+    })
+}
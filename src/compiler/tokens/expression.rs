@@ -0,0 +1,140 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+use super::attribute::quote;
+
+/// A condition of an `@if`, evaluated against the JSON when rendering.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// A variable, such as `@user.role`, or `user.role`.
+    Variable(String),
+    /// A quoted string, number, `true`, `false`, or `null`.
+    Literal(Value),
+    /// `!expr`, true when the expression is false.
+    Not(Box<Expr>),
+    /// Compares two expressions, such as `@count > 3`.
+    Compare(Box<Expr>, Comparison, Box<Expr>),
+    /// `left && right`, true when both expressions are true.
+    And(Box<Expr>, Box<Expr>),
+    /// `left || right`, true when either expression is true.
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// How tightly the expression binds, where `||` binds the loosest, and an operand, or `!`
+    /// binds the tightest.
+    fn precedence(&self) -> usize {
+        match *self {
+            Expr::Or(_, _) => 0,
+            Expr::And(_, _) => 1,
+            Expr::Compare(_, _, _) => 2,
+            Expr::Variable(_) | Expr::Literal(_) | Expr::Not(_) => 3,
+        }
+    }
+
+    /// Writes the expression as the operand of an operator with `precedence`, in parentheses if
+    /// it would otherwise be parsed differently.
+    fn operand(&self, precedence: usize) -> String {
+        if self.precedence() < precedence {
+            format!("({})", self)
+        } else {
+            self.to_string()
+        }
+    }
+}
+
+impl Display for Expr {
+    /// Writes the expression as it's written in a condition, which parses back into the same
+    /// expression.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Expr::Variable(ref name) => write!(f, "@{}", name),
+            Expr::Literal(ref value) => write!(f, "{}", format_value(value)),
+            Expr::Not(ref operand) => write!(f, "!{}", operand.operand(3)),
+            Expr::Compare(ref left, comparison, ref right) => {
+                write!(f, "{} {} {}", left.operand(3), comparison, right.operand(3))
+            }
+            Expr::And(ref left, ref right) => {
+                write!(f, "{} && {}", left.operand(1), right.operand(2))
+            }
+            Expr::Or(ref left, ref right) => {
+                write!(f, "{} || {}", left.operand(0), right.operand(1))
+            }
+        }
+    }
+}
+
+/// Writes a literal as it's written in a template, with strings in quotes. A whole float keeps
+/// its `.0`, so it isn't read back as an integer.
+pub fn format_value(value: &Value) -> String {
+    match *value {
+        Value::String(ref text) => quote(text),
+        Value::F64(number) if number.fract() == 0.0 => format!("{:.1}", number),
+        ref value => {
+            match ::serde_json::to_string(value) {
+                Ok(text) => text,
+                Err(_) => String::new(),
+            }
+        }
+    }
+}
+
+/// An integer as JSON, which like a parsed number, is only signed if it's negative.
+pub fn integer_value(integer: i64) -> Value {
+    if integer < 0 {
+        Value::I64(integer)
+    } else {
+        Value::U64(integer as u64)
+    }
+}
+
+/// The operators comparing two values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// The comparison written as `operator`, if it is one.
+    pub fn from_operator(operator: &str) -> Option<Self> {
+        use self::Comparison::*;
+        match operator {
+            "==" => Some(Equal),
+            "!=" => Some(NotEqual),
+            "<" => Some(Less),
+            "<=" => Some(LessOrEqual),
+            ">" => Some(Greater),
+            ">=" => Some(GreaterOrEqual),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::Comparison::*;
+        let operator = match *self {
+            Equal => "==",
+            NotEqual => "!=",
+            Less => "<",
+            LessOrEqual => "<=",
+            Greater => ">",
+            GreaterOrEqual => ">=",
+        };
+
+        write!(f, "{}", operator)
+    }
+}
@@ -1,32 +1,94 @@
 use std::convert::Into;
 use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
 use super::*;
+use super::component::format_arg;
 
+/// A call of a registered function, such as `$std.each(array=@items, component=&item)`.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct FunctionCall {
     identifier: String,
     arguments: BTreeMap<String, ArgKey>,
+    children: Vec<Token>,
+    span: SourceSpan,
 }
 
 
 impl FunctionCall {
+    /// Creates a call of the function `identifier`, without any arguments.
     pub fn new(identifier: String) -> Self {
         FunctionCall { identifier: identifier.trim().to_owned(), ..Self::default() }
     }
 
+    /// Where the call was parsed from, if it was parsed.
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    /// Sets where the call was parsed from.
+    pub fn set_span(&mut self, span: Span) {
+        self.span = SourceSpan(Some(span));
+    }
+
+    /// The name of the function called.
     pub fn identifier(&self) -> &str {
         &self.identifier
     }
 
+    /// The arguments, by name.
     pub fn args(&self) -> &BTreeMap<String, ArgKey> {
         &self.arguments
     }
 
+    /// Passes the variable `value` to the argument `key`.
     pub fn add_value_arg<SK: AsRef<str>, SV: Into<String>>(&mut self, key: SK, value: SV) {
         self.arguments.insert(key.as_ref().trim().into(), ArgKey::Json(value.into()));
     }
 
+    /// Passes the component `value` to the argument `key`.
     pub fn add_component_arg<SK: AsRef<str>, SV: Into<String>>(&mut self, key: SK, value: SV) {
         self.arguments.insert(key.as_ref().trim().into(), ArgKey::Comp(value.into()));
     }
+
+    /// Passes a quoted string, number, `true`, `false`, or `null` to the argument `key`.
+    pub fn add_literal_arg<SK: AsRef<str>>(&mut self, key: SK, value: Value) {
+        self.arguments.insert(key.as_ref().trim().into(), ArgKey::Literal(value));
+    }
+
+    /// The block following the call, passed to the function as `children`.
+    pub fn children(&self) -> &Vec<Token> {
+        &self.children
+    }
+
+    /// The block following the call, which can be changed.
+    pub fn children_mut(&mut self) -> &mut Vec<Token> {
+        &mut self.children
+    }
+
+    /// Moves `children` to the end of the block following the call.
+    pub fn add_children(&mut self, children: &mut Vec<Token>) {
+        self.children.append(children);
+    }
+}
+
+impl Display for FunctionCall {
+    /// Writes the call as `$name(name=@value, name=&component){children}`.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let arguments: Vec<String> = self.arguments
+                                         .iter()
+                                         .map(|(name, value)| {
+                                             format!("{}={}", name, format_arg(value))
+                                         })
+                                         .collect();
+
+        let mut source = format!("${}({})", self.identifier, arguments.join(", "));
+        if !self.children.is_empty() {
+            source.push_str(&*format_block(&self.children));
+        }
+        write!(f, "{}", source)
+    }
 }
@@ -3,35 +3,78 @@ use std::fmt::{Display, Formatter};
 
 use self::Lexeme::*;
 use super::operator::Operator;
+use super::position::Position;
+use super::span::Span;
 
-/// Parent enum defining the two types of Terminal symbols within the language.
-/// Words, and operator symbols.
+/// Parent enum defining the types of Terminal symbols within the language. Words, operator
+/// symbols, and the text of raw blocks.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Lexeme {
-    Symbol(usize, Operator),
-    Word(usize, String),
+    /// A single character operator.
+    Symbol(Span, Operator),
+    /// A run of text between operators. A word keeps the whitespace before, and after it.
+    Word(Span, String),
+    /// The text within a raw block, or the children of a raw element such as `script`, kept
+    /// exactly as written.
+    RawText(Span, String),
+    /// Literal text following a `|`, kept exactly as written, along with the line break after
+    /// it.
+    Literal(Span, String),
+    /// A quoted string, and the quote it's quoted with, with its escapes other than `\\`, and
+    /// `\@` replaced. Those are left for the parser, as they decide which `@`s start a variable.
+    Str(Span, Operator, String),
+    /// A number directly after a `=`, such as `3`, `-2`, `1.5`, or `.5`. Like a word, it keeps
+    /// the whitespace before, and after it.
+    Number(Span, String),
+    /// The `/*` of a block comment without a `*/`, which comments out the rest of the source.
+    OpenComment(Span),
 }
 
 impl Lexeme {
+    /// The length of the lexeme's text, in bytes.
     pub fn length(&self) -> usize {
         match *self {
             Symbol(_, _) => 1,
-            Word(_, ref word) => word.len(),
+            OpenComment(_) => 2,
+            Word(_, ref word) |
+            RawText(_, ref word) |
+            Literal(_, ref word) |
+            Str(_, _, ref word) |
+            Number(_, ref word) => word.len(),
         }
     }
 
-    pub fn index(&self) -> usize {
+    /// The region of the source the lexeme covers.
+    pub fn span(&self) -> Span {
         match *self {
-            Symbol(index, _) | Word(index, _) => index,
+            Symbol(span, _) |
+            Word(span, _) |
+            RawText(span, _) |
+            Literal(span, _) |
+            Str(span, _, _) |
+            Number(span, _) |
+            OpenComment(span) => span,
         }
     }
+
+    /// Where the lexeme starts.
+    pub fn position(&self) -> Position {
+        self.span().start
+    }
 }
 
 impl Display for Lexeme {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let display = match *self {
-            Symbol(_, ref operator) => format!("OPERATOR: {}", operator.to_string()),
-            Word(_, ref word) => format!("WORD: {}", word.clone()),
+            Symbol(_, ref operator) => format!("'{}'", operator),
+            Word(_, ref word) |
+            RawText(_, ref word) |
+            Literal(_, ref word) |
+            Number(_, ref word) => {
+                format!("'{}'", word.trim())
+            }
+            Str(_, quote, ref text) => format!("'{}{}{}'", quote, text, quote),
+            OpenComment(_) => String::from("'/*'"),
         };
 
         write!(f, "{}", display)
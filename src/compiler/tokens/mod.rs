@@ -1,17 +1,25 @@
 pub mod args;
+pub mod attribute;
 pub mod ast;
 pub mod consts;
 pub mod component;
 pub mod element;
+pub mod expression;
 pub mod function_call;
 pub mod lexeme;
 pub mod operator;
+pub mod position;
+pub mod span;
 
 pub use self::args::*;
+pub use self::attribute::*;
 pub use self::ast::*;
 pub use self::consts::*;
 pub use self::component::*;
 pub use self::element::*;
+pub use self::expression::*;
 pub use self::function_call::*;
 pub use self::lexeme::*;
 pub use self::operator::*;
+pub use self::position::*;
+pub use self::span::*;
@@ -3,38 +3,48 @@ use std::fmt::{Display, Formatter};
 
 use super::consts::*;
 
-/// The types of operators
-#[derive(Debug, PartialEq, Clone)]
+/// The symbols of the language, each of which is a single character.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Operator {
-    /// TODO
+    /// `&`, which starts a component definition, or call.
     Ampersand,
-    /// TODO
+    /// `@`, which starts a variable, or a keyword such as `@if`.
     At,
-    /// TODO
+    /// `\`, which escapes the symbol after it.
     BackSlash,
-    /// TODO
+    /// `}`, which ends a block of children.
     CloseBrace,
-    /// TODO
+    /// `)`, which ends a list of attributes, or arguments.
     CloseParam,
-    /// TODO
+    /// `,`, which separates arguments.
     Comma,
-    /// TODO
+    /// `$`, which starts a function call.
     Dollar,
-    /// TODO
+    /// `.`, which starts a class, or separates the parts of a name.
     Dot,
-    /// TODO
+    /// `=`, which separates a name from its value.
     Equals,
-    /// TODO
+    /// `/`, which starts an element. Two together start a comment instead.
     ForwardSlash,
-    /// TODO
+    /// `>`, which puts a space before an element, or makes the element after it the only
+    /// child, as in `/ul > /li {}`.
+    GreaterThan,
+    /// `<`, which trims the whitespace from the start of the text after an element.
+    LessThan,
+    /// `{`, which starts a block of children.
     OpenBrace,
-    /// TODO
+    /// `(`, which starts a list of attributes, or arguments.
     OpenParam,
-    /// TODO
+    /// `|`, which passes a variable through a filter.
+    Pipe,
+    /// `#`, which starts an element's id.
     Pound,
-    /// TODO
+    /// `"`, which starts, or ends a quoted value.
     Quote,
-    /// TODO
+    /// `'`, which starts, or ends a quoted value, when it's after a `=`, `(`, or `,`, or ends
+    /// one it started. Otherwise it's part of the text.
+    SingleQuote,
+    /// `*`, which marks an element's attributes as raw.
     Star,
 }
 
@@ -52,10 +62,14 @@ impl Display for Operator {
             Dot => DOT,
             Equals => EQUALS,
             ForwardSlash => FORWARDSLASH,
+            GreaterThan => GREATERTHAN,
+            LessThan => LESSTHAN,
             OpenBrace => OPENBRACE,
             OpenParam => OPENPARAM,
+            Pipe => PIPE,
             Pound => POUND,
             Quote => DOUBLEQUOTE,
+            SingleQuote => SINGLEQUOTE,
             Star => STAR,
         };
         write!(f, "{}", ch)
@@ -0,0 +1,31 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// The location of a lexeme within the source. Lines, and columns start at one, and columns are
+/// counted in characters, while the offset is the byte index into the source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    /// The line number.
+    pub line: usize,
+    /// The column number.
+    pub column: usize,
+    /// The byte offset from the start of the source.
+    pub offset: usize,
+}
+
+impl Position {
+    /// Creates a new position.
+    pub fn new(line: usize, column: usize, offset: usize) -> Self {
+        Position {
+            line: line,
+            column: column,
+            offset: offset,
+        }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use super::position::Position;
+
+/// The region of the source a lexeme, or error covers. The end is exclusive, so it's the
+/// position directly after the last character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    /// Where the span starts.
+    pub start: Position,
+    /// Where the span ends.
+    pub end: Position,
+}
+
+impl Span {
+    /// Creates a new span.
+    pub fn new(start: Position, end: Position) -> Self {
+        Span {
+            start: start,
+            end: end,
+        }
+    }
+
+    /// The length of the span in bytes.
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+
+    /// Whether the span doesn't cover any of the source.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.start)
+    }
+}
+
+/// The span of source a token was parsed from, which a token built by hand doesn't have. It's
+/// ignored when comparing tokens, so a token is equal to the same token written anywhere else.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SourceSpan(pub Option<Span>);
+
+impl PartialEq for SourceSpan {
+    fn eq(&self, _: &SourceSpan) -> bool {
+        true
+    }
+}
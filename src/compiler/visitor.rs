@@ -0,0 +1,185 @@
+use super::tokens::{ArgKey, AttributeValue, Collection, ComponentCall, Element, Expr,
+                    FunctionCall, Segment, Token};
+use super::tokens::Token::*;
+
+/// Traverses a template's AST with `walk`, for tools such as linters. Each method is called
+/// for a node of its kind, and does nothing unless it's overridden, so a visitor only
+/// implements the nodes it cares about.
+pub trait Visitor {
+    /// Called for each element, before its children.
+    fn visit_element(&mut self, _element: &Element) {}
+
+    /// Called for each variable, escaped or not, with the filters it's passed through. The
+    /// variables of conditions, loops, switches, attribute values, and arguments are also
+    /// visited, without any filters.
+    fn visit_variable(&mut self, _name: &str, _filters: &[String]) {}
+
+    /// Called for each component call, before its children.
+    fn visit_component(&mut self, _component_call: &ComponentCall) {}
+
+    /// Called for each function call, before its children.
+    fn visit_function(&mut self, _function_call: &FunctionCall) {}
+
+    /// Called for each piece of text.
+    fn visit_text(&mut self, _text: &str) {}
+}
+
+/// Visits each token in order, recursing into the children of elements, component calls,
+/// function calls, and every branch of a conditional, loop, or switch, along with the
+/// variables those use.
+pub fn walk<V: Visitor>(tokens: &[Token], visitor: &mut V) {
+    for token in tokens {
+        match *token {
+            Html(ref element) => {
+                visitor.visit_element(element);
+                walk_attributes(element, visitor);
+                if let Some(ref component_call) = *element.resource() {
+                    walk_component(component_call, visitor);
+                }
+                walk(element.children(), visitor);
+            }
+            Text(ref text) => visitor.visit_text(text),
+            Variable(ref name, ref filters, _) |
+            RawVariable(ref name, ref filters, _) => visitor.visit_variable(name, filters),
+            CompCall(ref component_call) => walk_component(component_call, visitor),
+            Function(ref function_call) => {
+                visitor.visit_function(function_call);
+                for argument in function_call.args().values() {
+                    walk_argument(argument, visitor);
+                }
+                walk(function_call.children(), visitor);
+            }
+            Conditional { ref condition, ref then_branch, ref else_ifs, ref else_branch, .. } => {
+                walk_expression(condition, visitor);
+                walk(then_branch, visitor);
+                for &(ref condition, ref children) in else_ifs {
+                    walk_expression(condition, visitor);
+                    walk(children, visitor);
+                }
+                if let Some(ref else_branch) = *else_branch {
+                    walk(else_branch, visitor);
+                }
+            }
+            Loop { ref collection, ref body, ref else_branch, .. } => {
+                match *collection {
+                    Collection::Variable(ref name) => visitor.visit_variable(name, &[]),
+                    Collection::Range(ref start, ref end) => {
+                        walk_expression(start, visitor);
+                        walk_expression(end, visitor);
+                    }
+                }
+                walk(body, visitor);
+                if let Some(ref else_branch) = *else_branch {
+                    walk(else_branch, visitor);
+                }
+            }
+            Switch { ref subject, ref cases, ref default, .. } => {
+                visitor.visit_variable(subject, &[]);
+                for &(_, ref children) in cases {
+                    walk(children, visitor);
+                }
+                if let Some(ref default) = *default {
+                    walk(default, visitor);
+                }
+            }
+            Comment(ref children) => walk(children, visitor),
+            Block { ref body, .. } => walk(body, visitor),
+            Raw(_) | Doctype(_) => {}
+        }
+    }
+}
+
+/// Visits the variables within an element's attribute values, the conditions of its
+/// attributes, and the objects spread into its attributes.
+fn walk_attributes<V: Visitor>(element: &Element, visitor: &mut V) {
+    for &(ref key, ref value) in element.attributes() {
+        match *value {
+            Some(AttributeValue::Interpolated(ref segments)) => {
+                for segment in segments {
+                    if let Segment::Variable(ref name) = *segment {
+                        visitor.visit_variable(name, &[]);
+                    }
+                }
+            }
+            Some(AttributeValue::Literal(_)) => {}
+            None => {
+                if let Some(condition) = element.condition(key) {
+                    walk_expression(condition, visitor);
+                }
+            }
+        }
+    }
+    for name in element.spreads() {
+        visitor.visit_variable(name, &[]);
+    }
+}
+
+/// Visits a component call, the variables passed to it, and its children.
+fn walk_component<V: Visitor>(component_call: &ComponentCall, visitor: &mut V) {
+    visitor.visit_component(component_call);
+    for argument in component_call.values() {
+        walk_argument(argument, visitor);
+    }
+    for argument in component_call.named_values().values() {
+        walk_argument(argument, visitor);
+    }
+    walk(component_call.children(), visitor);
+}
+
+/// Visits the variable passed as an argument, if it's a variable.
+fn walk_argument<V: Visitor>(argument: &ArgKey, visitor: &mut V) {
+    if let ArgKey::Json(ref name) = *argument {
+        visitor.visit_variable(name, &[]);
+    }
+}
+
+/// Visits each variable within an expression, from left to right.
+fn walk_expression<V: Visitor>(expression: &Expr, visitor: &mut V) {
+    match *expression {
+        Expr::Variable(ref name) => visitor.visit_variable(name, &[]),
+        Expr::Literal(_) => {}
+        Expr::Not(ref operand) => walk_expression(operand, visitor),
+        Expr::Compare(ref left, _, ref right) |
+        Expr::And(ref left, ref right) |
+        Expr::Or(ref left, ref right) => {
+            walk_expression(left, visitor);
+            walk_expression(right, visitor);
+        }
+    }
+}
+
+#[allow(dead_code, unused_imports)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{Visitor, walk};
+    use compiler::{Lexer, Parser};
+
+    /// Collects the name of every variable used in a template.
+    struct VariableNames {
+        names: BTreeSet<String>,
+    }
+
+    impl Visitor for VariableNames {
+        fn visit_variable(&mut self, name: &str, _filters: &[String]) {
+            let _ = self.names.insert(String::from(name));
+        }
+    }
+
+    #[test]
+    fn collects_variable_names() {
+        let source = "/div{/p{@title}&card(@user){@!body}\
+                      @if admin {@user.name | upper} @else {$greet(name=@user){@greeting}}\
+                      @each item in @items {/li{@item.title}}\
+                      @each page in 1..@pages {/a(href=\"/pages/@page\"){@page}}\
+                      @switch @status {@case 1 {Active}}}";
+        let tokens = Parser::parse(Lexer::new(source).output()).unwrap();
+        let mut visitor = VariableNames { names: BTreeSet::new() };
+        walk(&tokens, &mut visitor);
+
+        let names: Vec<&str> = visitor.names.iter().map(|name| &**name).collect();
+        assert_eq!(names,
+                   vec!["admin", "body", "greeting", "item.title", "items", "page", "pages",
+                        "status", "title", "user", "user.name"]);
+    }
+}
@@ -11,5 +11,7 @@ extern crate serde_json;
 mod compiler;
 mod template;
 
-pub use template::{PollyFn, std_functions, Template, TemplateError};
-pub use compiler::{ArgValue, AstError, CodegenError};
+pub use template::{PollyFilter, PollyFn, std_filters, std_functions, Template, TemplateError};
+pub use compiler::{ArgValue, AstError, CodegenError, ComponentCall, Element, FunctionCall, Lexeme,
+                   Lexer, Operator, ParseOptions, Parser, Position, RenderOptions, SourceSpan,
+                   Span, Token, Visitor, resolve_path, walk};
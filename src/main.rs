@@ -1,64 +1,70 @@
 #[macro_use]
 extern crate clap;
 extern crate polly;
-extern crate serde_json;
 
-use std::collections::BTreeMap;
-use std::fs::{File, metadata};
-use std::io::{Read, Write};
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::process;
 
-use clap::App;
+use clap::{App, ArgMatches};
 use polly::Template;
-use serde_json::Value;
 
 fn main() {
     let yaml = load_yaml!("../cli.yml");
     let matches = App::from_yaml(yaml).get_matches();
 
-    let paths = matches.values_of("input").unwrap();
+    if let Err(message) = run(&matches) {
+        let _ = writeln!(io::stderr(), "{}", message);
+        process::exit(1);
+    }
+}
 
+/// Renders each input template, stopping at the first that fails, whose message is returned.
+fn run(matches: &ArgMatches) -> Result<(), String> {
+    let lang = match matches.value_of("lang") {
+        Some(lang) => lang,
+        None => "en",
+    };
+    let paths = match matches.values_of("input") {
+        Some(paths) => paths.collect::<Vec<_>>(),
+        None => return Err(String::from("No input file was given.")),
+    };
+    // Each template would replace the HTML of the one before it.
+    if paths.len() > 1 && matches.is_present("output") {
+        return Err(String::from("--output can only be given with a single input file."));
+    }
     for path in paths {
-        let path_metadata = metadata(path)
-                                .ok()
-                                .expect("Couldn't find file, please make sure your path is \
-                                         correct.");
-        if path_metadata.is_file() {
-            let lang = match matches.value_of("lang") {
-                Some(lang) => lang,
-                _ => "en",
-            };
-
-            let json = if let Some(path) = matches.value_of("json") {
-                let contents = {
-                    let mut file = File::open(path).expect("JSON File not found");
-                    let mut contents = String::new();
-                    file.read_to_string(&mut contents).expect("Couldn't JSON read file.");
-                    contents
-                };
-                match serde_json::from_str(&*contents).expect("Wasn't valid JSON") {
-                    Value::Object(object) => object,
-                    _ => panic!("Wasn't a JSON object"),
-                }
-            } else {
-                BTreeMap::new()
-            };
+        let mut template = match Template::load(path) {
+            Ok(template) => template,
+            Err(error) => return Err(error.to_string()),
+        };
+        if let Some(data) = matches.value_of("data") {
+            if let Err(error) = template.load_data_file(data) {
+                return Err(error.to_string());
+            }
+        }
+        if matches.is_present("no-locales") {
+            template = template.no_locales();
+        }
 
-            let html = if matches.is_present("no-locales") {
-                Template::load(path).unwrap().json(json).no_locales().render(lang).unwrap()
-            } else {
-                Template::load(path).unwrap().json(json).render(lang).unwrap()
-            };
+        let html = match template.render(lang) {
+            Ok(html) => html,
+            Err(error) => return Err(format!("{}: {}", path, error)),
+        };
 
-            if let Some(path) = matches.value_of("file") {
-                let mut file = File::create(path)
-                                   .ok()
-                                   .expect("Couldn't create file at destination");
-                file.write_all(&html.into_bytes()).ok().expect("Couldn't write to file");
-            } else {
-                println!("{}", html);
+        let written = match matches.value_of("output") {
+            Some(output) => {
+                match File::create(output) {
+                    Ok(mut file) => file.write_all(html.as_bytes()),
+                    Err(error) => Err(error),
+                }
             }
-        } else {
-            panic!("Path provided wasn't a file: {}", path);
+            None => writeln!(io::stdout(), "{}", html),
+        };
+        if let Err(error) = written {
+            return Err(format!("Couldn't write the HTML of {}: {}", path, error));
         }
     }
+    Ok(())
 }
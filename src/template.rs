@@ -1,18 +1,34 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, BTreeMap};
+use std::error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::Read;
+use std::mem;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use serde_json::Value;
 
-use compiler::{ArgValue, AstError, Codegen, CodegenError, CodegenResult, Component, Lexer, Parser};
+use compiler::{ArgValue, AstError, Codegen, CodegenError, CodegenResult, Component, Lexer,
+               ParseOptions, Parser, RenderOptions, Token, find_unknown_call, json_into_bool,
+               namespace_components};
 
-/// A type abstracting the functions used for Polly.
+/// How deeply components can call other components by default.
+const DEFAULT_COMPONENT_DEPTH: usize = 64;
+
+/// A filter a variable is passed through with `@name | filter`, taking the variable as text,
+/// and returning the text to write in its place.
+pub type PollyFilter = Box<Fn(&str) -> String + Send + Sync>;
+
+/// A type abstracting the functions used for Polly. Functions are `Send`, and `Sync`, so a
+/// template can be shared between threads.
 pub type PollyFn = Box<Fn(BTreeMap<String, ArgValue>, &Rc<RefCell<Template>>)
-                          -> Result<String, String>>;
+                          -> Result<String, String> + Send + Sync>;
 
 macro_rules! template_try {
     ($result:expr) => {
@@ -302,29 +318,37 @@ pub fn std_functions() -> HashMap<String, PollyFn> {
     map
 }
 
+/// The standard filters available to every template, `upper`, `lower`, and `trim`.
+pub fn std_filters() -> HashMap<String, PollyFilter> {
+    let mut map: HashMap<String, PollyFilter> = HashMap::new();
+
+    map.insert(String::from("upper"), Box::new(|text| text.to_uppercase()));
+    map.insert(String::from("lower"), Box::new(|text| text.to_lowercase()));
+    map.insert(String::from("trim"), Box::new(|text| text.trim().to_owned()));
+    map
+}
+
 /// The Polly template.
 pub struct Template {
+    ast: Vec<Token>,
     components: HashMap<String, Component>,
+    /// The directory imports, and includes are resolved against, which is the directory of
+    /// `file` for a template on disk.
+    directory: Option<PathBuf>,
+    errors: Vec<AstError>,
     file: PathBuf,
+    filters: HashMap<String, PollyFilter>,
     functions: HashMap<String, PollyFn>,
     source: String,
     locales_dir: Option<String>,
+    max_component_depth: usize,
+    parse_options: ParseOptions,
+    render_options: RenderOptions,
     variables: BTreeMap<String, Value>,
 }
 
 
 impl Template {
-    fn add_components(&mut self,
-                      mut components: HashMap<String, Component>)
-                      -> Result<(), TemplateError> {
-        for (key, value) in components.drain() {
-            if let Some(_) = self.components.insert(key, value) {
-                return Err(TemplateError::PreDefinedComponent);
-            }
-        }
-        Ok(())
-    }
-
     fn add_component(&mut self, key: String, value: Component) -> Result<(), TemplateError> {
         if let Some(_) = self.components.insert(key, value) {
             return Err(TemplateError::PreDefinedComponent);
@@ -349,30 +373,99 @@ impl Template {
         self.components.get(name)
     }
 
+    /// Get a filter from within the template.
+    pub fn get_filter(&self, name: &str) -> Option<&PollyFilter> {
+        self.filters.get(name)
+    }
+
     /// Get a function from within the template.
     pub fn get_function(&self, name: &str) -> Option<&PollyFn> {
         self.functions.get(name)
     }
 
-    /// Loads the template from the file path.
+    /// Loads the template from the file path, and parses it. Returns the first error found while
+    /// reading, or parsing the file.
     pub fn load<P: AsRef<Path>>(file_path: P) -> Result<Self, TemplateError> {
-        let source = match Template::read_to_source(file_path.as_ref()) {
+        let path = file_path.as_ref().to_path_buf();
+        let source = match Template::read_to_source(&path) {
             Ok(source) => source,
+            Err(TemplateError::IoError(error)) => return Err(TemplateError::FileError(path, error)),
             Err(error) => return Err(error),
         };
 
-        Ok(Template::new(file_path, source))
+        let directory = Template::directory_of(&path);
+        Template::parse(path, source, Some(directory))
     }
 
     fn new<P: AsRef<Path>, S: Into<String>>(path: P, source: S) -> Self {
-        Template {
+        let directory = Template::directory_of(path.as_ref());
+        Template::compile(path.as_ref().to_path_buf(), source.into(), Some(directory))
+    }
+
+    /// Parses the source. Imports, and includes are resolved against `directory`, and are an
+    /// error without one.
+    fn compile(file: PathBuf, source: String, directory: Option<PathBuf>) -> Self {
+        let mut template = Template {
+            ast: Vec::new(),
             components: HashMap::new(),
-            file: path.as_ref().to_path_buf(),
+            directory: directory,
+            errors: Vec::new(),
+            file: file,
+            filters: std_filters(),
             functions: std_functions(),
-            source: source.into(),
+            source: source,
             locales_dir: Some(String::from("./templates/locales")),
+            max_component_depth: DEFAULT_COMPONENT_DEPTH,
+            parse_options: ParseOptions::default(),
+            render_options: RenderOptions::default(),
             variables: BTreeMap::new(),
+        };
+        template.parse_source();
+        template
+    }
+
+    /// Parses the source with the template's parse options, replacing the AST, and any errors.
+    fn parse_source(&mut self) {
+        let lexemes = Lexer::with_options(&self.source, &self.parse_options).output();
+        let file = match self.directory {
+            Some(_) => Some(self.file.as_path()),
+            None => None,
+        };
+        let void_elements = self.render_options.void_elements.clone();
+        let parser = Parser::with_options(lexemes, file, void_elements, &self.parse_options);
+
+        for (name, component) in parser.get_components() {
+            let _ = self.components.insert(name, component);
         }
+        match parser.result() {
+            Ok(ast) => {
+                self.ast = ast;
+                self.errors = Vec::new();
+            }
+            Err(errors) => {
+                self.ast = Vec::new();
+                self.errors = errors;
+            }
+        }
+    }
+
+    /// Sets the options the template's source is read with, such as which elements are raw,
+    /// parsing the source again.
+    pub fn parse_options(mut self, options: ParseOptions) -> Self {
+        self.parse_options = options;
+        self.parse_source();
+        self
+    }
+
+    /// The options the template's source was read with.
+    pub fn get_parse_options(&self) -> &ParseOptions {
+        &self.parse_options
+    }
+
+    /// The parsed AST of the template, which can be traversed with `walk`. Empty if the source
+    /// had any errors.
+    pub fn ast(&self) -> &[Token] {
+        &self.ast
     }
 
     /// Specify that a template has no locales available.
@@ -381,6 +474,45 @@ impl Template {
         self
     }
 
+    /// Parses a template that isn't on disk. Errors refer to the template as `<anonymous>`, and
+    /// the template can't use `$import`.
+    pub fn from_source(source: &str) -> Result<Self, TemplateError> {
+        Template::from_source_named("<anonymous>", source)
+    }
+
+    /// Parses a template that isn't on disk, where errors refer to the template by `name`. The
+    /// template can't use `$import`, as there is no directory to resolve the path against, and
+    /// has no locales unless they're set with `locales_dir`.
+    pub fn from_source_named(name: &str, source: &str) -> Result<Self, TemplateError> {
+        match Template::parse(PathBuf::from(name), String::from(source), None) {
+            Ok(template) => Ok(template.no_locales()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// The directory `$import` paths are resolved against, for a template at `path`.
+    fn directory_of(path: &Path) -> PathBuf {
+        match path.parent() {
+            Some(directory) => directory.to_path_buf(),
+            None => PathBuf::new(),
+        }
+    }
+
+    /// Compiles the source, returning every error found while parsing it. Used by both `load`,
+    /// and `from_source`, so a template behaves the same wherever its source came from.
+    fn parse(file: PathBuf,
+             source: String,
+             directory: Option<PathBuf>)
+             -> Result<Self, TemplateError> {
+        let template = Template::compile(file, source, directory);
+
+        if template.errors.is_empty() {
+            Ok(template)
+        } else {
+            Err(TemplateError::ParseError(template.file, template.errors))
+        }
+    }
+
     /// Loads the template from the source provided. The file path is also required, for error 
     /// handling
     pub fn load_from_source<P: AsRef<Path>, S: Into<String>>(path: P, source: S) -> Self {
@@ -393,21 +525,128 @@ impl Template {
         self
     }
 
+    /// Replaces every variable with the fields of the JSON object. A value that isn't an object
+    /// is a `DataError`, and leaves the variables as they were.
+    pub fn set_variables(&mut self, value: Value) -> Result<(), TemplateError> {
+        match value {
+            Value::Object(object) => {
+                self.variables = object;
+                Ok(())
+            }
+            _ => {
+                let message = String::from("The variables have to be a JSON object.");
+                Err(TemplateError::DataError(self.file.clone(), message))
+            }
+        }
+    }
+
+    /// Reads a JSON file, replacing every variable with the fields of its root object. The file
+    /// has to contain a single JSON object.
+    pub fn load_data_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), TemplateError> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        let read = match File::open(path) {
+            Ok(mut file) => file.read_to_string(&mut contents),
+            Err(error) => Err(error),
+        };
+        if let Err(error) = read {
+            return Err(TemplateError::FileError(path.to_path_buf(), error));
+        }
+
+        match serde_json::from_str(&*contents) {
+            Ok(Value::Object(object)) => {
+                self.variables = object;
+                Ok(())
+            }
+            Ok(_) => {
+                let message = String::from("The root of the data has to be a JSON object.");
+                Err(TemplateError::DataError(path.to_path_buf(), message))
+            }
+            Err(error) => Err(TemplateError::DataError(path.to_path_buf(), error.to_string())),
+        }
+    }
+
+    /// Sets a single variable, replacing any variable with the same name.
+    pub fn set_variable(&mut self, key: &str, value: Value) {
+        let _ = self.variables.insert(String::from(key), value);
+    }
+
+    /// Sets how deeply components can call other components before rendering stops with a
+    /// `RecursiveComponent` error. Defaults to 64.
+    pub fn component_depth(mut self, depth: usize) -> Self {
+        self.max_component_depth = depth;
+        self
+    }
+
+    /// How deeply components can call other components.
+    pub fn max_component_depth(&self) -> usize {
+        self.max_component_depth
+    }
+
+    /// Sets the options used when rendering the template. The source is parsed again when the
+    /// void elements change, as giving a void element children is an error when parsing.
+    pub fn render_options(mut self, options: RenderOptions) -> Self {
+        let reparse = options.void_elements != self.render_options.void_elements;
+        self.render_options = options;
+        if reparse {
+            self.parse_source();
+        }
+        self
+    }
+
+    /// The options used when rendering the template.
+    pub fn get_render_options(&self) -> &RenderOptions {
+        &self.render_options
+    }
+
     /// Override the default locales directory.
     pub fn locales_dir<S: Into<String>>(mut self, locales_dir: S) -> Self {
         self.locales_dir = Some(locales_dir.into());
         self
     }
 
-    /// Registers a function to the template.
+    /// Registers a function to the template. Returns `PreDefinedFunction`, leaving the existing
+    /// function in place, if a function, including a standard function, already has the name.
     pub fn register(&mut self, name: String, function: PollyFn) -> Result<(), TemplateError> {
-        if let Some(_) = self.functions.insert(name, function) {
+        if self.functions.contains_key(&name) {
             Err(TemplateError::PreDefinedFunction)
         } else {
+            self.functions.insert(name, function);
             Ok(())
         }
     }
 
+    /// Registers a closure as a function, boxing it in place of the caller.
+    pub fn register_function<S, F>(&mut self, name: S, function: F) -> Result<(), TemplateError>
+        where S: Into<String>,
+              F: Fn(BTreeMap<String, ArgValue>, &Rc<RefCell<Template>>) -> Result<String, String>,
+              F: Send + Sync + 'static
+    {
+        self.register(name.into(), Box::new(function))
+    }
+
+    /// Registers a filter to the template. Returns `PreDefinedFilter`, leaving the existing
+    /// filter in place, if a filter, including a standard filter, already has the name.
+    pub fn register_filter<S, F>(&mut self, name: S, filter: F) -> Result<(), TemplateError>
+        where S: Into<String>,
+              F: Fn(&str) -> String + Send + Sync + 'static
+    {
+        let name = name.into();
+        if self.filters.contains_key(&name) {
+            Err(TemplateError::PreDefinedFilter)
+        } else {
+            self.filters.insert(name, Box::new(filter));
+            Ok(())
+        }
+    }
+
+    /// The names of the functions the template can call, in alphabetical order.
+    pub fn functions(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(|name| &**name).collect();
+        names.sort();
+        names
+    }
+
     /// Imports components from another template.
     pub fn import<P: AsRef<Path>>(&mut self, path: P) -> Result<(), TemplateError> {
         match Template::read_to_source(path) {
@@ -423,8 +662,89 @@ impl Template {
         }
     }
 
+    /// Loads every `.polly`, or `.poly` file within the directory, and its subdirectories, as
+    /// components. The components are namespaced by the file's path relative to the directory,
+    /// so `&text` in `forms/input.polly` is called with `&forms.input.text`. Returns the number
+    /// of components loaded.
+    pub fn load_components_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, TemplateError> {
+        let mut files = Vec::new();
+        if let Err(error) = Template::find_component_files(path.as_ref(), &mut files) {
+            return Err(error);
+        }
+
+        let mut count = 0;
+        for file in files {
+            let source = match Template::read_to_source(&file) {
+                Ok(source) => source,
+                Err(TemplateError::IoError(error)) => {
+                    return Err(TemplateError::FileError(file, error))
+                }
+                Err(error) => return Err(error),
+            };
+            let lexemes = Lexer::with_options(&*source, &self.parse_options).output();
+            let components = match Parser::parse_components(lexemes) {
+                Ok(components) => components,
+                Err(errors) => {
+                    return Err(TemplateError::ComponentFileError(file, errors[0].clone()))
+                }
+            };
+
+            let relative = match file.strip_prefix(path.as_ref()) {
+                Ok(relative) => relative.with_extension(""),
+                Err(_) => file.with_extension(""),
+            };
+            let namespace = relative.components()
+                                    .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                                    .collect::<Vec<_>>()
+                                    .join(".");
+
+            for (name, component) in namespace_components(components, &namespace) {
+                if self.components.contains_key(&name) {
+                    return Err(TemplateError::DuplicateComponent(file, name));
+                }
+                let _ = self.components.insert(name, component);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Collects every `.polly`, or `.poly` file within the directory, sorted so they load in the
+    /// same order. A directory that can't be read is an error with its path.
+    fn find_component_files(directory: &Path,
+                            files: &mut Vec<PathBuf>)
+                            -> Result<(), TemplateError> {
+        let mut paths = Vec::new();
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(error) => return Err(TemplateError::FileError(directory.to_path_buf(), error)),
+        };
+        for entry in entries {
+            match entry {
+                Ok(entry) => paths.push(entry.path()),
+                Err(error) => return Err(TemplateError::FileError(directory.to_path_buf(), error)),
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                if let Err(error) = Template::find_component_files(&path, files) {
+                    return Err(error);
+                }
+            } else if path.extension() == Some(OsStr::new("polly")) ||
+                      path.extension() == Some(OsStr::new("poly")) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
     fn read_to_source<P: AsRef<Path>>(path: P) -> Result<String, TemplateError> {
-        let mut file = File::open(path.as_ref()).unwrap();
+        let mut file = match File::open(path.as_ref()) {
+            Ok(file) => file,
+            Err(error) => return Err(TemplateError::IoError(error)),
+        };
         let mut contents = String::new();
         match file.read_to_string(&mut contents) {
             Ok(_) => Ok(contents),
@@ -433,22 +753,47 @@ impl Template {
     }
 
     /// Renders the template into a HTML String.
-    pub fn render(mut self, lang: &str) -> Result<String, TemplateError> {
-        let output = {
-            let parser = Parser::new(Lexer::new(&self.source).output());
-            if let Err(error) = self.add_components(parser.get_components()) {
-                return Err(error);
-            };
-            parser.output()
-        };
-        let file_name = self.file.file_name().unwrap().to_str().unwrap().to_owned();
+    pub fn render(self, lang: &str) -> Result<String, TemplateError> {
+        let mut html = Vec::new();
+        if let Err(error) = self.render_to(lang, &mut html) {
+            return Err(error);
+        }
+
+        match String::from_utf8(html) {
+            Ok(html) => Ok(html),
+            Err(error) => Err(TemplateError::CodegenError(CodegenError::FromUtf8Error(error))),
+        }
+    }
 
-        let locales_dir = match self.locales_dir {
-            Some(ref locales_dir) => locales_dir.clone(),
-            None => String::new(),
+    /// Renders the template with the options, in place of those set by `render_options`.
+    pub fn render_with(self,
+                       lang: &str,
+                       options: &RenderOptions)
+                       -> Result<String, TemplateError> {
+        self.render_options(options.clone()).render(lang)
+    }
+
+    /// Renders the template into the writer, writing the HTML as it's generated. Errors from the
+    /// writer are returned as `CodegenError::IoError`.
+    pub fn render_to<W: io::Write>(mut self,
+                                   lang: &str,
+                                   writer: &mut W)
+                                   -> Result<(), TemplateError> {
+        if let Some(error) = self.errors.first() {
+            return Err(TemplateError::AstError(error.clone()));
+        }
+
+        let output = mem::replace(&mut self.ast, Vec::new());
+
+        // A template named without a file, such as `..`, has no locale file to look for.
+        let locales = match (&self.locales_dir, self.file.file_name()) {
+            (&Some(ref locales_dir), Some(file_name)) if !locales_dir.is_empty() => {
+                Some((locales_dir.clone(), file_name.to_string_lossy().into_owned()))
+            }
+            _ => None,
         };
 
-        if !locales_dir.is_empty() {
+        if let Some((locales_dir, file_name)) = locales {
             let path = format!("{dir}/{lang}/{file}",
                                dir = locales_dir,
                                lang = lang,
@@ -462,21 +807,36 @@ impl Template {
                         };
                     }
                 }
-                error => return error,
+                Err(error) => return Err(error),
+            }
+        }
+
+        if self.render_options.strict {
+            let mut unknown = find_unknown_call(&output, &self);
+            for component in self.components.values() {
+                if unknown.is_none() {
+                    unknown = find_unknown_call(&component.ast(), &self);
+                }
+            }
+            if let Some(error) = unknown {
+                return Err(TemplateError::CodegenError(error));
             }
         }
 
         let variables = self.variables.to_owned();
 
         let mut codegen = Codegen::new(output, variables, Rc::new(RefCell::new(self)));
-        match codegen.generate_html() {
-            Ok(html) => Ok(html),
+        match codegen.write_html(writer) {
+            Ok(()) => Ok(()),
             Err(error) => Err(TemplateError::CodegenError(error)),
         }
     }
     /// Renders the component, or panics if there is an error.
     pub fn unwrap_render(self, locale: &str) -> String {
-        let file_name = self.file.file_name().unwrap().to_str().unwrap().to_owned();
+        let file_name = match self.file.file_name() {
+            Some(file_name) => file_name.to_string_lossy().into_owned(),
+            None => self.file.to_string_lossy().into_owned(),
+        };
         let source = self.source.clone();
         match self.render(locale) {
             Ok(html) => html,
@@ -488,90 +848,144 @@ impl Template {
     }
 
     fn render_error(error: TemplateError, source: String, file_name: String) {
-        if let TemplateError::CodegenError(CodegenError::AstError(error)) = error {
-            const SPACE: usize = 1;
-            const CONSOLE_WIDTH: usize = 89;
-            if error == AstError::Eof {
+        let error = match error {
+            TemplateError::AstError(error) |
+            TemplateError::CodegenError(CodegenError::AstError(error)) => error,
+            error => {
+                println!("{:?}", error);
                 return;
             }
-            let (index, token_length) = error.values();
-            let mut line_number: usize = 0;
-            let mut col_number: usize = 1;
-
-            for ch in source[..index].chars() {
-                col_number += 1;
-                if ch == '\n' {
-                    line_number += 1;
-                    col_number = 1;
-                }
-            }
-
-            let mut section = String::new();
-            for ch in source[..index].chars().rev() {
-                if ch == '\n' {
-                    section = section.chars().rev().collect();
-                    break;
-                } else {
-                    section.push(ch);
-                }
+        };
+        const SPACE: usize = 1;
+        const CONSOLE_WIDTH: usize = 89;
+        if error == AstError::Eof {
+            return;
+        }
+        let (index, token_length) = error.values();
+        let position = error.position();
+        let col_number = position.column;
+
+        let mut section = String::new();
+        for ch in source[..index].chars().rev() {
+            if ch == '\n' {
+                section = section.chars().rev().collect();
+                break;
+            } else {
+                section.push(ch);
             }
+        }
 
-            let file_name_print = format!("{}:{}:{}:", file_name, line_number, col_number);
+        let file_name_print = format!("{}:{}:{}:", file_name, position.line, col_number);
 
-            for ch in source[index..].chars() {
-                if ch == '\n' || (section.len() + SPACE + file_name_print.len()) == CONSOLE_WIDTH {
-                    break;
-                } else {
-                    section.push(ch);
-                }
+        for ch in source[index..].chars() {
+            if ch == '\n' || (section.len() + SPACE + file_name_print.len()) == CONSOLE_WIDTH {
+                break;
+            } else {
+                section.push(ch);
             }
-            println!("");
-            let mut underline = String::from("^");
+        }
+        println!("");
+        let mut underline = String::from("^");
 
-            for _ in 1..token_length {
-                underline.push('~');
-            }
-            println!("{} {}", file_name_print, error);
-            println!("{} {}", file_name_print, section.trim());
-            println!("{0:>1$}",
-                     underline,
-                     SPACE + col_number + file_name_print.len() -
-                     (section.len() - section.trim().len()));
-        } else {
-            println!("{:?}", error);
+        for _ in 1..token_length {
+            underline.push('~');
         }
+        println!("{} {}", file_name_print, error);
+        println!("{} {}", file_name_print, section.trim());
+        println!("{0:>1$}",
+                 underline,
+                 SPACE + col_number + file_name_print.len() -
+                 (section.len() - section.trim().len()));
     }
 }
 /// Errors relating to the templating rendering.
 #[derive(Debug)]
 pub enum TemplateError {
+    /// Error found while parsing the template.
+    AstError(AstError),
     /// Error within the Code generation.
     CodegenError(CodegenError),
+    /// Error found while parsing a file of components, with the path of the file.
+    ComponentFileError(PathBuf, AstError),
+    /// A component from the file has the same name as one already loaded.
+    DuplicateComponent(PathBuf, String),
     /// The component called already exists.
     PreDefinedComponent,
+    /// The filter registered already exists.
+    PreDefinedFilter,
     /// The function called already exists.
     PreDefinedFunction,
     /// Any IO errors, from the methods.
     IoError(io::Error),
+    /// The template file couldn't be read, with the path of the file.
+    FileError(PathBuf, io::Error),
+    /// Every error found while parsing the template file, with the path of the file.
+    ParseError(PathBuf, Vec<AstError>),
+    /// The data file wasn't a JSON object, with the path of the file, and the reason.
+    DataError(PathBuf, String),
+}
+
+impl error::Error for TemplateError {
+    fn description(&self) -> &str {
+        match *self {
+            TemplateError::AstError(_) => "Error found while parsing the template.",
+            TemplateError::CodegenError(_) => "Error found while rendering the template.",
+            TemplateError::ComponentFileError(_, _) => "Error found while parsing components.",
+            TemplateError::DuplicateComponent(_, _) => "The component is already defined.",
+            TemplateError::PreDefinedComponent => "The component is already defined.",
+            TemplateError::PreDefinedFilter => "The filter is already defined.",
+            TemplateError::PreDefinedFunction => "The function is already defined.",
+            TemplateError::IoError(_) => "Couldn't read the file.",
+            TemplateError::FileError(_, _) => "Couldn't read the template file.",
+            TemplateError::ParseError(_, _) => "Errors found while parsing the template file.",
+            TemplateError::DataError(_, _) => "Couldn't load the data file.",
+        }
+    }
 }
-fn json_into_bool(json: &Value) -> bool {
-    match *json {
-        Value::Array(ref array) => !array.is_empty(),
-        Value::Null => false,
-        Value::Bool(ref boolean) => *boolean,
-        Value::I64(ref num) => *num != 0,
-        Value::U64(ref num) => *num != 0,
-        Value::F64(ref num) => *num != 0.0,
-        Value::String(ref string) => !string.is_empty(),
-        Value::Object(ref object) => !object.is_empty(),
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use std::error::Error;
+        match *self {
+            TemplateError::AstError(ref error) => write!(f, "{}", error),
+            TemplateError::CodegenError(ref error) => write!(f, "{}", error),
+            TemplateError::ComponentFileError(ref path, ref error) => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+            TemplateError::DuplicateComponent(ref path, ref name) => {
+                write!(f, "{}: {} Got '{}'.", path.display(), self.description(), name)
+            }
+            TemplateError::IoError(ref error) => write!(f, "{} {}", self.description(), error),
+            TemplateError::FileError(ref path, ref error) => {
+                write!(f, "{}: {} {}", path.display(), self.description(), error)
+            }
+            TemplateError::DataError(ref path, ref message) => {
+                write!(f, "{}: {} {}", path.display(), self.description(), message)
+            }
+            TemplateError::ParseError(ref path, ref errors) => {
+                for (index, error) in errors.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { "\n" };
+                    if let Err(error) = write!(f, "{}{}: {}", separator, path.display(), error) {
+                        return Err(error);
+                    }
+                }
+                Ok(())
+            }
+            _ => write!(f, "{}", self.description()),
+        }
     }
 }
 #[allow(dead_code, unused_imports)]
 mod tests {
-    use super::Template;
+    use super::{Template, TemplateError};
+    use compiler::{ArgValue, AstError, CodegenError, Lexeme, ParseOptions, Position,
+                   RenderOptions};
     use std::fs::File;
-    use std::io::Read;
+    use std::io;
+    use std::io::{Read, Write};
     use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::thread;
     use serde_json;
     use serde_json::Value;
 
@@ -586,6 +1000,34 @@ mod tests {
                    BASIC);
     }
 
+    #[test]
+    fn load_parses_file() {
+        let template = Template::load("./tests/element.polly").unwrap();
+
+        assert_eq!(template.ast.len(), 2);
+        assert!(template.errors.is_empty());
+    }
+
+    #[test]
+    fn load_missing_file() {
+        match Template::load("./tests/does_not_exist.polly") {
+            Err(error @ TemplateError::FileError(_, _)) => {
+                assert!(error.to_string().starts_with("./tests/does_not_exist.polly: "))
+            }
+            _ => panic!("Expected a FileError"),
+        }
+    }
+
+    #[test]
+    fn load_invalid_file() {
+        match Template::load("./tests/invalid.polly") {
+            Err(error @ TemplateError::ParseError(_, _)) => {
+                assert!(error.to_string().starts_with("./tests/invalid.polly: "))
+            }
+            _ => panic!("Expected a ParseError"),
+        }
+    }
+
     #[test]
     fn component() {
         let json: Value = serde_json::from_str(r#"{"world": "World"}"#).unwrap();
@@ -618,78 +1060,1844 @@ mod tests {
     }
 
     #[test]
-    fn variable_inside_another_variable() {
-        let json: Value = serde_json::from_str(r#"{"object": {"world": "World"}}"#).unwrap();
+    fn nested_elements() {
+        let json: Value = serde_json::from_str(r#"{"name": "World"}"#).unwrap();
 
-        let template = Template::load("./tests/variable_inside.polly")
-                           .unwrap()
+        let template = Template::load_from_source("nested",
+                                                  "/div.outer.wide#main{/p.text{Hello @name!}}")
                            .no_locales()
                            .json(json.as_object().unwrap().to_owned());
-        assert_eq!(template.unwrap_render("en"), BASIC);
+        assert_eq!(template.unwrap_render("en"),
+                   "<div class=\"outer wide\" id=\"main\"><p class=\"text\">Hello \
+                    World!</p></div>");
     }
 
     #[test]
-    fn function_each() {
-        let expected = "<!DOCTYPE \
-                        html><html><body><ul><li>Rust</li><li>C++</li><li>JavaScript</li></ul></bo\
-                        dy></html>";
-        let json: Value = serde_json::from_str(r#"{"array": ["Rust", "C++", "JavaScript"]}"#)
-                              .unwrap();
+    fn void_element() {
+        let template = Template::load_from_source("void", r#"/p{/img(src="image.jpg")/br}"#)
+                           .no_locales();
 
-        assert_eq!(Template::load("./tests/function_each.polly")
-                       .unwrap()
-                       .no_locales()
-                       .json(json.as_object().unwrap().to_owned())
-                       .unwrap_render("en"),
-                   expected);
+        assert_eq!(template.unwrap_render("en"), r#"<p><img src="image.jpg"><br></p>"#);
+    }
+
+    #[test]
+    fn void_element_with_children() {
+        let template = Template::load_from_source("void", "/br{Hello}").no_locales();
 
+        match template.render("en") {
+            Err(TemplateError::AstError(AstError::VoidElementWithChildren(_, ref tag))) => {
+                assert_eq!(tag, "br")
+            }
+            result => panic!("Expected a VoidElementWithChildren error, got: {:?}", result),
+        }
     }
 
     #[test]
-    fn function_if() {
-        let json: Value = serde_json::from_str(r#"{"condition": true, "text": "Hello World!"}"#)
-                              .unwrap();
+    fn xhtml_void_element() {
+        let options = RenderOptions { xhtml: true, ..RenderOptions::default() };
+        let template = Template::load_from_source("void", r#"/p{/img(src="image.jpg")/br}"#)
+                           .no_locales()
+                           .render_options(options);
 
-        assert_eq!(Template::load("./tests/function_if.polly")
-                       .unwrap()
+        assert_eq!(template.unwrap_render("en"), r#"<p><img src="image.jpg" /><br /></p>"#);
+    }
+
+    #[test]
+    fn custom_void_elements() {
+        let options = RenderOptions {
+            void_elements: vec![String::from("circle")],
+            ..RenderOptions::default()
+        };
+        let template = Template::load_from_source("void", r#"/svg{/circle(r="4")/br{Hello}}"#)
+                           .no_locales()
+                           .render_options(options);
+
+        assert_eq!(template.unwrap_render("en"), r#"<svg><circle r="4"><br>Hello</br></svg>"#);
+
+        let options = RenderOptions { void_elements: Vec::new(), ..RenderOptions::default() };
+        let html = Template::load_from_source("void", "/br{Hi}")
                        .no_locales()
-                       .json(json.as_object().unwrap().to_owned())
-                       .unwrap_render("en"),
-                   BASIC);
+                       .render_with("en", &options);
+        assert_eq!(html.unwrap(), "<br>Hi</br>");
+    }
 
+    #[test]
+    fn missing_variable() {
+        let template = Template::load_from_source("missing", "/p{Hello @name!}").no_locales();
+
+        match template.render("en") {
+            Err(TemplateError::CodegenError(error @ CodegenError::MissingVariable(..))) => {
+                assert_eq!(error.span().unwrap().start, Position::new(1, 10, 9));
+                assert_eq!(error.to_string(),
+                           "Variable doesn't exist in the JSON passed in:  NAME: name AT: line 1, \
+                            column 10");
+            }
+            result => panic!("Expected a MissingVariable error, got: {:?}", result),
+        }
     }
 
     #[test]
-    fn function_if_else() {
-        let json: Value = serde_json::from_str(r#"{"condition": false, "text": "Hello World!"}"#)
-                              .unwrap();
+    fn conditional() {
+        let source = "/p{@if logged_in {Welcome back!} @else {Please log in.}}";
+        let json: Value = serde_json::from_str(r#"{"logged_in": true}"#).unwrap();
 
-        assert_eq!(Template::load("./tests/function_if_else.polly")
-                       .unwrap()
-                       .no_locales()
-                       .json(json.as_object().unwrap().to_owned())
-                       .unwrap_render("en"),
-                   BASIC);
+        let template = Template::load_from_source("conditional", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p>Welcome back!</p>");
+
+        let template = Template::load_from_source("conditional", source).no_locales();
+        assert_eq!(template.unwrap_render("en"), "<p>Please log in.</p>");
+    }
+
+    #[test]
+    fn else_if_chain() {
+        let source = "@if @count > 10 {Many} @elseif @count > 1 {Some} @else if @count {One} \
+                      @else {None}";
+        let render = |count: u64| {
+            let mut json = BTreeMap::new();
+            json.insert(String::from("count"), Value::U64(count));
+            Template::load_from_source("else_if", source)
+                .no_locales()
+                .json(json)
+                .unwrap_render("en")
+        };
 
+        assert_eq!(render(20), "Many");
+        assert_eq!(render(5), "Some");
+        assert_eq!(render(1), "One");
+        assert_eq!(render(0), "None");
     }
 
+    #[test]
+    fn unless() {
+        let source = "/p{@unless logged_in {Please log in.} @else {Welcome back!}}";
+        let json: Value = serde_json::from_str(r#"{"logged_in": true}"#).unwrap();
+
+        let template = Template::load_from_source("unless", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p>Welcome back!</p>");
+
+        let template = Template::load_from_source("unless", source).no_locales();
+        assert_eq!(template.unwrap_render("en"), "<p>Please log in.</p>");
+    }
 
     #[test]
-    fn locales() {
-        let json: BTreeMap<String, Value> = BTreeMap::new();
+    fn switch() {
+        let source = "/p{@switch @status {\n@case \"active\" {Active}\n@case 0 {Banned}\n\
+                      @default {Unknown}\n}}";
+        let render = |json: &str| {
+            let json: Value = serde_json::from_str(json).unwrap();
+            Template::load_from_source("switch", source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .unwrap_render("en")
+        };
 
-        assert_eq!(Template::load("./tests/locales.polly")
-                       .unwrap()
-                       .locales_dir("./tests/locales/")
-                       .json(json.to_owned())
-                       .unwrap_render("en"),
-                   BASIC);
-        assert_eq!(Template::load("./tests/locales.polly")
-                       .unwrap()
-                       .locales_dir("./tests/locales/")
-                       .json(json)
-                       .unwrap_render("de"),
-                   BASIC_DE);
+        assert_eq!(render(r#"{"status": "active"}"#), "<p>Active</p>");
+        assert_eq!(render(r#"{"status": 0}"#), "<p>Banned</p>");
+        assert_eq!(render(r#"{"status": "0"}"#), "<p>Unknown</p>");
+        assert_eq!(render("{}"), "<p>Unknown</p>");
+    }
+
+    #[test]
+    fn switch_compares_numbers_by_value() {
+        let source = "@switch @n {@case 1 {One} @default {Other}}";
+        let mut template = Template::load_from_source("switch", source).no_locales();
+        template.set_variable("n", Value::I64(1));
+        assert_eq!(template.unwrap_render("en"), "One");
+        template.set_variable("n", Value::F64(1.0));
+        assert_eq!(template.unwrap_render("en"), "One");
+        template.set_variable("n", Value::I64(2));
+        assert_eq!(template.unwrap_render("en"), "Other");
+    }
+
+    #[test]
+    fn conditional_truthiness() {
+        let json: Value = serde_json::from_str(r#"{"values": [false, null, 0, 0.0, "", [], {},
+                                                               true, 1, "0", [0], {"a": 0}]}"#)
+                              .unwrap();
+        let source = "@each value in @values {@if value {T} @else {F}}";
+        let template = Template::load_from_source("truthiness", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "FFFFFFFTTTTT");
     }
 
+    #[test]
+    fn nested_conditionals() {
+        let json: Value = serde_json::from_str(r#"{"users": [{"name": "Jane", "admin": true},
+                                                              {"name": "Joe", "admin": false}]}"#)
+                              .unwrap();
+        let source = "/ul{@each user in @users {@if user {/li{@user.name@if user.admin { \
+                      admin} @else {@if user.name { user}}}}}}";
+        let template = Template::load_from_source("nested", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<ul><li>Jane admin</li><li>Joe user</li></ul>");
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        let json: Value = serde_json::from_str(r#"{"count": 5, "user": {"role": "admin",
+                                                                       "active": true}}"#)
+                              .unwrap();
+        let render = |condition: &str| {
+            let source = format!("@if {} {{Yes}} @else {{No}}", condition);
+            Template::load_from_source("expression", &source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .render("en")
+        };
+
+        assert_eq!(render("@count > 3").unwrap(), "Yes");
+        assert_eq!(render("@count <= 3").unwrap(), "No");
+        assert_eq!(render("@count == 5.0").unwrap(), "Yes");
+        assert_eq!(render(r#"@user.role == "admin" && @user.active"#).unwrap(), "Yes");
+        assert_eq!(render(r#"@user.role != "admin" || !@user.active"#).unwrap(), "No");
+        assert_eq!(render("!(@count > 3 && @missing)").unwrap(), "Yes");
+        assert_eq!(render("@missing > 3").unwrap(), "No");
+        assert_eq!(render("@missing == null").unwrap(), "Yes");
+
+        match render(r#"@count > "3""#) {
+            Err(TemplateError::CodegenError(CodegenError::InvalidComparison { left,
+                                                                               right,
+                                                                               .. })) => {
+                assert_eq!((left, right), ("number", "string"))
+            }
+            result => panic!("Expected an InvalidComparison error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn strict_conditional() {
+        let source = "/p{@if missing {Shown} @else {Hidden}}";
+        let template = Template::load_from_source("strict", source).no_locales();
+        assert_eq!(template.unwrap_render("en"), "<p>Hidden</p>");
+
+        let options = RenderOptions { strict: true, ..RenderOptions::default() };
+        let template = Template::load_from_source("strict", source).no_locales();
+        match template.render_with("en", &options) {
+            Err(TemplateError::CodegenError(CodegenError::MissingVariable(_, ref name, _))) => {
+                assert_eq!(name, "missing")
+            }
+            result => panic!("Expected a MissingVariable error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn each_loop() {
+        let json: Value = serde_json::from_str(r#"{"list": ["Rust", "C++", "JavaScript"]}"#)
+                              .unwrap();
+
+        let template = Template::load_from_source("loop", "/ul{@each item in @list {/li{@item}}}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<ul><li>Rust</li><li>C++</li><li>JavaScript</li></ul>");
+    }
+
+    #[test]
+    fn strict_unknown_calls() {
+        let render = |source: &str, options: &RenderOptions| {
+            Template::load_from_source("strict", source).no_locales().render_with("en", options)
+        };
+        let lenient = RenderOptions::default();
+        let strict = RenderOptions { strict: true, ..RenderOptions::default() };
+        let hidden = "/p{Hi}@if show {&nope()}";
+
+        assert_eq!(render(hidden, &lenient).unwrap(), "<p>Hi</p>");
+        match render(hidden, &strict) {
+            Err(TemplateError::CodegenError(CodegenError::NoSuchComponent(span, ref name))) => {
+                assert_eq!(name, "nope");
+                assert_eq!(span.unwrap().start, Position::new(1, 17, 16));
+            }
+            result => panic!("Expected a NoSuchComponent error, got: {:?}", result),
+        }
+        for options in &[&lenient, &strict] {
+            match render("&nope()", options) {
+                Err(TemplateError::CodegenError(CodegenError::NoSuchComponent(_, ref name))) => {
+                    assert_eq!(name, "nope")
+                }
+                result => panic!("Expected a NoSuchComponent error, got: {:?}", result),
+            }
+        }
+
+        match render("&card{x}&card{@unless show {$nope()}}", &strict) {
+            Err(TemplateError::CodegenError(CodegenError::NoSuchFunction(_, ref name))) => {
+                assert_eq!(name, "nope")
+            }
+            result => panic!("Expected a NoSuchFunction error, got: {:?}", result),
+        }
+        assert_eq!(render("&card{/b{$children()}}/p{&card{Hi}}", &strict).unwrap(),
+                   "<p><b>Hi</b></p>");
+    }
+
+    #[test]
+    fn each_loop_position() {
+        let json: Value = serde_json::from_str(r#"{"item": "Outer", "list": ["A", "B", "C"]}"#)
+                              .unwrap();
+        let source = "/ul{@each item in @list {/li(title=\"@loop.index\"){@item\
+                      @if loop.first { first}@if loop.last { last}}}}/p{@item}";
+
+        let template = Template::load_from_source("loop", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<ul><li title=\"0\">A first</li><li title=\"1\">B</li><li title=\"2\">C \
+                    last</li></ul><p>Outer</p>");
+    }
+
+    #[test]
+    fn each_loop_else() {
+        let source = "/ul{@each item in @list {/li{@item}} @else {/li{No results}}}";
+        let render = |json: &str, options: &RenderOptions| {
+            let json: Value = serde_json::from_str(json).unwrap();
+            Template::load_from_source("loop", source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .render_with("en", options)
+        };
+        let lenient = RenderOptions::default();
+        let strict = RenderOptions { strict: true, ..RenderOptions::default() };
+        let empty = "<ul><li>No results</li></ul>";
+
+        for options in &[&lenient, &strict] {
+            assert_eq!(render(r#"{"list": ["Rust"]}"#, options).unwrap(),
+                       "<ul><li>Rust</li></ul>");
+            assert_eq!(render(r#"{"list": []}"#, options).unwrap(), empty);
+            assert_eq!(render(r#"{"list": null}"#, options).unwrap(), empty);
+        }
+        assert_eq!(render("{}", &lenient).unwrap(), empty);
+        match render("{}", &strict) {
+            Err(TemplateError::CodegenError(CodegenError::MissingVariable(_, ref name, _))) => {
+                assert_eq!(name, "list")
+            }
+            result => panic!("Expected a MissingVariable error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn each_loop_object() {
+        let json: Value = serde_json::from_str(r#"{"settings": {"theme": "dark", "lang": "en"}}"#)
+                              .unwrap();
+        let source = "@each key, value in @settings {/tr{/td{@loop.index}/td{@key}/td{@value}}}\
+                      @each value in @settings {@value@if !loop.last {;}}";
+
+        let template = Template::load_from_source("loop", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<tr><td>0</td><td>lang</td><td>en</td></tr><tr><td>1</td><td>theme</td>\
+                    <td>dark</td></tr>en;dark");
+    }
+
+    #[test]
+    fn each_loop_index() {
+        let json: Value = serde_json::from_str(r#"{"list": ["A", "B"]}"#).unwrap();
+
+        let template = Template::load_from_source("loop", "@each i, item in @list {@i@item}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "0A1B");
+    }
+
+    #[test]
+    fn each_loop_range() {
+        let render = |source: &str, json: &str| {
+            let json: Value = serde_json::from_str(json).unwrap();
+            Template::load_from_source("range", source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .render("en")
+        };
+
+        assert_eq!(render("@each i in 1..5 {@i}", "{}").unwrap(), "12345");
+        assert_eq!(render("@each i in -1..1 {[@i]}", "{}").unwrap(), "[-1][0][1]");
+        assert_eq!(render("@each page in 1..@pages {/a{@page}}", r#"{"pages": 3}"#).unwrap(),
+                   "<a>1</a><a>2</a><a>3</a>");
+        assert_eq!(render("@each i in 3..@end {@i} @else {None}", r#"{"end": 1}"#).unwrap(),
+                   "None");
+        assert_eq!(render("@each i in 1..@end {@i} @else {None}", "{}").unwrap(), "None");
+        assert_eq!(render("@each i in 1..3 {@i@if loop.last {!}}", "{}").unwrap(), "123!");
+
+        match render("@each i in 1..@end {@i}", r#"{"end": "3"}"#) {
+            Err(TemplateError::CodegenError(CodegenError::NotAnInteger(_, ref bound, kind))) => {
+                assert_eq!(bound, "@end");
+                assert_eq!(kind, "string");
+            }
+            result => panic!("Expected a NotAnInteger error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn each_loop_not_iterable() {
+        let json: Value = serde_json::from_str(r#"{"list": "Rust"}"#).unwrap();
+
+        let template = Template::load_from_source("loop", "/ul{@each item in @list {/li{@item}}}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::NotIterable(span, ref name, kind))) => {
+                assert_eq!(name, "list");
+                assert_eq!(kind, "string");
+                assert_eq!(span.unwrap().start, Position::new(1, 5, 4));
+            }
+            result => panic!("Expected a NotIterable error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn variable_inside_another_variable() {
+        let json: Value = serde_json::from_str(r#"{"object": {"world": "World"}}"#).unwrap();
+
+        let template = Template::load("./tests/variable_inside.polly")
+                           .unwrap()
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), BASIC);
+    }
+
+    #[test]
+    fn function_each() {
+        let expected = "<!DOCTYPE \
+                        html><html><body><ul><li>Rust</li><li>C++</li><li>JavaScript</li></ul></bo\
+                        dy></html>";
+        let json: Value = serde_json::from_str(r#"{"array": ["Rust", "C++", "JavaScript"]}"#)
+                              .unwrap();
+
+        assert_eq!(Template::load("./tests/function_each.polly")
+                       .unwrap()
+                       .no_locales()
+                       .json(json.as_object().unwrap().to_owned())
+                       .unwrap_render("en"),
+                   expected);
+
+    }
+
+    #[test]
+    fn function_if() {
+        let json: Value = serde_json::from_str(r#"{"condition": true, "text": "Hello World!"}"#)
+                              .unwrap();
+
+        assert_eq!(Template::load("./tests/function_if.polly")
+                       .unwrap()
+                       .no_locales()
+                       .json(json.as_object().unwrap().to_owned())
+                       .unwrap_render("en"),
+                   BASIC);
+
+    }
+
+    #[test]
+    fn function_if_else() {
+        let json: Value = serde_json::from_str(r#"{"condition": false, "text": "Hello World!"}"#)
+                              .unwrap();
+
+        assert_eq!(Template::load("./tests/function_if_else.polly")
+                       .unwrap()
+                       .no_locales()
+                       .json(json.as_object().unwrap().to_owned())
+                       .unwrap_render("en"),
+                   BASIC);
+
+    }
+
+
+    #[test]
+    fn locales() {
+        let json: BTreeMap<String, Value> = BTreeMap::new();
+
+        assert_eq!(Template::load("./tests/locales.polly")
+                       .unwrap()
+                       .locales_dir("./tests/locales/")
+                       .json(json.to_owned())
+                       .unwrap_render("en"),
+                   BASIC);
+        assert_eq!(Template::load("./tests/locales.polly")
+                       .unwrap()
+                       .locales_dir("./tests/locales/")
+                       .json(json)
+                       .unwrap_render("de"),
+                   BASIC_DE);
+    }
+
+
+    #[test]
+    fn bare_component_calls() {
+        fn render(call: &str) -> String {
+            let source = format!("&header {{/h1{{Title}}}}\n{}", call);
+            Template::load_from_source("components", &source).no_locales().unwrap_render("en")
+        }
+
+        assert_eq!(render("&header"), render("&header()"));
+        assert_eq!(render("/div{&header}"), render("/div{&header()}"));
+        assert_eq!(render("/div&header"), render("/div&header()"));
+        assert_eq!(render("/div{&header}"), "<div><h1>Title</h1></div>");
+    }
+
+    #[test]
+    fn escaped_attributes() {
+        let source = r#"/a(title="a \"quoted\" & <b>")"#;
+        let template = Template::load_from_source("escaped", source).no_locales();
+
+        assert_eq!(template.unwrap_render("en"),
+                   r#"<a title="a &quot;quoted&quot; &amp; &lt;b&gt;"></a>"#);
+    }
+
+    #[test]
+    fn raw_attributes() {
+        let template = Template::load_from_source("raw", r#"/a*(title="<b>")"#).no_locales();
+
+        assert_eq!(template.unwrap_render("en"), r#"<a title="<b>"></a>"#);
+    }
+
+    #[test]
+    fn whitespace_control() {
+        let render = |source: &str| {
+            Template::load_from_source("whitespace", source).no_locales().unwrap_render("en")
+        };
+
+        assert_eq!(render("/a{A} foo /b{B}"), "<a>A</a> foo <b>B</b>");
+        assert_eq!(render("/a<{A} foo /b{B}"), "<a>A</a>foo <b>B</b>");
+        assert_eq!(render("/a{A}/b>{B}"), "<a>A</a> <b>B</b>");
+        assert_eq!(render("/a{A}/b> /i{I}"), "<a>A</a> <b></b><i>I</i>");
+        assert_eq!(render(r#"/a{A}/br> /img(src="x")"#), r#"<a>A</a> <br><img src="x">"#);
+        assert_eq!(render(r#"/a{A}/img(src="x")> /br"#), r#"<a>A</a> <img src="x"><br>"#);
+        assert_eq!(render("/a<>{A} foo"), " <a>A</a>foo");
+    }
+
+    #[test]
+    fn escaped_text() {
+        let template = Template::load_from_source("escaped", r"/p{1 < 2 \& 3 > 2}").no_locales();
+
+        assert_eq!(template.unwrap_render("en"), "<p>1 &lt; 2 &amp; 3 &gt; 2</p>");
+    }
+
+    #[test]
+    fn component_defaults() {
+        let json: Value = serde_json::from_str(r#"{"title": "Polly", "sub": "Templates"}"#)
+                              .unwrap();
+        let definition = r#"&card(@title, @subtitle = "None") {/h1{@title}/h2{@subtitle}}"#;
+
+        let template = Template::load_from_source("defaults",
+                                                  &format!("{}\n&card(@title)", definition))
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<h1>Polly</h1><h2>None</h2>");
+
+        let template = Template::load_from_source("defaults",
+                                                  &format!("{}\n&card(@title, @sub)", definition))
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<h1>Polly</h1><h2>Templates</h2>");
+    }
+
+    #[test]
+    fn component_too_many_arguments() {
+        let json: Value = serde_json::from_str(r#"{"title": "Polly"}"#).unwrap();
+        let source = "&card(@title) {/h1{@title}}\n&card(@title, @title)";
+
+        let template = Template::load_from_source("defaults", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        match template.render("en") {
+            Err(TemplateError::CodegenError(error)) => {
+                assert_eq!(error.to_string(),
+                           "Wrong number of arguments passed to the component:  NAME: card \
+                            EXPECTED: 1 FOUND: 2 AT: line 2, column 1")
+            }
+            result => panic!("Expected an ArgumentCountMismatch error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn component_too_few_arguments() {
+        let json: Value = serde_json::from_str(r#"{"title": "Polly"}"#).unwrap();
+        let source = "&card(@title, @body, @footer = \"\") {/h1{@title}}\n&card(@title)";
+
+        let template = Template::load_from_source("defaults", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::ArgumentCountMismatch { ref component,
+                                                                                  expected,
+                                                                                  found,
+                                                                                  .. })) => {
+                assert_eq!((&**component, expected, found), ("card", 2, 1))
+            }
+            result => panic!("Expected an ArgumentCountMismatch error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn escaped_variables() {
+        let json: Value = serde_json::from_str(r#"{"html": "<b>\"Bold\" & more</b>"}"#).unwrap();
+        let source = r#"/p{@html}/p{@!html}/p(title="@html")"#;
+
+        let template = Template::load_from_source("escaped", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<p>&lt;b&gt;\"Bold\" &amp; more&lt;/b&gt;</p>\
+                    <p><b>\"Bold\" & more</b></p>\
+                    <p title=\"&lt;b&gt;&quot;Bold&quot; &amp; more&lt;/b&gt;\"></p>");
+    }
+
+    #[test]
+    fn escaped_forwarded_arguments() {
+        let json: Value = serde_json::from_str(r#"{"name": "<Polly>"}"#).unwrap();
+        let source = "&inner(@text) {/b{@text}}\n&outer(@text) {&inner(@text)}\n/p{&outer(@name)}";
+
+        let template = Template::load_from_source("escaped", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p><b>&lt;Polly&gt;</b></p>");
+    }
+
+    #[test]
+    fn component_literal_arguments() {
+        let source = "&card(@title) {/h1{@title}}\n/div{&card(\"Hello\")}/main&card(\"World\")";
+        let template = Template::load_from_source("literal", source).no_locales();
+        assert_eq!(template.unwrap_render("en"),
+                   "<div><h1>Hello</h1></div><main><h1>World</h1></main>");
+    }
+
+    #[test]
+    fn component_depth() {
+        let source = "&a {&b}\n&b {&c}\n&c {/p{Deep}}\n/div{&a}";
+        let template = Template::load_from_source("depth", source).no_locales();
+        assert_eq!(template.unwrap_render("en"), "<div><p>Deep</p></div>");
+
+        let template = Template::load_from_source("depth", source).no_locales().component_depth(2);
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::RecursiveComponent(_, ref chain))) => {
+                assert_eq!(chain, "a -> b -> c")
+            }
+            result => panic!("Expected a RecursiveComponent error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn component_named_arguments() {
+        fn render(call: &str) -> Result<String, TemplateError> {
+            let json: Value = serde_json::from_str(r#"{"title": "Polly", "body": "Templates"}"#)
+                                  .unwrap();
+            let source = format!("&card(@title, @body) {{/h1{{@title}}/p{{@body}}}}\n{}", call);
+
+            Template::load_from_source("named", &source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .render("en")
+        }
+
+        let expected = "<h1>Polly</h1><p>Templates</p>";
+        assert_eq!(render("&card(body = @body, title = @title)").unwrap(), expected);
+        assert_eq!(render("&card(@title, body = @body)").unwrap(), expected);
+        assert_eq!(render("/div&card(@title, body = @body)").unwrap(),
+                   format!("<div>{}</div>", expected));
+
+        match render("&card(@title, title = @title)") {
+            Err(TemplateError::CodegenError(CodegenError::DuplicateArgument(_, ref name))) => {
+                assert_eq!(name, "title")
+            }
+            result => panic!("Expected a DuplicateArgument error, got: {:?}", result),
+        }
+        match render("&card(@title, footer = @body)") {
+            Err(TemplateError::CodegenError(CodegenError::NoSuchArgument(_, ref name))) => {
+                assert_eq!(name, "footer")
+            }
+            result => panic!("Expected a NoSuchArgument error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn boolean_attributes() {
+        let template = Template::load_from_source("boolean", "/input(disabled)").no_locales();
+        assert_eq!(template.unwrap_render("en"), "<input disabled>");
+
+        let template = Template::load_from_source("boolean", r#"/input(value="")"#).no_locales();
+        assert_eq!(template.unwrap_render("en"), r#"<input value="">"#);
+    }
+
+    #[test]
+    fn conditional_attributes() {
+        let source = "/input(type=checkbox checked ?= @selected disabled ?= @form.locked)";
+        let render = |json: &str| {
+            let json: Value = serde_json::from_str(json).unwrap();
+            Template::load_from_source("conditional", source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .unwrap_render("en")
+        };
+
+        assert_eq!(render(r#"{"selected": true, "form": {"locked": 1}}"#),
+                   r#"<input type="checkbox" checked disabled>"#);
+        assert_eq!(render(r#"{"selected": false, "form": {"locked": []}}"#),
+                   r#"<input type="checkbox">"#);
+        assert_eq!(render("{}"), r#"<input type="checkbox">"#);
+    }
+
+    #[test]
+    fn boolean_variable_attributes() {
+        let source = "/input(checked=@active disabled?=@locked value=@label)";
+        let render = |json: &str| {
+            let json: Value = serde_json::from_str(json).unwrap();
+            Template::load_from_source("boolean", source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .unwrap_render("en")
+        };
+
+        assert_eq!(render(r#"{"active": true, "locked": true, "label": "On"}"#),
+                   r#"<input checked disabled value="On">"#);
+        assert_eq!(render(r#"{"active": false, "locked": false, "label": false}"#),
+                   "<input>");
+        assert_eq!(render(r#"{"active": "yes", "locked": "", "label": "Off"}"#),
+                   r#"<input checked="yes" value="Off">"#);
+    }
+
+    #[test]
+    fn variable_classes() {
+        let json = r#"{"extra": ["wide", "card", "shadow"], "state": {"active": true,
+                       "hidden": false, "open": 1}, "size": "large  bold"}"#;
+        let render = |source: &str| render_with(source, json, RenderOptions::default());
+
+        assert_eq!(render("/div.card.dark(class=@extra)").unwrap(),
+                   r#"<div class="card dark wide shadow"></div>"#);
+        assert_eq!(render("/div.card(class=@state)").unwrap(),
+                   r#"<div class="card active open"></div>"#);
+        assert_eq!(render("/div.card(class=\"btn @size\")").unwrap(),
+                   r#"<div class="card btn large bold"></div>"#);
+
+        let json = r#"{"extra": ["wide", {"name": "card"}]}"#;
+        match render_with("/div.card(class=@extra)", json, RenderOptions::default()) {
+            Err(TemplateError::CodegenError(CodegenError::InvalidClass(span, ref name, kind))) => {
+                assert_eq!(span.unwrap().start, Position::new(1, 1, 0));
+                assert_eq!(name, "extra");
+                assert_eq!(kind, "object");
+            }
+            result => panic!("Expected an InvalidClass error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn attribute_spread() {
+        let json = r#"{"link": {"href": "/users", "title": "<Users>", "hidden": true,
+                       "target": null, "download": false, "tabindex": 2, "class": "wide"},
+                       "none": null}"#;
+        let render = |source: &str| render_with(source, json, RenderOptions::default());
+
+        assert_eq!(render("/a(href=\"/\" **@link){Users}").unwrap(),
+                   "<a href=\"/\" class=\"wide\" hidden tabindex=\"2\" \
+                    title=\"&lt;Users&gt;\">Users</a>");
+        assert_eq!(render("/a.link(**@link){Users}").unwrap(),
+                   "<a class=\"link wide\" hidden href=\"/users\" tabindex=\"2\" \
+                    title=\"&lt;Users&gt;\">Users</a>");
+        assert_eq!(render("/a.wide(**@link){Users}").unwrap(),
+                   "<a class=\"wide\" hidden href=\"/users\" tabindex=\"2\" \
+                    title=\"&lt;Users&gt;\">Users</a>");
+        assert_eq!(render("/a(**@none){Users}").unwrap(), "<a>Users</a>");
+
+        match render_with("/a(**@link){Users}", r#"{"link": {"on click": "go()"}}"#,
+                          RenderOptions::default()) {
+            Err(TemplateError::CodegenError(CodegenError::InvalidAttributeName(_, ref name))) => {
+                assert_eq!(name, "on click")
+            }
+            result => panic!("Expected an InvalidAttributeName error, got: {:?}", result),
+        }
+        match render_with("/a(**@link){Users}", r#"{"link": {"href": "javascript:go()"}}"#,
+                          RenderOptions::default()) {
+            Err(TemplateError::CodegenError(CodegenError::UnsafeUrl(_, _))) => {}
+            result => panic!("Expected an UnsafeUrl error, got: {:?}", result),
+        }
+        match render_with("/a(**@link){Users}", r#"{"link": "/users"}"#, RenderOptions::default()) {
+            Err(TemplateError::CodegenError(CodegenError::InvalidSpread(_, ref name, kind))) => {
+                assert_eq!(name, "link");
+                assert_eq!(kind, "string");
+            }
+            result => panic!("Expected an InvalidSpread error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn deterministic_attributes() {
+        let source = r#"/input#title.wide(type="text" name="title" required value="")"#;
+        let expected = "<input id=\"title\" class=\"wide\" type=\"text\" name=\"title\" required \
+                        value=\"\">";
+
+        for _ in 0..100 {
+            let template = Template::load_from_source("deterministic", source).no_locales();
+            assert_eq!(template.unwrap_render("en"), expected);
+        }
+    }
+
+    #[test]
+    fn classes_in_source_order() {
+        let render = |source: &str| {
+            Template::load_from_source("order", source).no_locales().unwrap_render("en")
+        };
+
+        assert_eq!(render("/a.link#home(href=\"/\"){Home}"),
+                   r#"<a class="link" id="home" href="/">Home</a>"#);
+        assert_eq!(render("/a#home.link(href=\"/\"){Home}"),
+                   r#"<a id="home" class="link" href="/">Home</a>"#);
+        assert_eq!(render("/input(type=\"text\" class=\"wide\" name=\"q\")"),
+                   r#"<input type="text" class="wide" name="q">"#);
+    }
+
+    #[test]
+    fn chained_children() {
+        let source = "/ul.menu > /li > /a(href=@url) { @title }/pre > /code{  a   b }";
+        let html = render_with(source,
+                               r#"{"url": "/home", "title": "Home"}"#,
+                               RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   "<ul class=\"menu\"><li><a href=\"/home\">Home </a></li></ul>\
+                    <pre><code>  a   b </code></pre>");
+    }
+
+    #[test]
+    fn data_and_aria_attributes() {
+        let source = r#"/button(data-user-id="@uid" data-role="user-@uid" aria-label="Close"){x}"#;
+        let html = render_with(source, r#"{"uid": 7}"#, RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   r#"<button data-user-id="7" data-role="user-7" aria-label="Close">x</button>"#);
+    }
+
+    #[test]
+    fn number_attributes() {
+        let source = "/input(tabindex=3 min=-1 step=.5 size=3px)/p{a = 1.5}";
+        let html = render_with(source, "{}", RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   r#"<input tabindex="3" min="-1" step=".5" size="3px"><p>a = 1.5</p>"#);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let source = r#"/p(title="Say \"hi\"\u{21}" data-at='\@name \\@name'){@name "\@b"}"#;
+        let html = render_with(source, r#"{"name": "Polly"}"#, RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   r#"<p title="Say &quot;hi&quot;!" data-at="@name \Polly">Polly "@b"</p>"#);
+    }
+
+    #[test]
+    fn quoted_text_child() {
+        let source = r#"/h1 "Hello, @name!"/p "Say \"1 < 2\" to @!html" /pre "  a  b ""#;
+        let html = render_with(source,
+                               r#"{"name": "<Polly>", "html": "<b>you</b>"}"#,
+                               RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   "<h1>Hello, &lt;Polly&gt;!</h1><p>Say \"1 &lt; 2\" to <b>you</b></p>\
+                    <pre>  a  b </pre>");
+
+        let source = r#"/pre{/b "  a  b "}/p{/b "  a  b "}"#;
+        let html = render_with(source, "{}", RenderOptions::default());
+        assert_eq!(html.unwrap(), "<pre><b>  a  b </b></pre><p><b> a b </b></p>");
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let source = r"/p{a\nb\tc C:\Users\me \\ \@name \ n}/b{x}\";
+        let html = render_with(source, "{}", RenderOptions::default());
+        assert_eq!(html.unwrap(), "<p>a\nb\tc C:\\Users\\me \\@name \\ n</p><b>x</b>\\");
+    }
+
+    #[test]
+    fn merges_class_attributes() {
+        let source = "/div.card(class=\"highlight card\"){a}\
+                      /p(class=\"@size\" class=\"@tone wide\"){b}";
+        let html = render_with(source,
+                               r#"{"size": "big", "tone": "dark"}"#,
+                               RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   r#"<div class="card highlight">a</div><p class="big dark wide">b</p>"#);
+    }
+
+    #[test]
+    fn duplicate_attributes() {
+        let source = "/a(href=\"/\" href=\"/home\"){Home}";
+        let template = Template::load_from_source("duplicate", source).no_locales();
+        match template.render("en") {
+            Err(TemplateError::AstError(AstError::DuplicateAttribute(ref key))) => {
+                assert_eq!(key, &Lexeme::Word(key.span(), String::from(" href")))
+            }
+            result => panic!("Expected a DuplicateAttribute error, got: {:?}", result),
+        }
+
+        let options = ParseOptions { allow_duplicate_attributes: true, ..ParseOptions::default() };
+        let html = Template::load_from_source("duplicate", source)
+                       .no_locales()
+                       .parse_options(options)
+                       .unwrap_render("en");
+        assert_eq!(html, r#"<a href="/home">Home</a>"#);
+    }
+
+    #[test]
+    fn attributes_in_source_order() {
+        let template = Template::load_from_source("order", "/a(z=1 a=2 m=3)").no_locales();
+        assert_eq!(template.unwrap_render("en"), r#"<a z="1" a="2" m="3"></a>"#);
+    }
+
+    #[test]
+    fn multiple_classes() {
+        let template = Template::load_from_source("classes", "/div.a.b.c.btn-primary.b{}")
+                           .no_locales();
+        assert_eq!(template.unwrap_render("en"), r#"<div class="a b c btn-primary"></div>"#);
+    }
+
+    #[test]
+    fn chained_classes() {
+        let source = "/div.card.col-6#main.shadow.large.h1.3d{/p.col-12.col-md-4{Text}}";
+        let template = Template::load_from_source("classes", source).no_locales();
+        assert_eq!(template.unwrap_render("en"),
+                   "<div class=\"card col-6 shadow large h1 3d\" id=\"main\">\
+                    <p class=\"col-12 col-md-4\">Text</p></div>");
+    }
+
+    #[test]
+    fn single_quoted_attributes() {
+        let source = r#"/a(title='He said "hi"' data-name="Polly's"){Don't}"#;
+        let template = Template::load_from_source("quotes", source).no_locales();
+        assert_eq!(template.unwrap_render("en"),
+                   r#"<a title="He said &quot;hi&quot;" data-name="Polly's">Don't</a>"#);
+    }
+
+    #[test]
+    fn single_quoted_condition() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let template = Template::load_from_source("quotes", "@if name == 'Polly' {/p{It's @name}}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p>It's Polly</p>");
+    }
+
+    #[test]
+    fn quotes_in_text() {
+        let render = |source: &str| {
+            Template::load_from_source("quotes", source).no_locales().unwrap_render("en")
+        };
+
+        assert_eq!(render("/p{a = 'b'}"), "<p>a = 'b'</p>");
+        assert_eq!(render("/p{Hello, 'world'}"), "<p>Hello, 'world'</p>");
+        assert_eq!(render(r"/p{It\'s (a='b')}"), "<p>It's (a='b')</p>");
+    }
+
+    #[test]
+    fn implicit_div() {
+        let source = ".card.shadow(data-id=7) {\n    #main {/p{End.}.x}\n    .5 is half.\n}\n\
+                      .footer";
+        let template = Template::load_from_source("div", source).no_locales();
+        assert_eq!(template.unwrap_render("en"),
+                   "<div class=\"card shadow\" data-id=\"7\"><div id=\"main\"><p>End.</p>.x\
+                    </div>.5 is half.</div><div class=\"footer\"></div>");
+    }
+
+    #[test]
+    fn minify() {
+        let source = "/div {\n    /h1 { Title }\n    /p {\n        Some   /b{bold} text\n    }\n\
+                      /pre {\n  keep   this\n}\n}";
+        let options = RenderOptions { minify: true, ..RenderOptions::default() };
+        let template = Template::load_from_source("minify", source)
+                           .no_locales()
+                           .render_options(options);
+        assert_eq!(template.unwrap_render("en"),
+                   "<div><h1>Title</h1><p>Some <b>bold</b> text</p><pre>\n  keep   this\n</pre>\
+                    </div>");
+    }
+
+    #[test]
+    fn hyphenated_names() {
+        let json: Value = serde_json::from_str(r#"{"id": 7}"#).unwrap();
+        let source = "&my-card(@id) {/p(data-id=@id aria-label=\"main-label\"){Card}}\n\
+                      /nav.btn-primary#main-nav(data-toggle=drop-down){&my-card(@id) - x}";
+        let template = Template::load_from_source("hyphens", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<nav class=\"btn-primary\" id=\"main-nav\" data-toggle=\"drop-down\">\
+                    <p data-id=\"7\" aria-label=\"main-label\">Card</p> - x</nav>");
+    }
+
+    #[test]
+    fn interpolated_attributes() {
+        let json: Value = serde_json::from_str(r#"{"user": {"id": 7, "name": "Joe"}}"#).unwrap();
+        let source = r#"/a(href="/users/@user.id")/p(title="\@@user.name.")"#;
+
+        let template = Template::load_from_source("interpolated", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   r#"<a href="/users/7"></a><p title="@Joe."></p>"#);
+    }
+
+    #[test]
+    fn interpolated_attribute_missing_variable() {
+        let template = Template::load_from_source("interpolated", r#"/a(href="/users/@id")"#)
+                           .no_locales();
+
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::MissingVariable(_, ref name, _))) => {
+                assert_eq!(name, "id")
+            }
+            result => panic!("Expected a MissingVariable error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn component_children() {
+        fn render(call: &str) -> String {
+            let json: Value = serde_json::from_str(r#"{"title": "Polly", "body": "Hello"}"#)
+                                  .unwrap();
+            let definition = "&panel(@title) {/div{/h1{@title}$children()}}";
+
+            Template::load_from_source("slots", &format!("{}\n{}", definition, call))
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+                .unwrap_render("en")
+        }
+
+        assert_eq!(render("/main{&panel(@title) {/p{@body}}}"),
+                   "<main><div><h1>Polly</h1><p>Hello</p></div></main>");
+        assert_eq!(render("/main&panel(@title) {/p{@body}}"),
+                   "<main><div><h1>Polly</h1><p>Hello</p></div></main>");
+        assert_eq!(render("/main{&panel(@title) {&panel(@body) {/p{@body}}}}"),
+                   "<main><div><h1>Polly</h1><div><h1>Hello</h1><p>Hello</p></div></div></main>");
+    }
+
+    #[test]
+    fn recursive_component() {
+        let template = Template::load_from_source("recursive", "&a {/p{&a}}\n&a").no_locales();
+
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::RecursiveComponent(_, ref cycle))) => {
+                assert_eq!(cycle, "a -> a")
+            }
+            result => panic!("Expected a RecursiveComponent error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn recursive_component_cycle() {
+        let source = "&a {&b}\n&b {&c}\n&c {&a}\n/p{&b}";
+        let template = Template::load_from_source("recursive", source).no_locales();
+
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::RecursiveComponent(_, ref cycle))) => {
+                assert_eq!(cycle, "b -> c -> a -> b")
+            }
+            result => panic!("Expected a RecursiveComponent error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn imported_components() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let source = r#"$import(path = "components/ui.polly", as = ui)&ui.card(@name)"#;
+
+        let template = Template::load_from_source("./tests/page.polly", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<div class=\"card\"><h1>Polly</h1></div>");
+    }
+
+    #[test]
+    fn imported_components_default_namespace() {
+        let source = r#"$import(path = "components/ui.polly")&ui.title(@name)"#;
+        let template = Template::load_from_source("./tests/page.polly", source).no_locales();
+        assert!(template.get_component("ui.title").is_some());
+    }
+
+    #[test]
+    fn missing_import() {
+        let source = r#"$import(path = "components/missing.polly", as = ui)"#;
+        match Template::load_from_source("./tests/page.polly", source).render("en") {
+            Err(TemplateError::AstError(AstError::MissingImport(_, ref path))) => {
+                assert_eq!(path, "components/missing.polly")
+            }
+            result => panic!("Expected a MissingImport error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn duplicate_import() {
+        let source = r#"$import(path = "components/ui.polly", as = ui)
+$import(path = "components/ui.polly", as = ui)"#;
+        match Template::load_from_source("./tests/page.polly", source).render("en") {
+            Err(TemplateError::AstError(AstError::DuplicateComponent(_, _))) => {}
+            result => panic!("Expected a DuplicateComponent error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn include() {
+        let source = "@include \"partials/header.polly\"\n/main {&title(\"Main\")}";
+        let mut variables = BTreeMap::new();
+        let _ = variables.insert(String::from("title"), Value::String(String::from("Polly")));
+        let template = Template::load_from_source("./tests/includes/page.polly", source)
+                           .no_locales()
+                           .json(variables);
+        assert_eq!(template.unwrap_render("en"),
+                   "<header><h1>Polly</h1><nav><a href=\"/\">Home</a></nav></header>\
+                    <main><h1>Main</h1></main>");
+    }
+
+    #[test]
+    fn include_with_parse_options() {
+        let options = ParseOptions {
+            raw_tags: vec![String::from("code")],
+            ..ParseOptions::default()
+        };
+        let html = Template::load_from_source("./tests/includes/page.polly",
+                                              "@include \"partials/code.polly\"")
+                       .no_locales()
+                       .parse_options(options)
+                       .unwrap_render("en");
+        assert_eq!(html, "<code>/b{@a}</code>");
+    }
+
+    #[test]
+    fn include_unreadable_file() {
+        let source = "@include \"partials\"";
+        match Template::load_from_source("./tests/includes/page.polly", source).render("en") {
+            Err(TemplateError::AstError(AstError::UnreadableFile(_, ref path, _))) => {
+                assert!(path.ends_with("partials"))
+            }
+            result => panic!("Expected an UnreadableFile error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn include_cycle() {
+        match Template::load("./tests/includes/cycle_a.polly") {
+            Err(TemplateError::ParseError(_, ref errors)) => {
+                match errors[..] {
+                    [AstError::IncludeCycle(_, ref path)] => {
+                        assert!(path.ends_with("cycle_a.polly"))
+                    }
+                    ref errors => panic!("Expected an IncludeCycle error, got {:?}", errors),
+                }
+            }
+            result => panic!("Expected a ParseError, got {:?}", result.err()),
+        }
+    }
+
+    #[test]
+    fn extends() {
+        let mut variables = BTreeMap::new();
+        let _ = variables.insert(String::from("name"), Value::String(String::from("Polly")));
+        let template = Template::load("./tests/inheritance/page.polly")
+                           .unwrap()
+                           .no_locales()
+                           .json(variables);
+        assert_eq!(template.unwrap_render("en"),
+                   "<!DOCTYPE html><html><head><title>Home</title></head><body>\
+                    <h1>Hello Polly</h1><footer>Footer</footer></body></html>");
+    }
+
+    #[test]
+    fn extends_unknown_block() {
+        let source = "@extends \"base.polly\"\n@block sidebar {Links}";
+        match Template::load_from_source("./tests/inheritance/broken.polly", source).render("en") {
+            Err(TemplateError::AstError(AstError::UnknownBlock(_, ref name))) => {
+                assert_eq!(name, "sidebar")
+            }
+            result => panic!("Expected an UnknownBlock error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn components_dir() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let mut template = Template::load_from_source("dir", "&layout.page(@name)")
+                               .no_locales()
+                               .json(json.as_object().unwrap().to_owned());
+
+        assert_eq!(template.load_components_dir("./tests/library").unwrap(), 3);
+        assert!(template.get_component("forms.input.text").is_some());
+        assert_eq!(template.unwrap_render("en"),
+                   "<main><input type=\"text\"><label>Polly</label></main>");
+    }
+
+    #[test]
+    fn components_dir_with_parse_options() {
+        let options = ParseOptions {
+            raw_tags: vec![String::from("code")],
+            ..ParseOptions::default()
+        };
+        let mut template = Template::load_from_source("dir", "&snippets.code")
+                               .no_locales()
+                               .parse_options(options);
+
+        assert_eq!(template.load_components_dir("./tests/library_raw").unwrap(), 1);
+        assert_eq!(template.unwrap_render("en"), "<code>/b{@a}</code>");
+    }
+
+    #[test]
+    fn components_dir_duplicate() {
+        let mut template = Template::load_from_source("dir", "").no_locales();
+        match template.load_components_dir("./tests/library_duplicate") {
+            Err(TemplateError::DuplicateComponent(_, ref name)) => {
+                assert_eq!(name, "forms.input.text")
+            }
+            result => panic!("Expected a DuplicateComponent error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn components_dir_invalid_file() {
+        let mut template = Template::load_from_source("dir", "").no_locales();
+        match template.load_components_dir("./tests/library_invalid") {
+            Err(TemplateError::ComponentFileError(ref path, _)) => {
+                assert!(path.ends_with("broken.polly"))
+            }
+            result => panic!("Expected a ComponentFileError, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn components_dir_missing() {
+        let mut template = Template::load_from_source("dir", "").no_locales();
+        match template.load_components_dir("./tests/library_missing") {
+            Err(TemplateError::FileError(ref path, _)) => {
+                assert!(path.ends_with("library_missing"))
+            }
+            result => panic!("Expected a FileError, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn registered_function() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let mut template = Template::load_from_source("function", "/p{$uppercase(text=@name)}")
+                               .no_locales()
+                               .json(json.as_object().unwrap().to_owned());
+
+        template.register(String::from("uppercase"),
+                          Box::new(|args, _| {
+                              match args.get("text") {
+                                  Some(&ArgValue::Json(Some(Value::String(ref text)))) => {
+                                      Ok(text.to_uppercase())
+                                  }
+                                  _ => Err(String::from("Expected text to be a string.")),
+                              }
+                          }))
+                .unwrap();
+        assert_eq!(template.unwrap_render("en"), "<p>POLLY</p>");
+    }
+
+    #[test]
+    fn function_literal_arguments() {
+        let source = r#"/p{$count(text="items", total=3, upper=true)}"#;
+        let mut template = Template::load_from_source("function", source).no_locales();
+
+        template.register(String::from("count"),
+                          Box::new(|args, _| {
+                              match (args.get("text"), args.get("total"), args.get("upper")) {
+                                  (Some(&ArgValue::Json(Some(Value::String(ref text)))),
+                                   Some(&ArgValue::Json(Some(Value::U64(total)))),
+                                   Some(&ArgValue::Json(Some(Value::Bool(upper))))) => {
+                                      let text = format!("{} {}", total, text);
+                                      Ok(if upper { text.to_uppercase() } else { text })
+                                  }
+                                  _ => Err(String::from("Expected text, total, and upper.")),
+                              }
+                          }))
+                .unwrap();
+        assert_eq!(template.unwrap_render("en"), "<p>3 ITEMS</p>");
+    }
+
+    #[test]
+    fn function_children() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let source = "/div{$card(title=@name){/p{Hello @name}}}";
+        let mut template = Template::load_from_source("function", source)
+                               .no_locales()
+                               .json(json.as_object().unwrap().to_owned());
+
+        template.register(String::from("card"),
+                          Box::new(|args, _| {
+                              match (args.get("title"), args.get("children")) {
+                                  (Some(&ArgValue::Json(Some(Value::String(ref title)))),
+                                   Some(&ArgValue::Children(ref children))) => {
+                                      Ok(format!("<h1>{}</h1>{}", title, children))
+                                  }
+                                  _ => Err(String::from("Expected a title, and children.")),
+                              }
+                          }))
+                .unwrap();
+        assert_eq!(template.unwrap_render("en"),
+                   "<div><h1>Polly</h1><p>Hello Polly</p></div>");
+    }
+
+    #[test]
+    fn function_error() {
+        let mut template = Template::load_from_source("function", "/p{$fail()}").no_locales();
+        template.register(String::from("fail"),
+                          Box::new(|_, _| Err(String::from("Nothing to render."))))
+                .unwrap();
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::FunctionError(span, ref error))) => {
+                assert_eq!(error, "Nothing to render.");
+                assert_eq!(span.unwrap().start, Position::new(1, 4, 3));
+            }
+            result => panic!("Expected a FunctionError, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Template>();
+
+        let template = Arc::new(Template::load_from_source("shared", "/p{Hello}").no_locales());
+        let handle = {
+            let template = template.clone();
+            thread::spawn(move || template.get_component("missing").is_none())
+        };
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn filters() {
+        let json: Value = serde_json::from_str(r#"{"name": "  Polly  ", "html": "<b>"}"#).unwrap();
+        let source = "/p{@name | trim | upper!}/p{@name|lower|trim @!html | upper}";
+        let template = Template::load_from_source("filters", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p>POLLY!</p><p>polly <B></p>");
+    }
+
+    #[test]
+    fn registered_filter() {
+        let mut template = Template::load_from_source("filters", "/p{@name | reverse}")
+                               .no_locales();
+        template.set_variable("name", Value::String(String::from("Polly")));
+        template.register_filter("reverse", |text| text.chars().rev().collect()).unwrap();
+
+        match template.register_filter("trim", |text| text.to_owned()) {
+            Err(TemplateError::PreDefinedFilter) => {}
+            result => panic!("Expected a PreDefinedFilter error, got {:?}", result),
+        }
+        assert_eq!(template.unwrap_render("en"), "<p>ylloP</p>");
+    }
+
+    #[test]
+    fn unknown_filter() {
+        let mut template = Template::load_from_source("filters", "/p{@name | shout}").no_locales();
+        template.set_variable("name", Value::String(String::from("Polly")));
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::UnknownFilter(_, ref name))) => {
+                assert_eq!(name, "shout")
+            }
+            result => panic!("Expected an UnknownFilter error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn duplicate_function() {
+        let mut template = Template::load_from_source("function", "/p{$greet()}").no_locales();
+        template.register_function("greet", |_, _| Ok(String::from("Hello"))).unwrap();
+
+        match template.register_function("greet", |_, _| Ok(String::from("Goodbye"))) {
+            Err(TemplateError::PreDefinedFunction) => {}
+            result => panic!("Expected a PreDefinedFunction error, got {:?}", result),
+        }
+        match template.register_function("std.if", |_, _| Ok(String::new())) {
+            Err(TemplateError::PreDefinedFunction) => {}
+            result => panic!("Expected a PreDefinedFunction error, got {:?}", result),
+        }
+
+        assert_eq!(template.functions(), vec!["greet", "std.each", "std.if", "std.if_else"]);
+        assert_eq!(template.unwrap_render("en"), "<p>Hello</p>");
+    }
+
+    #[test]
+    fn unregistered_function() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let template = Template::load_from_source("function", "/p{$uppercase(text=@name)}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::NoSuchFunction(_, ref name))) => {
+                assert_eq!(name, "uppercase")
+            }
+            result => panic!("Expected a NoSuchFunction error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn set_variables() {
+        let mut template = Template::load_from_source("variables", "/p{@greeting @user.name}")
+                               .no_locales();
+        template.set_variables(serde_json::from_str(r#"{"greeting": "Hi"}"#).unwrap()).unwrap();
+        template.set_variable("user", serde_json::from_str(r#"{"name": "Polly"}"#).unwrap());
+        assert_eq!(template.unwrap_render("en"), "<p>Hi Polly</p>");
+    }
+
+    #[test]
+    fn set_variables_not_an_object() {
+        let mut template = Template::load_from_source("variables", "/p{@greeting}").no_locales();
+        template.set_variable("greeting", Value::String(String::from("Hi")));
+        match template.set_variables(Value::Array(Vec::new())) {
+            Err(TemplateError::DataError(_, _)) => {}
+            result => panic!("Expected a DataError, got {:?}", result),
+        }
+        assert_eq!(template.unwrap_render("en"), "<p>Hi</p>");
+    }
+
+    #[test]
+    fn load_data_file() {
+        let source = "/ul{@each user in @users {/li{@user.profile.name: \
+                      @each tag in @user.tags {@tag }}}}";
+        let mut template = Template::load_from_source("data", source).no_locales();
+        template.load_data_file("tests/data/users.json").unwrap();
+        assert_eq!(template.unwrap_render("en"),
+                   "<ul><li>Polly: admin author </li><li>Aaron: author </li></ul>");
+    }
+
+    #[test]
+    fn load_data_file_not_an_object() {
+        let mut template = Template::load_from_source("data", "/p{}").no_locales();
+        match template.load_data_file("tests/data/array.json") {
+            Err(error @ TemplateError::DataError(_, _)) => {
+                assert_eq!(error.to_string(),
+                           "tests/data/array.json: Couldn't load the data file. The root of the \
+                            data has to be a JSON object.")
+            }
+            result => panic!("Expected a DataError, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn nested_variable_through_non_object() {
+        let mut template = Template::load_from_source("variables", "/p{@user.name}").no_locales();
+        template.set_variable("user", Value::String(String::from("Polly")));
+        match template.render("en") {
+            Err(TemplateError::CodegenError(error @ CodegenError::NotAnObjectOrNull(_, _, _))) => {
+                assert_eq!(error.to_string(),
+                           "JSON passed in wasn't an object, or was null:  NAME: user.name \
+                            SEGMENT: user AT: line 1, column 4")
+            }
+            result => panic!("Expected a NotAnObjectOrNull error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn array_index_out_of_bounds() {
+        let json: Value = serde_json::from_str(r#"{"items": [1, 2, 3]}"#).unwrap();
+        let template = Template::load_from_source("variables", "/p{@items.7}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        match template.render("en") {
+            Err(TemplateError::CodegenError(error @ CodegenError::IndexOutOfBounds(..))) => {
+                assert!(error.to_string().contains("(index 7 but array has 3 elements)"))
+            }
+            result => panic!("Expected an IndexOutOfBounds error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn negative_array_index() {
+        let json: Value = serde_json::from_str(r#"{"items": [1, 2, 3]}"#).unwrap();
+        let template = Template::load_from_source("variables", "/p{@items.-1}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::NegativeIndex(_, _, ref index))) => {
+                assert_eq!(index, "-1")
+            }
+            result => panic!("Expected a NegativeIndex error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn numeric_object_key() {
+        let json: Value = serde_json::from_str(r#"{"years": {"2016": "Polly"}}"#).unwrap();
+        let template = Template::load_from_source("variables", "/p{@years.2016}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p>Polly</p>");
+    }
+
+    #[test]
+    fn nested_variable_missing_segment() {
+        let json: Value = serde_json::from_str(r#"{"user": {"name": "Polly"}}"#).unwrap();
+        let template = Template::load_from_source("variables", "/p{@user.address.city}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        match template.render("en") {
+            Err(TemplateError::CodegenError(CodegenError::MissingVariable(_,
+                                                                          ref name,
+                                                                          ref segment))) => {
+                assert_eq!(name, "user.address.city");
+                assert_eq!(segment, "address");
+            }
+            result => panic!("Expected a MissingVariable error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn nested_variables() {
+        let json: Value = serde_json::from_str(r#"{"user": {"address": {"city": "Dublin"}},
+                                                   "items": [{"title": "First"}]}"#)
+                              .unwrap();
+        let template = Template::load_from_source("nested",
+                                                  "/p{@user.address.city}/p{@items.0.title}")
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p>Dublin</p><p>First</p>");
+    }
+
+    #[test]
+    fn nested_variable_in_conditional() {
+        let json: Value = serde_json::from_str(r#"{"items": [{"title": "First"}]}"#).unwrap();
+        let source = "@if items.0.title {/p{Found}} @else {/p{None}}\
+                      @if items.1.title {/p{Found}} @else {/p{None}}";
+        let template = Template::load_from_source("nested", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"), "<p>Found</p><p>None</p>");
+    }
+
+    #[test]
+    fn from_source() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let template = Template::from_source("/p{Hello @name}")
+                           .unwrap()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.render("en").unwrap(), "<p>Hello Polly</p>");
+    }
+
+    #[test]
+    fn from_source_without_file_name() {
+        for name in &["", ".."] {
+            let template = Template::from_source_named(name, "/p{Hello}").unwrap();
+            assert_eq!(template.render("en").unwrap(), "<p>Hello</p>");
+        }
+        let template = Template::from_source_named("..", "/p{Hello}")
+                           .unwrap()
+                           .locales_dir("./tests/locales");
+        assert_eq!(template.unwrap_render("en"), "<p>Hello</p>");
+    }
+
+    #[test]
+    fn from_source_component_call() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let source = "&greeting(@who) {/b{Hello @who}}\n/div.card{&greeting(@name)}";
+        let template = Template::from_source(source).unwrap();
+        assert!(template.get_component("greeting").is_some());
+
+        let template = template.json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   r#"<div class="card"><b>Hello Polly</b></div>"#);
+    }
+
+    #[test]
+    fn from_source_named_invalid() {
+        match Template::from_source_named("greeting", "/p{Hello") {
+            Err(error @ TemplateError::ParseError(_, _)) => {
+                assert!(error.to_string().starts_with("greeting: "))
+            }
+            result => panic!("Expected a ParseError, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn from_source_import() {
+        let source = r#"$import(path = "components/ui.polly", as = ui)"#;
+        match Template::from_source(source) {
+            Err(TemplateError::ParseError(_, ref errors)) => {
+                match errors[0] {
+                    AstError::ImportWithoutDirectory(_) => {}
+                    ref error => panic!("Expected an ImportWithoutDirectory error, got {}", error),
+                }
+            }
+            result => panic!("Expected a ParseError, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    /// A writer that fails once more than `limit` bytes have been written to it.
+    struct LimitedWriter {
+        written: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written.len() + buf.len() > self.limit {
+                return Err(io::Error::new(io::ErrorKind::Other, "writer is full"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn render_to() {
+        let mut writer = LimitedWriter { written: Vec::new(), limit: 100 };
+        Template::load_from_source("stream", "/ul{/li{One}/li{Two}}")
+            .no_locales()
+            .render_to("en", &mut writer)
+            .unwrap();
+        assert_eq!(String::from_utf8(writer.written).unwrap(),
+                   "<ul><li>One</li><li>Two</li></ul>");
+    }
+
+    #[test]
+    fn render_to_matches_render() {
+        let json: Value = serde_json::from_str(r#"{"items": ["One", "Two"], "title": "List"}"#)
+                              .unwrap();
+        let source = "&item(@text) {/li{@text}}\n\
+                      /h1{@title}/ul{@each item in @items {&item(@item)}}";
+        let template = || {
+            Template::load_from_source("stream", source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+        };
+
+        let mut html = Vec::new();
+        template().render_to("en", &mut html).unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), template().unwrap_render("en"));
+    }
+
+    #[test]
+    fn render_to_failing_writer() {
+        let mut writer = LimitedWriter { written: Vec::new(), limit: 10 };
+        let result = Template::load_from_source("stream", "/ul{/li{One}/li{Two}}")
+                         .no_locales()
+                         .render_to("en", &mut writer);
+
+        match result {
+            Err(TemplateError::CodegenError(CodegenError::IoError(_))) => {}
+            result => panic!("Expected an IoError, got {:?}", result),
+        }
+        assert_eq!(String::from_utf8(writer.written).unwrap(), "<ul><li>");
+    }
+
+    #[test]
+    fn html_comments() {
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let source = "/! Made with @name\n/p{Hello}/!{Not -- closed}";
+
+        let template = Template::load_from_source("comments", source)
+                           .no_locales()
+                           .json(json.as_object().unwrap().to_owned());
+        assert_eq!(template.unwrap_render("en"),
+                   "<!-- Made with Polly --><p>Hello</p><!-- Not - - closed -->");
+    }
+
+    #[test]
+    fn template_comments() {
+        let source = "// Not rendered\n/p/* Nor this */{Hello}\n/ div {/*\n{ */World}";
+
+        let template = Template::load_from_source("comments", source).no_locales();
+        assert_eq!(template.unwrap_render("en"), "<p>Hello</p><div>World</div>");
+    }
+
+    #[test]
+    fn doctype() {
+        let template = Template::load_from_source("doctype", "/!doctype html").no_locales();
+        assert_eq!(template.unwrap_render("en"), "<!DOCTYPE html>");
+
+        let template = Template::load_from_source("doctype", "/!doctype\n/p{Hello}").no_locales();
+        assert_eq!(template.unwrap_render("en"), "<!DOCTYPE html><p>Hello</p>");
+    }
+
+    #[test]
+    fn named_doctypes() {
+        let template = Template::load_from_source("doctype", "/!doctype html5").no_locales();
+        assert_eq!(template.unwrap_render("en"), "<!DOCTYPE html>");
+
+        let template = Template::load_from_source("doctype", "/!doctype xhtml-strict")
+                           .no_locales();
+        assert_eq!(template.unwrap_render("en"),
+                   "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \
+                    \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\">");
+    }
+
+    #[test]
+    fn misplaced_doctype() {
+        for source in &["/p{Hello}\n/!doctype html", "/html{\n/!doctype html\n}"] {
+            match Template::from_source(source) {
+                Err(TemplateError::ParseError(_, ref errors)) => {
+                    assert_eq!(errors[0].to_string(),
+                               "The doctype has to come before the first element. Found at \
+                                line 2, column 1.")
+                }
+                result => panic!("Expected a ParseError, got {:?}", result.err()),
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_doctype() {
+        let source = r#"/!doctype html PUBLIC "-//W3C//DTD HTML 4.01//EN""#;
+        let template = Template::load_from_source("doctype", source).no_locales();
+        assert_eq!(template.unwrap_render("en"),
+                   r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN">"#);
+    }
+
+    fn render_with(source: &str,
+                   json: &str,
+                   options: RenderOptions)
+                   -> Result<String, TemplateError> {
+        let json: Value = serde_json::from_str(json).unwrap();
+        Template::load_from_source("context", source)
+            .no_locales()
+            .render_options(options)
+            .json(json.as_object().unwrap().to_owned())
+            .render("en")
+    }
+
+    /// Renders like `render_with`, with variables still rendered within scripts, and styles.
+    fn render_interpolated(source: &str,
+                           json: &str,
+                           options: RenderOptions)
+                           -> Result<String, TemplateError> {
+        let json: Value = serde_json::from_str(json).unwrap();
+        Template::load_from_source("context", source)
+            .no_locales()
+            .parse_options(ParseOptions { interpolate_raw: true, ..ParseOptions::default() })
+            .render_options(options)
+            .json(json.as_object().unwrap().to_owned())
+            .render("en")
+    }
+
+    #[test]
+    fn script_escaping() {
+        let json = r#"{"name": "</script><b>\"Polly\"</b>"}"#;
+        let html = render_interpolated("/script{var name=\"@name\";}",
+                                       json,
+                                       RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   r#"<script>var name="<\/script><b>\"Polly\"<\/b>";</script>"#);
+    }
+
+    #[test]
+    fn raw_block() {
+        let source = "/script. { if (a < b) { return @name; } }/div.{<b>Hi</b>}";
+        let html = render_with(source, r#"{"name": "Polly"}"#, RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   "<script> if (a < b) { return @name; } </script><div><b>Hi</b></div>");
+    }
+
+    #[test]
+    fn raw_elements() {
+        let source = "/script(type=\"module\"){ var a = {b: \"@name\"}; // 1 < 2 }\
+                      /style.dark{body {color: red;}}/p{@name}";
+        let html = render_with(source, r#"{"name": "Polly"}"#, RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   "<script type=\"module\"> var a = {b: \"@name\"}; // 1 < 2 </script>\
+                    <style class=\"dark\">body {color: red;}</style><p>Polly</p>");
+    }
+
+    #[test]
+    fn raw_tags() {
+        let options = ParseOptions {
+            raw_tags: vec![String::from("code")],
+            ..ParseOptions::default()
+        };
+        let html = Template::load_from_source("raw", "/code{/p{@a}}/script{/b{x}}")
+                       .no_locales()
+                       .parse_options(options)
+                       .unwrap_render("en");
+        assert_eq!(html, "<code>/p{@a}</code><script><b>x</b></script>");
+    }
+
+    #[test]
+    fn literal_text() {
+        let source = "/p { | Price: $10.00 (incl. VAT) for @user.name! }\n\
+                      /pre {\n|| /b {@user} // Bold\n|@user.name|upper\n}";
+        let html = render_with(source, r#"{"user": {"name": "Polly"}}"#, RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   "<p> Price: $10.00 (incl. VAT) for Polly! </p>\
+                    <pre> /b {@user} // Bold\nPolly|upper\n</pre>");
+    }
+
+    #[test]
+    fn literal_text_block() {
+        let source = "/p {\n    | One  line,   spaced\n    |   and    another\n}";
+        let html = render_with(source, "{}", RenderOptions::default());
+        assert_eq!(html.unwrap(), "<p> One  line,   spaced\n   and    another\n</p>");
+    }
+
+    #[test]
+    fn style_escaping() {
+        let json = r#"{"colour": "red;} body {display: none"}"#;
+        let html = render_interpolated("/style{color: @colour;}", json, RenderOptions::default());
+        assert_eq!(html.unwrap(),
+                   r#"<style>color: red\3b \7d  body \7b display\3a  none;</style>"#);
+    }
+
+    #[test]
+    fn javascript_url() {
+        let json = r#"{"url": " JavaScript:alert(1)"}"#;
+        match render_with("/a(href=\"@url\"){Link}", json, RenderOptions::default()) {
+            Err(TemplateError::CodegenError(CodegenError::UnsafeUrl(_, ref url))) => {
+                assert_eq!(url, " JavaScript:alert(1)")
+            }
+            result => panic!("Expected an UnsafeUrl error, got: {:?}", result),
+        }
+
+        let html = render_with("/a*(href=\"@url\"){Link}", json, RenderOptions::default());
+        assert_eq!(html.unwrap(), r#"<a href=" JavaScript:alert(1)">Link</a>"#);
+    }
+
+    #[test]
+    fn url_control_characters() {
+        let json = r#"{"path": "a\tb"}"#;
+        let html = render_with("/a(href=\"/@path\"){Link}", json, RenderOptions::default());
+        assert_eq!(html.unwrap(), r#"<a href="/a%09b">Link</a>"#);
+    }
+
+    #[test]
+    fn contextual_escaping_disabled() {
+        let options = RenderOptions { contextual_escaping: false, ..RenderOptions::default() };
+        let json = r#"{"name": "</script>", "url": "javascript:alert(1)"}"#;
+        let html = render_interpolated("/script{@name}/a(href=\"@url\"){Link}", json, options);
+        assert_eq!(html.unwrap(),
+                   r#"<script>&lt;/script&gt;</script><a href="javascript:alert(1)">Link</a>"#);
+    }
+
+    #[test]
+    fn pretty_print() {
+        let source = "/!doctype html\n/ul.menu{/li{/a(href=\"/\"){Home}}/li{/span{@name}}/li{/br}}";
+        let json: Value = serde_json::from_str(r#"{"name": "Polly"}"#).unwrap();
+        let template = || {
+            Template::load_from_source("pretty", source)
+                .no_locales()
+                .json(json.as_object().unwrap().to_owned())
+        };
+
+        assert_eq!(template().unwrap_render("en"),
+                   "<!DOCTYPE html><ul class=\"menu\"><li><a href=\"/\">Home</a></li><li><span>\
+                    Polly</span></li><li><br></li></ul>");
+
+        let options = RenderOptions {
+            pretty: true,
+            indent: String::from("  "),
+            ..RenderOptions::default()
+        };
+        assert_eq!(template().render_with("en", &options).unwrap(),
+                   "<!DOCTYPE html>\n<ul class=\"menu\">\n  <li>\n    <a href=\"/\">Home</a>\n  \
+                    </li>\n  <li>\n    <span>Polly</span>\n  </li>\n  <li>\n    <br>\n  </li>\n\
+                    </ul>\n");
+    }
+
+    #[test]
+    fn pretty_print_text() {
+        let source = "/p{Hello /b{World}}/div{/p{One}/p{Two}}";
+        let options = RenderOptions { pretty: true, ..RenderOptions::default() };
+        let template = Template::load_from_source("pretty", source).no_locales();
+        assert_eq!(template.render_with("en", &options).unwrap(),
+                   "<p>Hello <b>World</b></p>\n<div>\n    <p>One</p>\n    <p>Two</p>\n</div>\n");
+    }
 }
@@ -0,0 +1,80 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Runs the compiler with the arguments, from the directory of the test fixtures.
+fn pollyc(args: &[&str]) -> Output {
+    let mut binary = env::current_exe().unwrap();
+    binary.pop();
+    if binary.ends_with("deps") {
+        binary.pop();
+    }
+    binary.push(format!("pollyc{}", env::consts::EXE_SUFFIX));
+
+    Command::new(binary)
+        .args(args)
+        .current_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/cli"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn renders_to_stdout() {
+    let output = pollyc(&["page.polly", "--data", "data.json", "--no-locales"]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "<p>Hello Polly</p>\n");
+}
+
+#[test]
+fn renders_to_output() {
+    let path = env::temp_dir().join("pollyc_renders_to_output.html");
+    let output = pollyc(&["page.polly",
+                          "--data",
+                          "data.json",
+                          "--output",
+                          path.to_str().unwrap(),
+                          "--no-locales"]);
+    assert!(output.status.success());
+
+    let mut html = String::new();
+    File::open(&path).unwrap().read_to_string(&mut html).unwrap();
+    assert_eq!(html, "<p>Hello Polly</p>");
+}
+
+#[test]
+fn output_with_multiple_inputs() {
+    let path = env::temp_dir().join("pollyc_output_with_multiple_inputs.html");
+    let _ = fs::remove_file(&path);
+    let output = pollyc(&["page.polly",
+                          "page.polly",
+                          "--data",
+                          "data.json",
+                          "--output",
+                          path.to_str().unwrap(),
+                          "--no-locales"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!path.exists());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(),
+               "--output can only be given with a single input file.\n");
+}
+
+#[test]
+fn missing_variable() {
+    let output = pollyc(&["page.polly", "--no-locales"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("page.polly: "));
+}
+
+#[test]
+fn invalid_data() {
+    let output = pollyc(&["page.polly", "--data", "page.polly", "--no-locales"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("page.polly: Couldn't load the data"));
+}